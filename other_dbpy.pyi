@@ -1,36 +1,280 @@
-from typing import Tuple, Optional
+from typing import Any, TypedDict, AsyncIterator, Literal, Awaitable, Dict, Iterable, Tuple, Optional, Iterator, Union, MutableSet, AbstractSet, Set as PySet
+
+# 哈希表的键,元组的元素也可以是这几种类型
+Key = Union[bytes, str, int, Tuple['Key', ...]]
+
+class OtherDbError(Exception): ...
+class StorageError(OtherDbError): ...
+class DatabaseClosed(StorageError): ...
+class KeyNotFound(OtherDbError, KeyError): ...
+class IndexOutOfRange(OtherDbError, IndexError): ...
+class CorruptMetadata(OtherDbError): ...
+class TupleArityError(OtherDbError): ...
+class InvalidIndexPath(OtherDbError): ...
+class ArchiveNotFound(KeyNotFound): ...
+class TransactionError(OtherDbError): ...
+class TransactionConflict(TransactionError): ...
+class NameConflict(OtherDbError): ...
+
+CollectionKindName = Literal["List", "TupleList", "Hashtable", "SledHashtable", "SortedMap", "Set", "Deque", "Archive"]
+
+class CollectionInfo(TypedDict):
+    name: str
+    kind: CollectionKindName
+    arity: int  # 元组长度,不是元组列表时为 0
+    created_at: int  # 创建时间(unix 毫秒),旧版本的集合为 0
+    size: int  # 和 len() 相同,档案为其内部集合的个数
+    owner: Optional[str]  # 所属的档案
+
+class GcReport(TypedDict):
+    archives: int
+    reachable: int
+    removed: List[Tuple[CollectionKindName, str]]
+    keys: int
+
+class MigrationReport(TypedDict):
+    collections: int
+    archives: int
+    keys: int
+    metadata: int  # 改写为新格式的元数据个数
+    tagged: int  # 中间索引改写为带标记格式的档案个数
+    catalogued: int  # 新登记到目录中的集合个数
+    skipped: List[bytes]  # 无法识别而没有迁移的旧键
 
 class PyKvDbOperaObject:
     def __init__(self) -> None: ...
     def clone(self) -> 'PyKvDbOperaObject': ...
+    def transaction(self) -> 'Transaction': ...
+    def migrate_string_keys(self) -> MigrationReport: ...
+    def rehash_hashtables(self) -> int: ...
+    # 命名空间(sled Tree),不同命名空间中的同名集合互不影响;事务只作用于一个命名空间
+    def open_tree(self, name: str) -> 'PyKvDbOperaObject': ...
+    def drop_tree(self, name: str) -> bool: ...
+    def list_trees(self) -> List[str]: ...
+    def tree_name(self) -> Optional[str]: ...
+    def flush(self) -> int: ...
+    def flush_async(self) -> Awaitable[int]: ...
+    # 关闭后用这个数据库创建的所有对象都会抛出 DatabaseClosed
+    def close(self) -> None: ...
+    def is_closed(self) -> bool: ...
+    # 集合目录,同名的集合有多个(种类不同)时需要指定 kind,否则抛出 NameConflict
+    def collections(self) -> List[CollectionInfo]: ...
+    def describe(self, name: str, kind: Optional[CollectionKindName] = None) -> CollectionInfo: ...
+    def drop(self, name: str, kind: Optional[CollectionKindName] = None) -> None: ...
+    def rename(self, old: str, new: str, kind: Optional[CollectionKindName] = None) -> None: ...
+    def rebuild_catalog(self) -> int: ...
+    # 删除档案中无法到达的中间索引和集合(覆写中间层、删除元素后留下的)
+    def gc(self, dry_run: bool = False) -> GcReport: ...
+    def __enter__(self) -> 'PyKvDbOperaObject': ...
+    def __exit__(self, exc_type: Any, exc_value: Any, traceback: Any) -> bool: ...
+
+class Transaction:
+    def __enter__(self) -> PyKvDbOperaObject: ...
+    def __exit__(self, exc_type, exc_value, traceback) -> bool: ...
+    def commit(self) -> None: ...
+    def rollback(self) -> None: ...
 
 class List:
     def __init__(self, db: PyKvDbOperaObject, name: str) -> None: ...
     # def open(self, db: PyKvDbOperaObject, name: str) -> 'List': ...
-    def append(self, value: bytes) -> bool: ...
+    def append(self, value: bytes) -> None: ...
     def access(self, index: int) -> Optional[bytes]: ...
-    def overwrite(self, index: int, value: bytes) -> bool: ...
-    def delete(self, index: int) -> bool: ...
-    def len(self) -> Optional[int]: ...
+    def overwrite(self, index: int, value: bytes) -> None: ...
+    def delete(self, index: int) -> None: ...
+    def extend(self, values: List[bytes]) -> None: ...
+    def get_range(self, start: int = 0, end: Optional[int] = None) -> List[Optional[bytes]]: ...
+    def insert(self, index: int, value: bytes) -> None: ...
+    def pop(self, index: int = -1) -> Optional[bytes]: ...
+    def remove(self, value: bytes) -> None: ...
+    def compact(self) -> int: ...
+    def truncate(self, len: int) -> None: ...
+    def clear(self) -> None: ...
+    def len(self) -> int: ...
+    def __len__(self) -> int: ...
+    def __getitem__(self, index: int) -> Optional[bytes]: ...
+    def __delitem__(self, index: int) -> None: ...
+    def __contains__(self, value: bytes) -> bool: ...
+    def __iter__(self) -> Iterator[bytes]: ...
+    def __reversed__(self) -> Iterator[bytes]: ...
+    # 异步接口,需要在运行中的事件循环里调用,数据库操作在线程池中执行
+    def aappend(self, value: bytes) -> Awaitable[None]: ...
+    def aaccess(self, index: int) -> Awaitable[Optional[bytes]]: ...
+    def aoverwrite(self, index: int, value: bytes) -> Awaitable[None]: ...
+    def adelete(self, index: int) -> Awaitable[None]: ...
+    def aextend(self, values: List[bytes]) -> Awaitable[None]: ...
+    def aget_range(self, start: int = 0, end: Optional[int] = None) -> Awaitable[List[Optional[bytes]]]: ...
+    def alen(self) -> Awaitable[int]: ...
+    def __aiter__(self) -> AsyncIterator[bytes]: ...
+
+class TupleList:
+    def __init__(self, db: PyKvDbOperaObject, name: str, arity: int) -> None: ...
+    def append(self, value: List[bytes]) -> None: ...
+    def access(self, index: int) -> List[Optional[bytes]]: ...
+    def overwrite(self, index: int, value: List[bytes]) -> None: ...
+    def delete(self, index: int) -> None: ...
+    def len(self) -> int: ...
+    def arity(self) -> int: ...
+    def __len__(self) -> int: ...
+    def __getitem__(self, index: int) -> List[Optional[bytes]]: ...
+    def __contains__(self, value: List[bytes]) -> bool: ...
+    def __iter__(self) -> Iterator[List[Optional[bytes]]]: ...
+    def __reversed__(self) -> Iterator[List[Optional[bytes]]]: ...
+    def aappend(self, value: List[bytes]) -> Awaitable[None]: ...
+    def aaccess(self, index: int) -> Awaitable[List[Optional[bytes]]]: ...
+    def alen(self) -> Awaitable[int]: ...
+    def __aiter__(self) -> AsyncIterator[List[Optional[bytes]]]: ...
 
 class Hashtable:
-    def __init__(self, db: PyKvDbOperaObject, name: str) -> None: ...
+    # backend: "zipper" (拉链法哈希表) 或 "sled" (键有序,支持 range)
+    def __init__(self, db: PyKvDbOperaObject, name: str, backend: str = "zipper") -> None: ...
     # def open(self, db: PyKvDbOperaObject, name: str) -> 'Hashtable': ...
-    def insert(self, key: str, value: bytes) -> bool: ...
-    def get(self, key: str) -> Optional[bytes]: ...
-    def delete(self, key: str) -> bool: ...
-    def to_tuple_list(self, number_of_entries: Optional[int]) -> List[Tuple[str, bytes]]: ...
+    def insert(self, key: Key, value: bytes) -> None: ...
+    def get(self, key: Key) -> Optional[bytes]: ...
+    def delete(self, key: Key) -> None: ...
+    def update(self, items: Union[Dict[Key, bytes], Iterable[Tuple[Key, bytes]]]) -> None: ...
+    def get_many(self, keys: Iterable[Key]) -> List[Optional[bytes]]: ...
+    def delete_many(self, keys: Iterable[Key]) -> int: ...
+    def to_tuple_list(self, number_of_entries: Optional[int]) -> List[Tuple[Key, bytes]]: ...
+    def len(self) -> int: ...
+    def rehash(self) -> bool: ...
+    def set_load_factor(self, percent: int) -> None: ...
+    def range(self, start: Optional[Key] = None, end: Optional[Key] = None) -> Iterator[Tuple[Key, bytes]]: ...
+    def items(self) -> Iterator[Tuple[Key, bytes]]: ...
+    def __len__(self) -> int: ...
+    def __getitem__(self, key: Key) -> bytes: ...
+    def __contains__(self, key: Key) -> bool: ...
+    def __iter__(self) -> Iterator[Key]: ...
+    def __reversed__(self) -> Iterator[Key]: ...
+    def ainsert(self, key: Key, value: bytes) -> Awaitable[None]: ...
+    def aget(self, key: Key) -> Awaitable[Optional[bytes]]: ...
+    def adelete(self, key: Key) -> Awaitable[None]: ...
+    def acontains(self, key: Key) -> Awaitable[bool]: ...
+    def aupdate(self, items: Union[Dict[Key, bytes], Iterable[Tuple[Key, bytes]]]) -> Awaitable[None]: ...
+    def aget_many(self, keys: Iterable[Key]) -> Awaitable[List[Optional[bytes]]]: ...
+    def adelete_many(self, keys: Iterable[Key]) -> Awaitable[int]: ...
+    def alen(self) -> Awaitable[int]: ...
+    def aitems(self) -> AsyncIterator[Tuple[Key, bytes]]: ...
+    def __aiter__(self) -> AsyncIterator[Key]: ...
 
-def open_db(path: str) -> PyKvDbOperaObject: ...
+class SortedMap:
+    # 键有序的映射,int 和 tuple 键按数值和元素顺序排列
+    def __init__(self, db: PyKvDbOperaObject, name: str) -> None: ...
+    def insert(self, key: Key, value: bytes) -> None: ...
+    def get(self, key: Key) -> Optional[bytes]: ...
+    def delete(self, key: Key) -> None: ...
+    def len(self) -> int: ...
+    def range(self, start: Optional[Key] = None, end: Optional[Key] = None, reverse: bool = False) -> Iterator[Tuple[Key, bytes]]: ...
+    def prefix(self, prefix: Key, reverse: bool = False) -> Iterator[Tuple[Key, bytes]]: ...
+    def first(self) -> Optional[Tuple[Key, bytes]]: ...
+    def last(self) -> Optional[Tuple[Key, bytes]]: ...
+    def floor(self, key: Key) -> Optional[Tuple[Key, bytes]]: ...
+    def ceiling(self, key: Key) -> Optional[Tuple[Key, bytes]]: ...
+    def items(self) -> Iterator[Tuple[Key, bytes]]: ...
+    def __len__(self) -> int: ...
+    # m[key] 或 m[start:end] / m[start:end:-1]
+    def __getitem__(self, key: Union[Key, slice]) -> Union[bytes, List[Tuple[Key, bytes]]]: ...
+    def __contains__(self, key: Key) -> bool: ...
+    def __iter__(self) -> Iterator[Key]: ...
+    def __reversed__(self) -> Iterator[Key]: ...
+
+class Set(MutableSet[Key]):
+    # 成员按字节顺序排列,集合运算返回 python 的 set
+    def __init__(self, db: PyKvDbOperaObject, name: str) -> None: ...
+    def add(self, member: Key) -> None: ...
+    def discard(self, member: Key) -> None: ...
+    def remove(self, member: Key) -> None: ...
+    def pop(self) -> Key: ...
+    def clear(self) -> None: ...
+    def len(self) -> int: ...
+    # 集合运算的参数可以是任意可迭代对象(包括另一个 Set)
+    def union(self, other: Iterable[Key]) -> PySet[Key]: ...
+    def intersection(self, other: Iterable[Key]) -> PySet[Key]: ...
+    def difference(self, other: Iterable[Key]) -> PySet[Key]: ...
+    def symmetric_difference(self, other: Iterable[Key]) -> PySet[Key]: ...
+    def isdisjoint(self, other: Iterable[Key]) -> bool: ...
+    def __or__(self, other: Iterable[Key]) -> PySet[Key]: ...
+    def __ror__(self, other: Iterable[Key]) -> PySet[Key]: ...
+    def __and__(self, other: Iterable[Key]) -> PySet[Key]: ...
+    def __rand__(self, other: Iterable[Key]) -> PySet[Key]: ...
+    def __sub__(self, other: Iterable[Key]) -> PySet[Key]: ...
+    def __rsub__(self, other: Iterable[Key]) -> PySet[Key]: ...
+    def __xor__(self, other: Iterable[Key]) -> PySet[Key]: ...
+    def __rxor__(self, other: Iterable[Key]) -> PySet[Key]: ...
+    # 原地修改数据库中的集合,每个运算在一个事务中完成
+    def __ior__(self, other: Iterable[Key]) -> 'Set': ...
+    def __iand__(self, other: Iterable[Key]) -> 'Set': ...
+    def __isub__(self, other: Iterable[Key]) -> 'Set': ...
+    def __ixor__(self, other: Iterable[Key]) -> 'Set': ...
+    # 只和集合(collections.abc.Set)比较
+    def __le__(self, other: AbstractSet[Any]) -> bool: ...
+    def __lt__(self, other: AbstractSet[Any]) -> bool: ...
+    def __ge__(self, other: AbstractSet[Any]) -> bool: ...
+    def __gt__(self, other: AbstractSet[Any]) -> bool: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __len__(self) -> int: ...
+    def __contains__(self, member: Key) -> bool: ...
+    def __iter__(self) -> Iterator[Key]: ...
+    def __reversed__(self) -> Iterator[Key]: ...
+
+class Deque:
+    # 双端队列,可以作为持久化的任务队列: 生产者 push_back, 消费者 pop
+    def __init__(self, db: PyKvDbOperaObject, name: str) -> None: ...
+    def push_back(self, value: bytes) -> None: ...
+    def push_front(self, value: bytes) -> None: ...
+    def pop_back(self) -> Optional[bytes]: ...
+    def pop_front(self) -> Optional[bytes]: ...
+    def peek_back(self) -> Optional[bytes]: ...
+    def peek_front(self) -> Optional[bytes]: ...
+    # 队列为空时等待,超时返回 None;timeout 为 None 时一直等待
+    def pop(self, timeout: Optional[float] = None) -> Optional[bytes]: ...
+    def len(self) -> int: ...
+    def __len__(self) -> int: ...
+    def __iter__(self) -> Iterator[bytes]: ...
+    def __reversed__(self) -> Iterator[bytes]: ...
+
+# compression 需要以 compression feature 编译
+def open_db(path: str, *, cache_capacity: Optional[int] = None, flush_every_ms: Optional[int] = 500,
+            mode: Literal["LowSpace", "HighThroughput"] = "LowSpace", temporary: bool = False,
+            compression: bool = False) -> PyKvDbOperaObject: ...
 def archive_new(db: PyKvDbOperaObject, name: str) -> Archive: ...
 def archive_open(db: PyKvDbOperaObject, name: str) -> Archive: ...
 
+# 档案的路径: 路径字符串或者 [("Hashtable", ["users"]), ("List", ["8"])] 这样的列表
+# 路径字符串的每一层为 "/种类:参数": h:键 sh:键 s:成员 l:列表索引 t:列表索引,元组索引,元组长度
+# 例如 "/h:users/l:8/t:8,1,2",键中的 '/' 和 '\' 写作 "\/" 和 "\\"
+IndexPath = Union[str, List[Tuple[str, List[str]]]]
+ArchiveKey = Union[str, int, Tuple[int, int, int]]
+
 class Archive:
     def __init__(self) -> None: ...
-    def new(self, py_index_list: List[Tuple[str, List[str]]]) -> EAR: ...
-    def open(self, py_index_list: List[Tuple[str, List[str]]]) -> EAR: ...
+    def new(self, py_index_list: IndexPath) -> EAR: ...
+    def open(self, py_index_list: IndexPath) -> EAR: ...
+    def delete_subtree(self, py_index_list: IndexPath) -> None: ...
+    def drop(self) -> None: ...
+    # archive["users"][8]: str 为哈希表的键,int 为列表索引,(列表索引, 元组索引, 元组长度) 为元组列表
+    # 下标只能表示这三种层;集合(s:)和 sled 哈希表(sh:)的层需要用 new/open 的路径访问
+    def __getitem__(self, key: ArchiveKey) -> 'ArchiveNode': ...
+    def __setitem__(self, key: ArchiveKey, value: bytes) -> None: ...
+    def __delitem__(self, key: ArchiveKey) -> None: ...
+    def anew(self, py_index_list: IndexPath) -> Awaitable[EAR]: ...
+    def aopen(self, py_index_list: IndexPath) -> Awaitable[EAR]: ...
+
+class ArchiveNode:
+    # 只记录路径,读写时才访问数据库;写入时缺少的层自动创建,已经存在的层保持不变
+    # 路径经过的某一层储存的是数据而不是下一层时,写入抛出 InvalidIndexPath,数据保持不变
+    path: str
+    def __getitem__(self, key: ArchiveKey) -> 'ArchiveNode': ...
+    def __setitem__(self, key: ArchiveKey, value: bytes) -> None: ...
+    def __delitem__(self, key: ArchiveKey) -> None: ...  # 同时删除下面的所有层
+    def access(self) -> Optional[bytes]: ...
+    def get(self, default: Any = None) -> Any: ...
+    def overwrite(self, value: bytes) -> None: ...
+    def delete(self) -> None: ...
 
 class EAR:
     def access(self) -> Optional[bytes]: ...
-    def delete(self) -> Optional[bool]: ...
-    def overwrite(self, data: bytes) -> Optional[bool]: ...
\ No newline at end of file
+    def delete(self) -> None: ...
+    def overwrite(self, data: bytes) -> None: ...
+    def aaccess(self) -> Awaitable[Optional[bytes]]: ...
+    def adelete(self) -> Awaitable[None]: ...
+    def aoverwrite(self, data: bytes) -> Awaitable[None]: ...
\ No newline at end of file
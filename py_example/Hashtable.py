@@ -26,6 +26,6 @@ print("查询耗时",t2-t1)
 print("将hash表转化为列表结果:",data.to_tuple_list())
 
 data.delete(user_name)
-pwd.delete(pwd.len())
+pwd.delete(pwd.len() - 1)
 
 print(data.get('xingzhi'))
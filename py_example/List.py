@@ -19,8 +19,13 @@ t2 = time.time()
 print(t2 - t1)
 
 t1 = time.time()
-for i in range(a.len()):  # 遍历访问所有元素
-    print(a.access(i))
+a.extend([[1, 1, 4, 5, 1, 4]] * 100)  # 批量追加,一次调用写入所有元素
+t2 = time.time()
+print(t2 - t1)
+
+t1 = time.time()
+for value in a:  # 遍历访问所有元素(跳过被删除的位置)
+    print(value)
 t2 = time.time()
 print(t2 - t1)  # 计时
 
@@ -0,0 +1,32 @@
+import asyncio
+
+import other_dbpy
+
+# a 开头的方法返回 awaitable,数据库操作在后台线程中执行,不会阻塞事件循环
+
+
+async def main():
+    db = other_dbpy.open_db(r"D:\tmp\welcome-to-sled")
+    data = other_dbpy.Hashtable(db, "用户名索引")
+    pwd = other_dbpy.List(db, "密码")
+
+    await pwd.aappend(b"aaa")
+    await data.ainsert("xingzhi", str(await pwd.alen() - 1).encode())
+    print("查询结果:", await data.aget("xingzhi"))
+
+    # 多个操作可以同时进行
+    await asyncio.gather(*(data.ainsert(("user", i), b"1") for i in range(100)))
+    print("条目个数:", await data.alen())
+
+    async for key, value in data.aitems():  # 异步迭代 (键, 值)
+        print(key, value)
+    async for value in pwd:  # 异步迭代列表(跳过被删除的位置)
+        print(value)
+
+    archive = other_dbpy.archive_new(db, "asyncio_example")
+    ear = await archive.anew([("Hashtable", ["xingzhi"])])
+    await ear.aoverwrite(b"archive data")
+    print("档案:", await ear.aaccess())
+
+
+asyncio.run(main())
@@ -0,0 +1,73 @@
+import os
+import shutil
+import sys
+import tempfile
+import threading
+import time
+
+import other_dbpy
+
+# 多线程压力测试: 数据库操作执行时会释放 GIL,多个 python 线程可以同时读写
+# 用法: python stress.py [线程数] [每个线程的操作次数]
+
+THREADS = int(sys.argv[1]) if len(sys.argv) > 1 else 4
+OPS = int(sys.argv[2]) if len(sys.argv) > 2 else 500
+
+
+def worker(db, run_name, n, errors):
+    try:
+        table = other_dbpy.Hashtable(db, "stress")
+        items = other_dbpy.List(db, f"{run_name}-{n}")
+        for i in range(OPS):
+            key = (n, i)
+            table.insert(key, i.to_bytes(4, "big"))
+            items.append(key[1].to_bytes(4, "big"))
+            assert bytes(table.get(key)) == i.to_bytes(4, "big")
+        assert items.len() == OPS
+    except Exception as e:  # 在主线程里统一报告
+        errors.append(e)
+
+
+def run(db, run_name, threads):
+    errors = []
+    workers = [threading.Thread(target=worker, args=(db.clone(), run_name, n, errors)) for n in range(threads)]
+    t1 = time.time()
+    for w in workers:
+        w.start()
+    for w in workers:
+        w.join()
+    elapsed = time.time() - t1
+    if errors:
+        raise errors[0]
+    return elapsed
+
+
+def heartbeat(stop, ticks):
+    # 数据库操作持有 GIL 时这个线程无法运行
+    while not stop.is_set():
+        ticks.append(time.time())
+        time.sleep(0.001)
+
+
+if __name__ == "__main__":
+    path = tempfile.mkdtemp()
+    try:
+        db = other_dbpy.open_db(os.path.join(path, "stress"))
+        stop, ticks = threading.Event(), []
+        beat = threading.Thread(target=heartbeat, args=(stop, ticks))
+        beat.start()
+        try:
+            single = run(db, "single", 1) * THREADS  # 单线程完成同样多的操作需要的时间
+            multi = run(db, "multi", THREADS)
+        finally:
+            stop.set()
+            beat.join()
+
+        table = other_dbpy.Hashtable(db, "stress")
+        assert len(table) == THREADS * OPS, len(table)  # 没有丢失的写入
+        gaps = max(b - a for a, b in zip(ticks, ticks[1:]))
+        print(f"{THREADS} threads x {OPS} ops")
+        print(f"single thread (estimated): {single:.2f}s, {THREADS} threads: {multi:.2f}s, speedup {single / multi:.2f}x")
+        print(f"longest heartbeat gap: {gaps * 1000:.1f}ms")
+    finally:
+        shutil.rmtree(path)
@@ -1,23 +1,233 @@
 use pyo3::prelude::*;
-mod sdk;
+use pyo3::basic::CompareOp;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIndexError, PyKeyError, PyStopAsyncIteration, PyStopIteration, PyTypeError, PyValueError};
+use pyo3::sync::GILOnceCell;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+use std::time::{Duration, Instant};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use pyo3::types::{PyBytes, PyDict, PyLong, PySet, PySlice, PyString, PyTuple, PyType};
+pub mod sdk;
 
 use sdk::db::kv_operation;
-use crate::sdk::db::list_db::ListDb;
-use sdk::db::hashtable_zipper_db::Hashtable as HashtableDb;
+use crate::sdk::db::list_db::{ListDb, ListIter};
+use sdk::db::tuple_list_db::{TupleList as TupleListDb, TupleListIter};
+use sdk::db::hashtable_zipper_db::{Hashtable as HashtableDb, HashtableIter};
+use sdk::db::hashtable_sled_db::Hashtable as SledHashtableDb;
+use sdk::db::sorted_map_db::{SortedMap as SortedMapDb, Entry};
+use sdk::db::set_db::{Set as SetDb, SetIter};
+use sdk::db::deque_db::{Deque as DequeDb, DequeIter};
+use sdk::db::typed_key::TypedKey;
 use crate::sdk::db::kv_operation::{ KvDbOperaTrait};
 use sdk::archive;
+use sdk::catalog::{Catalog, CollectionInfo};
+use sdk::db::metadata::CollectionKind;
+use sdk::error::{DbError, DbResult};
+
+create_exception!(other_dbpy, OtherDbError, PyException); // 所有数据库异常的基类
+create_exception!(other_dbpy, StorageError, OtherDbError);
+create_exception!(other_dbpy, DatabaseClosed, StorageError);
+create_exception!(other_dbpy, CorruptMetadata, OtherDbError);
+create_exception!(other_dbpy, TupleArityError, OtherDbError);
+create_exception!(other_dbpy, InvalidIndexPath, OtherDbError);
+create_exception!(other_dbpy, TransactionError, OtherDbError);
+create_exception!(other_dbpy, TransactionConflict, TransactionError);
+create_exception!(other_dbpy, NameConflict, OtherDbError);
+
+// KeyNotFound / IndexOutOfRange 同时是 KeyError / IndexError,`except KeyError` 也能捕获
+// create_exception! 只支持一个基类,这几个异常用 type() 创建
+static KEY_NOT_FOUND: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+static INDEX_OUT_OF_RANGE: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+static ARCHIVE_NOT_FOUND: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+fn exception_type<'py>(py:Python<'py>, cell:&'static GILOnceCell<Py<PyType>>, name:&str, bases:&[&'py PyType]) -> &'py PyType {
+    cell.get_or_init(py, || {
+        let namespace = PyDict::new(py);
+        namespace.set_item("__module__", "other_dbpy").expect("Failed to build exception namespace");
+        py.get_type::<PyType>().call1((name, PyTuple::new(py, bases), namespace))
+            .and_then(|t| Ok(t.downcast::<PyType>()?.into()))
+            .unwrap_or_else(|e| panic!("Failed to create exception {name}: {e}"))
+    }).as_ref(py)
+}
+
+fn key_not_found(py:Python<'_>) -> &PyType {
+    exception_type(py, &KEY_NOT_FOUND, "KeyNotFound", &[py.get_type::<OtherDbError>(), py.get_type::<PyKeyError>()])
+}
+
+fn index_out_of_range(py:Python<'_>) -> &PyType {
+    exception_type(py, &INDEX_OUT_OF_RANGE, "IndexOutOfRange", &[py.get_type::<OtherDbError>(), py.get_type::<PyIndexError>()])
+}
+
+fn archive_not_found(py:Python<'_>) -> &PyType {
+    exception_type(py, &ARCHIVE_NOT_FOUND, "ArchiveNotFound", &[key_not_found(py)])
+}
+
+impl From<DbError> for PyErr {
+    fn from(e: DbError) -> Self {
+        // 把 sdk 的错误映射为对应的 python 异常
+        let msg = e.to_string();
+        match e {
+            DbError::Storage(_) => StorageError::new_err(msg),
+            DbError::NotFound(_) => Python::with_gil(|py| PyErr::from_type(key_not_found(py), msg)),
+            DbError::OutOfBounds { .. } => Python::with_gil(|py| PyErr::from_type(index_out_of_range(py), msg)),
+            DbError::CorruptMetadata(_) => CorruptMetadata::new_err(msg),
+            DbError::TupleArity { .. } => TupleArityError::new_err(msg),
+            DbError::InvalidIndexPath(_) => InvalidIndexPath::new_err(msg),
+            DbError::ArchiveNotFound(_) => Python::with_gil(|py| PyErr::from_type(archive_not_found(py), msg)),
+            DbError::TransactionConflict => TransactionConflict::new_err(msg),
+            DbError::TransactionClosed => TransactionError::new_err(msg),
+            DbError::Closed => DatabaseClosed::new_err(msg),
+            DbError::NameConflict(_) => NameConflict::new_err(msg),
+        }
+    }
+}
+
+fn unlocked<T, F>(py:Python<'_>, f:F) -> PyResult<T> where F:Send + FnOnce() -> DbResult<T>, T:Send {
+    // 释放 GIL 执行数据库操作,sled 读写磁盘时其他 python 线程可以继续运行
+    // f 中不能使用 python 对象,参数要在调用前转换好
+    Ok(py.allow_threads(f)?)
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+fn pool() -> &'static Mutex<mpsc::Sender<Job>> {
+    // 执行异步接口的线程池,第一次使用时创建
+    static POOL: OnceLock<Mutex<mpsc::Sender<Job>>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = std::thread::available_parallelism().map_or(4, |n| n.get()).max(2);
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            std::thread::Builder::new().name(format!("other_dbpy-{i}")).spawn(move || loop {
+                let job = match receiver.lock().unwrap_or_else(|e| e.into_inner()).recv() {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+                job();
+            }).expect("Failed to spawn other_dbpy worker thread");
+        }
+        Mutex::new(sender)
+    })
+}
+
+fn offload<T, F>(py:Python<'_>, f:F) -> PyResult<PyObject> where F:Send + 'static + FnOnce() -> DbResult<T>, T:Send + 'static + IntoPy<PyObject> {
+    offload_with(py, f, |py, v| Ok(v.into_py(py)))
+}
+
+fn offload_with<T, F, C>(py:Python<'_>, f:F, convert:C) -> PyResult<PyObject>
+    where F:Send + 'static + FnOnce() -> DbResult<T>, T:Send + 'static, C:Send + 'static + FnOnce(Python<'_>, T) -> PyResult<PyObject> {
+    // 异步接口: 在线程池中执行 f,返回当前事件循环的 future
+    // f 不持有 GIL;convert 持有 GIL,把结果转换为 python 对象
+    let event_loop: PyObject = py.import("asyncio")?.call_method0("get_running_loop")?.into();
+    let future: PyObject = event_loop.call_method0(py, "create_future")?;
+    let ret = future.clone_ref(py);
+    let job: Job = Box::new(move || {
+        let result = f();
+        Python::with_gil(|py| {
+            let result = result.map_err(PyErr::from).and_then(|v| convert(py, v));
+            // 事件循环已经关闭时结果没人等待,直接丢弃
+            if let Ok(resolve) = Py::new(py, FutureResolver { future, result: Some(result) }) {
+                let _ = event_loop.call_method1(py, "call_soon_threadsafe", (resolve,));
+            }
+        })
+    });
+    pool().lock().unwrap_or_else(|e| e.into_inner()).send(job)
+        .map_err(|_| PyException::new_err("other_dbpy worker threads have stopped"))?;
+    Ok(ret)
+}
+
+#[pyclass]
+struct FutureResolver {
+    // 在事件循环的线程中设置 future 的结果
+    future : PyObject,
+    result : Option<PyResult<PyObject>>,
+}
+
+#[pymethods]
+impl FutureResolver {
+    fn __call__(&mut self, py:Python<'_>) -> PyResult<()> {
+        if self.future.call_method0(py, "done")?.is_truthy(py)? { // 已经被取消
+            return Ok(())
+        }
+        match self.result.take() {
+            Some(Ok(v)) => self.future.call_method1(py, "set_result", (v,))?,
+            Some(Err(e)) => self.future.call_method1(py, "set_exception", (e.into_value(py),))?,
+            None => return Ok(()),
+        };
+        Ok(())
+    }
+}
+
+#[pyclass]
+struct AsyncIterator {
+    // 把同步迭代器包装为异步迭代器,每一项都在线程池中读取
+    // 同时等待多个 __anext__ 时,用锁保证一次只有一个线程调用 __next__
+    iter : Arc<Mutex<PyObject>>,
+}
+
+#[pymethods]
+impl AsyncIterator {
+    fn __aiter__(slf:PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    fn __anext__(&self, py:Python<'_>) -> PyResult<Option<PyObject>> {
+        let iter = self.iter.clone();
+        let next = offload_with(py, move || {
+            // 先拿到锁再获取 GIL,__next__ 读取数据库时会释放 GIL
+            let iter = iter.lock().unwrap_or_else(|e| e.into_inner());
+            Ok(Python::with_gil(|py| iter.call_method0(py, "__next__")))
+        }, |py, next| match next {
+            Err(e) if e.is_instance_of::<PyStopIteration>(py) => Err(PyStopAsyncIteration::new_err(())),
+            ret => ret,
+        })?;
+        Ok(Some(next))
+    }
+}
+
+fn async_iter(py:Python<'_>, iter:impl IntoPy<PyObject>) -> AsyncIterator {
+    AsyncIterator { iter : Arc::new(Mutex::new(iter.into_py(py))) }
+}
 
 /// A Python module implemented in Rust.
 #[pymodule]
-fn other_dbpy(_py: Python, m: &PyModule) -> PyResult<()> {
+fn other_dbpy(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("OtherDbError", py.get_type::<OtherDbError>())?;
+    m.add("StorageError", py.get_type::<StorageError>())?;
+    m.add("DatabaseClosed", py.get_type::<DatabaseClosed>())?;
+    m.add("KeyNotFound", key_not_found(py))?;
+    m.add("IndexOutOfRange", index_out_of_range(py))?;
+    m.add("CorruptMetadata", py.get_type::<CorruptMetadata>())?;
+    m.add("TupleArityError", py.get_type::<TupleArityError>())?;
+    m.add("InvalidIndexPath", py.get_type::<InvalidIndexPath>())?;
+    m.add("ArchiveNotFound", archive_not_found(py))?;
+    m.add("TransactionError", py.get_type::<TransactionError>())?;
+    m.add("TransactionConflict", py.get_type::<TransactionConflict>())?;
+    m.add("NameConflict", py.get_type::<NameConflict>())?;
+
     m.add_function(wrap_pyfunction!(open_db, m)?)?;
     m.add_function(wrap_pyfunction!(archive_new, m)?)?;
     m.add_function(wrap_pyfunction!(archive_open, m)?)?;
 
     m.add_class::<PyKvDbOperaObject>()?;
+    m.add_class::<Transaction>()?;
     m.add_class::<List>()?;
+    m.add_class::<ListIterator>()?;
+    m.add_class::<TupleList>()?;
+    m.add_class::<TupleListIterator>()?;
     m.add_class::<Hashtable>()?;
+    m.add_class::<HashtableIterator>()?;
+    m.add_class::<SortedMap>()?;
+    m.add_class::<Set>()?;
+    m.add_class::<SetIterator>()?;
+    m.add_class::<Deque>()?;
+    m.add_class::<DequeIterator>()?;
+    m.add_class::<AsyncIterator>()?;
+    // Set 实现了 MutableSet 的协议,注册后 isinstance(s, MutableSet) 为 True
+    py.import("collections.abc")?.getattr("MutableSet")?.call_method1("register", (m.getattr("Set")?,))?;
     m.add_class::<Archive>()?;
+    m.add_class::<ArchiveNode>()?;
     m.add_class::<EAR>()?;
 
     Ok(())
@@ -25,8 +235,17 @@ fn other_dbpy(_py: Python, m: &PyModule) -> PyResult<()> {
 
 
 #[pyfunction]
-fn open_db(path:String) -> PyKvDbOperaObject{
-    PyKvDbOperaObject{ db : kv_operation::KvDbOpera::new(kv_operation::initialization(path)) }
+#[pyo3(signature = (path, *, cache_capacity=None, flush_every_ms=500, mode="LowSpace", temporary=false, compression=false))]
+fn open_db(py:Python<'_>,path:String,cache_capacity:Option<u64>,flush_every_ms:Option<u64>,mode:&str,temporary:bool,compression:bool) -> PyResult<PyKvDbOperaObject>{
+    // flush_every_ms 为 None 时不在后台写入硬盘,需要自己调用 flush
+    let mode = match mode {
+        "LowSpace" => kv_operation::Mode::LowSpace,
+        "HighThroughput" => kv_operation::Mode::HighThroughput,
+        other => return Err(PyValueError::new_err(format!("unknown mode: {other}, expected LowSpace or HighThroughput"))),
+    };
+    let config = kv_operation::Config { cache_capacity, flush_every_ms, mode, temporary, compression };
+    let db = unlocked(py, || kv_operation::open(path, &config))?;
+    Ok(PyKvDbOperaObject{ db : kv_operation::KvDbOpera::new(db) })
 }
 
 #[derive(Clone)]
@@ -38,7 +257,150 @@ struct PyKvDbOperaObject{
 impl PyKvDbOperaObject {
     fn clone(&self) -> Self {
         // 在python克隆自己,我是线程安全的
-        self.clone()
+        Clone::clone(self)
+    }
+    fn migrate_string_keys(&self,py:Python<'_>) -> PyResult<PyObject> {
+        // 把旧版本的字符串键迁移为二进制键,改写旧格式的元数据和档案索引并登记到目录中,返回迁移报告
+        let report = unlocked(py, || sdk::migration::migrate_string_keys(&self.db))?;
+        let dict = PyDict::new(py);
+        dict.set_item("collections", report.collections)?;
+        dict.set_item("archives", report.archives)?;
+        dict.set_item("keys", report.keys)?;
+        dict.set_item("metadata", report.metadata)?;
+        dict.set_item("tagged", report.tagged)?;
+        dict.set_item("catalogued", report.catalogued)?;
+        dict.set_item("skipped", report.skipped.into_iter().map(|key| PyBytes::new(py, &key)).collect::<Vec<_>>())?;
+        Ok(dict.into())
+    }
+    fn rehash_hashtables(&self,py:Python<'_>) -> PyResult<usize> {
+        // 用固定的哈希函数重建旧版本的哈希表,返回重建的个数
+        unlocked(py, || sdk::migration::rehash_hashtables(&self.db))
+    }
+    fn transaction(&self) -> Transaction {
+        // with db.transaction() as tx: 用 tx 创建的对象的所有写入在退出时一起提交
+        Transaction { db : self.db.begin(), nested : self.db.in_transaction() }
+    }
+    fn open_tree(&self,py:Python<'_>,name:String) -> PyResult<Self> {
+        // 命名空间: 用返回的对象创建的集合保存在单独的 sled Tree 中,和其他命名空间的同名集合互不影响
+        let db = unlocked(py, || self.db.open_tree(&name))?;
+        Ok(PyKvDbOperaObject { db })
+    }
+    fn drop_tree(&self,py:Python<'_>,name:String) -> PyResult<bool> {
+        // 删除整个命名空间及其中的所有集合,返回是否存在
+        unlocked(py, || self.db.drop_tree(&name))
+    }
+    fn list_trees(&self,py:Python<'_>) -> PyResult<Vec<String>> {
+        unlocked(py, || self.db.list_trees())
+    }
+    fn tree_name(&self) -> Option<&str> {
+        self.db.tree_name()
+    }
+    fn flush(&self,py:Python<'_>) -> PyResult<usize> {
+        // 阻塞直到所有写入都保存到硬盘,返回写入的字节数
+        unlocked(py, || self.db.flush())
+    }
+    fn flush_async(&self,py:Python<'_>) -> PyResult<PyObject> {
+        let db = self.db.clone();
+        offload(py, move || db.flush())
+    }
+    fn close(&self,py:Python<'_>) -> PyResult<()> {
+        // 写入硬盘并关闭,之后用这个数据库创建的所有对象都会抛出 DatabaseClosed
+        unlocked(py, || self.db.close())
+    }
+    fn is_closed(&self) -> bool {
+        self.db.is_closed()
+    }
+    fn collections(&self,py:Python<'_>) -> PyResult<Vec<PyObject>> {
+        // 所有集合和档案的信息(不包括档案内部的集合)
+        let infos = unlocked(py, || self.db.collections())?;
+        infos.into_iter().map(|info| collection_info(py, info)).collect()
+    }
+    #[pyo3(signature = (name, kind=None))]
+    fn describe(&self,py:Python<'_>,name:String,kind:Option<&str>) -> PyResult<PyObject> {
+        // 同名的集合有多个时需要指定 kind,否则抛出 NameConflict
+        let kind = collection_kind(kind)?;
+        let info = unlocked(py, || self.db.describe(&name, kind))?;
+        collection_info(py, info)
+    }
+    #[pyo3(signature = (name, kind=None))]
+    fn drop(&self,py:Python<'_>,name:String,kind:Option<&str>) -> PyResult<()> {
+        // 删除集合的所有数据,删除档案时同时删除档案中的所有集合
+        let kind = collection_kind(kind)?;
+        unlocked(py, || self.db.drop(&name, kind))
+    }
+    #[pyo3(signature = (old, new, kind=None))]
+    fn rename(&self,py:Python<'_>,old:String,new:String,kind:Option<&str>) -> PyResult<()> {
+        // 新名字已被同种集合使用时抛出 NameConflict
+        let kind = collection_kind(kind)?;
+        unlocked(py, || self.db.rename(&old, &new, kind))
+    }
+    #[pyo3(signature = (dry_run=false))]
+    fn gc(&self,py:Python<'_>,dry_run:bool) -> PyResult<PyObject> {
+        // 删除档案中无法到达的中间索引和集合,dry_run 时只返回将要删除的内容
+        let report = unlocked(py, || archive::gc(&self.db, dry_run))?;
+        let dict = PyDict::new(py);
+        dict.set_item("archives", report.archives)?;
+        dict.set_item("reachable", report.reachable)?;
+        dict.set_item("removed", report.removed.into_iter().map(|(kind, name)| (kind.name(), name)).collect::<Vec<_>>())?;
+        dict.set_item("keys", report.keys)?;
+        Ok(dict.into())
+    }
+    fn rebuild_catalog(&self,py:Python<'_>) -> PyResult<usize> {
+        // 登记目录出现之前创建的集合,返回新登记的个数
+        unlocked(py, || sdk::catalog::register_existing(&self.db))
+    }
+    fn __enter__(slf:PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    fn __exit__(&self, py:Python<'_>, _exc_type:Option<&PyAny>, _exc_value:Option<&PyAny>, _traceback:Option<&PyAny>) -> PyResult<bool> {
+        // with open_db(...) as db: 退出时关闭数据库
+        self.close(py)?;
+        Ok(false)
+    }
+}
+
+fn collection_kind(kind:Option<&str>) -> PyResult<Option<CollectionKind>> {
+    // python 中用类名表示集合的种类
+    kind.map(|name| CollectionKind::from_name(name).ok_or_else(|| PyValueError::new_err(format!("unknown collection kind: {name}")))).transpose()
+}
+
+fn collection_info(py:Python<'_>, info:CollectionInfo) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("name", info.name)?;
+    dict.set_item("kind", info.kind.name())?;
+    dict.set_item("arity", info.arity)?;
+    dict.set_item("created_at", info.created_at)?;
+    dict.set_item("size", info.size)?;
+    dict.set_item("owner", info.owner)?;
+    Ok(dict.into())
+}
+
+#[pyclass]
+struct Transaction {
+    db : kv_operation::KvDbOperaObject, // 绑定了事务的数据库对象
+    nested : bool, // 在事务中再开事务时直接加入外层事务,由外层负责提交
+}
+
+#[pymethods]
+impl Transaction {
+    fn __enter__(&self) -> PyKvDbOperaObject {
+        PyKvDbOperaObject { db : self.db.clone() }
+    }
+    fn __exit__(&self, py:Python<'_>, exc_type:Option<&PyAny>, _exc_value:Option<&PyAny>, _traceback:Option<&PyAny>) -> PyResult<bool> {
+        // 没有异常时提交,有异常时回滚,异常继续向外抛出
+        match exc_type {
+            None => self.commit(py)?,
+            Some(_) => self.rollback(py)?,
+        }
+        Ok(false)
+    }
+    fn commit(&self,py:Python<'_>) -> PyResult<()> {
+        if self.nested { return Ok(()) }
+        unlocked(py, || self.db.commit())
+    }
+    fn rollback(&self,py:Python<'_>) -> PyResult<()> {
+        if self.nested { return Ok(()) }
+        unlocked(py, || self.db.rollback())
     }
 }
 
@@ -51,89 +413,914 @@ struct List {
 #[pymethods]
 impl List {
     #[new]
-    fn new(db:PyKvDbOperaObject,name:String) -> Self {
-        List{ list_db_obj: ListDb::new(db.db.clone(),name).unwrap() }
+    fn new(py:Python<'_>,db:PyKvDbOperaObject,name:String) -> PyResult<Self> {
+        Ok(List{ list_db_obj: unlocked(py, || ListDb::new(db.db.clone(),name))? })
     }
 
     // fn open(db:PyKvDbOperaObject,name:String) -> Self {
     //     List{ list_db_obj: ListDb::open(db.db.clone(),name).unwrap() }
     // }
 
-    fn append(&self,value:Vec<u8>) -> bool {
-        match self.list_db_obj.append(&value) {
-            Ok(_) => true,
-            Err(_) => false,
+    fn append(&self,py:Python<'_>,value:Vec<u8>) -> PyResult<()> {
+        unlocked(py, || self.list_db_obj.append(&value))
+    }
+    fn access(&self,py:Python<'_>,index:usize) -> PyResult<Option<Vec<u8>>> {
+        unlocked(py, || self.list_db_obj.access(index))
+    }
+    fn overwrite(&self,py:Python<'_>,index:usize,value:Vec<u8>) -> PyResult<()> {
+        unlocked(py, || self.list_db_obj.overwrite(index,&value))
+    }
+    fn delete(&self,py:Python<'_>,index:usize) -> PyResult<()> {
+        unlocked(py, || self.list_db_obj.delete(index))
+    }
+    fn extend(&self,py:Python<'_>,values:Vec<Vec<u8>>) -> PyResult<()> {
+        // 批量追加,在一个事务中完成
+        unlocked(py, || self.list_db_obj.extend(&values))
+    }
+    #[pyo3(signature = (start=0, end=None))]
+    fn get_range(&self,py:Python<'_>,start:usize,end:Option<usize>) -> PyResult<Vec<Option<Vec<u8>>>> {
+        // 读取 [start, end) 的元素,end 为 None 时读到末尾
+        unlocked(py, || self.list_db_obj.get_range(start, end.unwrap_or(usize::MAX)))
+    }
+    fn insert(&self,py:Python<'_>,index:isize,value:Vec<u8>) -> PyResult<()> {
+        // 和 list.insert 一样,index 超出范围时插入到开头或末尾
+        unlocked(py, || {
+            let length = self.list_db_obj.length()?;
+            let index = if index < 0 { (index + length as isize).max(0) as usize } else { (index as usize).min(length) };
+            self.list_db_obj.insert(index,&value)
+        })
+    }
+    #[pyo3(signature = (index=-1))]
+    fn pop(&self,py:Python<'_>,index:isize) -> PyResult<Option<Vec<u8>>> {
+        // 取出元素并让后面的元素前移,取出的是被删除的位置时返回 None
+        unlocked(py, || self.list_db_obj.pop(py_index(index,self.list_db_obj.length()?)?))
+    }
+    fn remove(&self,py:Python<'_>,value:Vec<u8>) -> PyResult<()> {
+        // 和 list.remove 一样,不存在时抛出 ValueError
+        if !unlocked(py, || self.list_db_obj.remove(&value))? {
+            return Err(PyValueError::new_err("List.remove(x): x not in list"))
+        }
+        Ok(())
+    }
+    fn compact(&self,py:Python<'_>) -> PyResult<usize> {
+        // 去掉被删除的位置,返回去掉的个数
+        unlocked(py, || self.list_db_obj.compact())
+    }
+    fn truncate(&self,py:Python<'_>,len:usize) -> PyResult<()> {
+        unlocked(py, || self.list_db_obj.truncate(len))
+    }
+    fn clear(&self,py:Python<'_>) -> PyResult<()> {
+        unlocked(py, || self.list_db_obj.clear())
+    }
+    fn len(&self,py:Python<'_>) -> PyResult<usize> {
+        unlocked(py, || self.list_db_obj.length())
+    }
+    fn __len__(&self,py:Python<'_>) -> PyResult<usize> {
+        self.len(py)
+    }
+    fn __getitem__(&self,py:Python<'_>,index:isize) -> PyResult<Option<Vec<u8>>> {
+        // 支持负数索引,被删除的位置返回 None
+        unlocked(py, || self.list_db_obj.access(py_index(index,self.list_db_obj.length()?)?))
+    }
+    fn __delitem__(&self,py:Python<'_>,index:isize) -> PyResult<()> {
+        // del l[i] 和 list 一样让后面的元素前移
+        self.pop(py,index)?;
+        Ok(())
+    }
+    fn __contains__(&self,py:Python<'_>,value:Vec<u8>) -> PyResult<bool> {
+        unlocked(py, || self.list_db_obj.contains(&value))
+    }
+    fn __iter__(&self,py:Python<'_>) -> PyResult<ListIterator> {
+        // 迭代时跳过被删除的位置
+        Ok(ListIterator { iter : unlocked(py, || self.list_db_obj.iter())?, reverse : false })
+    }
+    fn __reversed__(&self,py:Python<'_>) -> PyResult<ListIterator> {
+        Ok(ListIterator { iter : unlocked(py, || self.list_db_obj.iter())?, reverse : true })
+    }
+
+    // 异步接口,在事件循环中 await,数据库操作在线程池中执行
+    fn aappend(&self,py:Python<'_>,value:Vec<u8>) -> PyResult<PyObject> {
+        let list = self.list_db_obj.clone();
+        offload(py, move || list.append(&value))
+    }
+    fn aaccess(&self,py:Python<'_>,index:usize) -> PyResult<PyObject> {
+        let list = self.list_db_obj.clone();
+        offload(py, move || list.access(index))
+    }
+    fn aoverwrite(&self,py:Python<'_>,index:usize,value:Vec<u8>) -> PyResult<PyObject> {
+        let list = self.list_db_obj.clone();
+        offload(py, move || list.overwrite(index,&value))
+    }
+    fn adelete(&self,py:Python<'_>,index:usize) -> PyResult<PyObject> {
+        let list = self.list_db_obj.clone();
+        offload(py, move || list.delete(index))
+    }
+    fn aextend(&self,py:Python<'_>,values:Vec<Vec<u8>>) -> PyResult<PyObject> {
+        let list = self.list_db_obj.clone();
+        offload(py, move || list.extend(&values))
+    }
+    #[pyo3(signature = (start=0, end=None))]
+    fn aget_range(&self,py:Python<'_>,start:usize,end:Option<usize>) -> PyResult<PyObject> {
+        let list = self.list_db_obj.clone();
+        offload(py, move || list.get_range(start, end.unwrap_or(usize::MAX)))
+    }
+    fn alen(&self,py:Python<'_>) -> PyResult<PyObject> {
+        let list = self.list_db_obj.clone();
+        offload(py, move || list.length())
+    }
+    fn __aiter__(&self,py:Python<'_>) -> PyResult<AsyncIterator> {
+        // async for 和 for 一样跳过被删除的位置
+        Ok(async_iter(py, self.__iter__(py)?))
+    }
+}
+
+fn next_item<T, I>(iter:&mut I, reverse:bool) -> DbResult<Option<T>> where I:DoubleEndedIterator<Item = DbResult<T>> {
+    // python 迭代器的下一项,reverse 时从末尾开始
+    let item = if reverse { iter.next_back() } else { iter.next() };
+    item.transpose()
+}
+
+fn py_index(index:isize,length:usize) -> Result<usize,DbError> {
+    // python 风格的索引,负数从末尾开始计算
+    let i = if index < 0 { index + length as isize } else { index };
+    if i < 0 {
+        return Err(DbError::OutOfBounds { index: index.unsigned_abs(), length })
+    }
+    Ok(i as usize)
+}
+
+#[pyclass]
+struct ListIterator {
+    iter : ListIter,
+    reverse : bool,
+}
+
+#[pymethods]
+impl ListIterator {
+    fn __iter__(slf:PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    fn __next__(&mut self,py:Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        let item = unlocked(py, || next_item(&mut self.iter, self.reverse))?;
+        Ok(item.map(|(_, value)| value))
+    }
+}
+
+#[pyclass]
+struct TupleList {
+    tuple_list : TupleListDb
+}
+
+#[pymethods]
+impl TupleList {
+    #[new]
+    fn new(py:Python<'_>,db:PyKvDbOperaObject,name:String,arity:u16) -> PyResult<Self> {
+        Ok(TupleList { tuple_list : unlocked(py, || TupleListDb::new(db.db,name,arity))? })
+    }
+
+    fn append(&self,py:Python<'_>,value:Vec<Vec<u8>>) -> PyResult<()> {
+        unlocked(py, || self.tuple_list.append(&tuple_arg(&value)))
+    }
+    fn access(&self,py:Python<'_>,index:usize) -> PyResult<Vec<Option<Vec<u8>>>> {
+        unlocked(py, || self.tuple_list.access(index))
+    }
+    fn overwrite(&self,py:Python<'_>,index:usize,value:Vec<Vec<u8>>) -> PyResult<()> {
+        unlocked(py, || self.tuple_list.overwrite(index,&tuple_arg(&value)))
+    }
+    fn delete(&self,py:Python<'_>,index:usize) -> PyResult<()> {
+        unlocked(py, || self.tuple_list.delete(index))
+    }
+    fn len(&self,py:Python<'_>) -> PyResult<usize> {
+        unlocked(py, || self.tuple_list.length())
+    }
+    fn arity(&self) -> u16 {
+        self.tuple_list.arity()
+    }
+    fn __len__(&self,py:Python<'_>) -> PyResult<usize> {
+        self.len(py)
+    }
+    fn __getitem__(&self,py:Python<'_>,index:isize) -> PyResult<Vec<Option<Vec<u8>>>> {
+        unlocked(py, || self.tuple_list.access(py_index(index,self.tuple_list.length()?)?))
+    }
+    fn __contains__(&self,py:Python<'_>,value:Vec<Vec<u8>>) -> PyResult<bool> {
+        unlocked(py, || self.tuple_list.contains(&tuple_arg(&value)))
+    }
+    fn __iter__(&self,py:Python<'_>) -> PyResult<TupleListIterator> {
+        // 迭代时跳过整个被删除的元组
+        Ok(TupleListIterator { iter : unlocked(py, || self.tuple_list.iter())?, reverse : false })
+    }
+    fn __reversed__(&self,py:Python<'_>) -> PyResult<TupleListIterator> {
+        Ok(TupleListIterator { iter : unlocked(py, || self.tuple_list.iter())?, reverse : true })
+    }
+
+    fn aappend(&self,py:Python<'_>,value:Vec<Vec<u8>>) -> PyResult<PyObject> {
+        let tuple_list = self.tuple_list.clone();
+        offload(py, move || tuple_list.append(&tuple_arg(&value)))
+    }
+    fn aaccess(&self,py:Python<'_>,index:usize) -> PyResult<PyObject> {
+        let tuple_list = self.tuple_list.clone();
+        offload(py, move || tuple_list.access(index))
+    }
+    fn alen(&self,py:Python<'_>) -> PyResult<PyObject> {
+        let tuple_list = self.tuple_list.clone();
+        offload(py, move || tuple_list.length())
+    }
+    fn __aiter__(&self,py:Python<'_>) -> PyResult<AsyncIterator> {
+        Ok(async_iter(py, self.__iter__(py)?))
+    }
+}
+
+fn tuple_arg(value:&[Vec<u8>]) -> Vec<&[u8]> {
+    value.iter().map(|v| v.as_slice()).collect()
+}
+
+#[pyclass]
+struct TupleListIterator {
+    iter : TupleListIter,
+    reverse : bool,
+}
+
+#[pymethods]
+impl TupleListIterator {
+    fn __iter__(slf:PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    fn __next__(&mut self,py:Python<'_>) -> PyResult<Option<Vec<Option<Vec<u8>>>>> {
+        let item = unlocked(py, || next_item(&mut self.iter, self.reverse))?;
+        Ok(item.map(|(_, tuple)| tuple))
+    }
+}
+
+#[derive(Clone)]
+enum HashtableBackend {
+    Zipper(HashtableDb), // 拉链法哈希表
+    Sled(SledHashtableDb), // 有序哈希表,键按字节顺序排列,支持范围查询
+}
+
+impl HashtableBackend {
+    fn insert(&self,key:&[u8],value:&[u8]) -> DbResult<()> {
+        match self {
+            HashtableBackend::Zipper(t) => t.insert(key, value),
+            HashtableBackend::Sled(t) => t.insert(key, value),
+        }
+    }
+    fn get(&self,key:&[u8]) -> DbResult<Option<Vec<u8>>> {
+        match self {
+            HashtableBackend::Zipper(t) => t.get(key),
+            HashtableBackend::Sled(t) => t.get(key),
+        }
+    }
+    fn delete(&self,key:&[u8]) -> DbResult<()> {
+        match self {
+            HashtableBackend::Zipper(t) => t.delete(key),
+            HashtableBackend::Sled(t) => t.delete(key),
+        }
+    }
+    fn insert_many(&self,items:&[(Vec<u8>, Vec<u8>)]) -> DbResult<()> {
+        match self {
+            HashtableBackend::Zipper(t) => t.insert_many(items),
+            HashtableBackend::Sled(t) => t.insert_many(items),
+        }
+    }
+    fn get_many(&self,keys:&[Vec<u8>]) -> DbResult<Vec<Option<Vec<u8>>>> {
+        match self {
+            HashtableBackend::Zipper(t) => t.get_many(keys),
+            HashtableBackend::Sled(t) => t.get_many(keys),
         }
     }
-    fn access(&self,index:usize) -> Option<Vec<u8>> {
-        match self.list_db_obj.access(index) {
-            Some(t) => Some(t),
-            _ => None,
+    fn delete_many(&self,keys:&[Vec<u8>]) -> DbResult<usize> {
+        match self {
+            HashtableBackend::Zipper(t) => t.delete_many(keys),
+            HashtableBackend::Sled(t) => t.delete_many(keys),
         }
     }
-    fn overwrite(&self,index:usize,value:Vec<u8>) -> bool {
-        match self.list_db_obj.overwrite(index,&value) {
-            Ok(_) => true,
-            Err(_) => false,
+    fn contains_key(&self,key:&[u8]) -> DbResult<bool> {
+        match self {
+            HashtableBackend::Zipper(t) => t.contains_key(key),
+            HashtableBackend::Sled(t) => t.contains_key(key),
         }
     }
-    fn delete(&self,index:usize) -> bool {
-        match self.list_db_obj.delete(index) {
-            Ok(_) => true,
-            Err(_) => false,
+    fn len(&self) -> DbResult<usize> {
+        match self {
+            HashtableBackend::Zipper(t) => t.len(),
+            HashtableBackend::Sled(t) => t.len(),
         }
     }
-    fn len(&self) -> Option<usize> {
-        self.list_db_obj.length()
+    fn iter(&self) -> DbResult<HashtableIter> {
+        match self {
+            HashtableBackend::Zipper(t) => t.iter(),
+            HashtableBackend::Sled(t) => Ok(Box::new(t.iter())),
+        }
+    }
+    fn to_tuple_list(&self,number_of_entries:Option<usize>) -> DbResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.iter()?.take(number_of_entries.unwrap_or(usize::MAX)).collect()
+    }
+    fn zipper(&self,method:&str) -> PyResult<&HashtableDb> {
+        match self {
+            HashtableBackend::Zipper(t) => Ok(t),
+            HashtableBackend::Sled(_) => Err(PyTypeError::new_err(format!("{method} is only supported by the zipper backend"))),
+        }
     }
 }
 
 #[pyclass]
 struct Hashtable {
-    hashtable : HashtableDb
+    hashtable : HashtableBackend
 }
 
 #[pymethods]
 impl Hashtable {
     #[new]
-    fn new(db:PyKvDbOperaObject,name:String) -> Self {
-        Hashtable { hashtable : HashtableDb::new(db.db,name) }
+    #[pyo3(signature = (db, name, backend="zipper"))]
+    fn new(py:Python<'_>,db:PyKvDbOperaObject,name:String,backend:&str) -> PyResult<Self> {
+        // backend: "zipper" 为拉链法哈希表, "sled" 为有序哈希表;两种哈希表的数据互相独立
+        let hashtable = match backend {
+            "zipper" => HashtableBackend::Zipper(unlocked(py, || HashtableDb::new(db.db,name))?),
+            "sled" => HashtableBackend::Sled(unlocked(py, || SledHashtableDb::new(db.db,name))?),
+            other => return Err(PyValueError::new_err(format!("unknown hashtable backend: {other}"))),
+        };
+        Ok(Hashtable { hashtable })
     }
 
     // fn open(db:PyKvDbOperaObject,name:String) -> Self {
     //     Hashtable { hashtable : HashtableDb::open(db.db,name) }
     // }
 
-    fn insert(&self,key:String,value:Vec<u8>) -> bool {
-        match self.hashtable.insert(&key, &value) {
-            Ok(_) => true,
-            Err(_) => false,
+    // 键可以是 bytes | str | int | tuple,编码方式见 sdk::db::typed_key
+    fn insert(&self,py:Python<'_>,key:&PyAny,value:Vec<u8>) -> PyResult<()> {
+        let key = hashtable_key(key)?;
+        unlocked(py, || self.hashtable.insert(&key, &value))
+    }
+    fn get(&self,py:Python<'_>,key:&PyAny) -> PyResult<Option<Vec<u8>>> {
+        let key = hashtable_key(key)?;
+        unlocked(py, || self.hashtable.get(&key))
+    }
+    fn delete(&self,py:Python<'_>,key:&PyAny) -> PyResult<()> {
+        let key = hashtable_key(key)?;
+        unlocked(py, || self.hashtable.delete(&key))
+    }
+    // 批量操作在一个事务中完成
+    fn update(&self,py:Python<'_>,items:&PyAny) -> PyResult<()> {
+        let items = hashtable_items(items)?;
+        unlocked(py, || self.hashtable.insert_many(&items))
+    }
+    fn get_many(&self,py:Python<'_>,keys:&PyAny) -> PyResult<Vec<Option<Vec<u8>>>> {
+        let keys = hashtable_keys(keys)?;
+        unlocked(py, || self.hashtable.get_many(&keys))
+    }
+    fn delete_many(&self,py:Python<'_>,keys:&PyAny) -> PyResult<usize> {
+        // 跳过不存在的键,返回删除的个数
+        let keys = hashtable_keys(keys)?;
+        unlocked(py, || self.hashtable.delete_many(&keys))
+    }
+    fn to_tuple_list(&self,py:Python<'_>,number_of_entries:Option<usize>) -> PyResult<Vec<(PyObject, Vec<u8>)>>{
+        unlocked(py, || self.hashtable.to_tuple_list(number_of_entries))?.into_iter()
+            .map(|(key, value)| Ok((decode_hashtable_key(py, &key)?, value)))
+            .collect()
+    }
+    fn len(&self,py:Python<'_>) -> PyResult<usize> {
+        unlocked(py, || self.hashtable.len())
+    }
+    fn rehash(&self,py:Python<'_>) -> PyResult<bool> {
+        // 旧哈希函数建立的哈希表用固定的哈希函数重建,返回是否重建
+        let table = self.hashtable.zipper("rehash")?;
+        unlocked(py, || table.rehash())
+    }
+    fn set_load_factor(&self,py:Python<'_>,percent:u32) -> PyResult<()> {
+        // 负载因子(平均每个桶的条目个数的百分比),超过时哈希表自动扩容
+        let table = self.hashtable.zipper("set_load_factor")?;
+        unlocked(py, || table.set_load_factor(percent))
+    }
+    #[pyo3(signature = (start=None, end=None))]
+    fn range(&self,start:Option<&PyAny>,end:Option<&PyAny>) -> PyResult<HashtableIterator> {
+        // 迭代 start <= 键 < end 的 (键, 值),只支持有序哈希表
+        let HashtableBackend::Sled(t) = &self.hashtable else {
+            return Err(PyTypeError::new_err("range is only supported by the sled backend"))
+        };
+        Ok(HashtableIterator { iter : Box::new(t.range(key_bounds(start, end)?)), reverse : false, items : true })
+    }
+    fn items(&self,py:Python<'_>) -> PyResult<HashtableIterator> {
+        // 迭代 (键, 值)
+        Ok(HashtableIterator { iter : unlocked(py, || self.hashtable.iter())?, reverse : false, items : true })
+    }
+    fn __len__(&self,py:Python<'_>) -> PyResult<usize> {
+        self.len(py)
+    }
+    fn __getitem__(&self,py:Python<'_>,key:&PyAny) -> PyResult<Vec<u8>> {
+        // 和 dict 一样,键不存在时抛出 KeyNotFound
+        let k = hashtable_key(key)?;
+        Ok(unlocked(py, || self.hashtable.get(&k))?.ok_or(DbError::NotFound(format!("Key {key}")))?)
+    }
+    fn __contains__(&self,py:Python<'_>,key:&PyAny) -> PyResult<bool> {
+        let key = hashtable_key(key)?;
+        unlocked(py, || self.hashtable.contains_key(&key))
+    }
+    fn __iter__(&self,py:Python<'_>) -> PyResult<HashtableIterator> {
+        // 和 dict 一样迭代键
+        Ok(HashtableIterator { iter : unlocked(py, || self.hashtable.iter())?, reverse : false, items : false })
+    }
+    fn __reversed__(&self,py:Python<'_>) -> PyResult<HashtableIterator> {
+        Ok(HashtableIterator { iter : unlocked(py, || self.hashtable.iter())?, reverse : true, items : false })
+    }
+
+    // 异步接口,在事件循环中 await,数据库操作在线程池中执行
+    fn ainsert(&self,py:Python<'_>,key:&PyAny,value:Vec<u8>) -> PyResult<PyObject> {
+        let (table, key) = (self.hashtable.clone(), hashtable_key(key)?);
+        offload(py, move || table.insert(&key, &value))
+    }
+    fn aget(&self,py:Python<'_>,key:&PyAny) -> PyResult<PyObject> {
+        let (table, key) = (self.hashtable.clone(), hashtable_key(key)?);
+        offload(py, move || table.get(&key))
+    }
+    fn adelete(&self,py:Python<'_>,key:&PyAny) -> PyResult<PyObject> {
+        let (table, key) = (self.hashtable.clone(), hashtable_key(key)?);
+        offload(py, move || table.delete(&key))
+    }
+    fn acontains(&self,py:Python<'_>,key:&PyAny) -> PyResult<PyObject> {
+        let (table, key) = (self.hashtable.clone(), hashtable_key(key)?);
+        offload(py, move || table.contains_key(&key))
+    }
+    fn aupdate(&self,py:Python<'_>,items:&PyAny) -> PyResult<PyObject> {
+        let (table, items) = (self.hashtable.clone(), hashtable_items(items)?);
+        offload(py, move || table.insert_many(&items))
+    }
+    fn aget_many(&self,py:Python<'_>,keys:&PyAny) -> PyResult<PyObject> {
+        let (table, keys) = (self.hashtable.clone(), hashtable_keys(keys)?);
+        offload(py, move || table.get_many(&keys))
+    }
+    fn adelete_many(&self,py:Python<'_>,keys:&PyAny) -> PyResult<PyObject> {
+        let (table, keys) = (self.hashtable.clone(), hashtable_keys(keys)?);
+        offload(py, move || table.delete_many(&keys))
+    }
+    fn alen(&self,py:Python<'_>) -> PyResult<PyObject> {
+        let table = self.hashtable.clone();
+        offload(py, move || table.len())
+    }
+    fn aitems(&self,py:Python<'_>) -> PyResult<AsyncIterator> {
+        // async for 迭代 (键, 值)
+        Ok(async_iter(py, self.items(py)?))
+    }
+    fn __aiter__(&self,py:Python<'_>) -> PyResult<AsyncIterator> {
+        // async for 迭代键
+        Ok(async_iter(py, self.__iter__(py)?))
+    }
+}
+
+#[pyclass]
+struct HashtableIterator {
+    iter : HashtableIter,
+    reverse : bool,
+    items : bool, // true 时返回 (键, 值),否则只返回键
+}
+
+#[pymethods]
+impl HashtableIterator {
+    fn __iter__(slf:PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    fn __next__(&mut self, py:Python<'_>) -> PyResult<Option<PyObject>> {
+        match unlocked(py, || next_item(&mut self.iter, self.reverse))? {
+            Some((key, value)) => {
+                let key = decode_hashtable_key(py, &key)?;
+                Ok(Some(if self.items { (key, value).into_py(py) } else { key }))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[pyclass]
+struct SortedMap {
+    map : SortedMapDb,
+}
+
+#[pymethods]
+impl SortedMap {
+    #[new]
+    fn new(py:Python<'_>,db:PyKvDbOperaObject,name:String) -> PyResult<Self> {
+        Ok(SortedMap { map : unlocked(py, || SortedMapDb::new(db.db,name))? })
+    }
+    fn insert(&self,py:Python<'_>,key:&PyAny,value:Vec<u8>) -> PyResult<()> {
+        let key = hashtable_key(key)?;
+        unlocked(py, || self.map.insert(&key,&value))
+    }
+    fn get(&self,py:Python<'_>,key:&PyAny) -> PyResult<Option<Vec<u8>>> {
+        let key = hashtable_key(key)?;
+        unlocked(py, || self.map.get(&key))
+    }
+    fn delete(&self,py:Python<'_>,key:&PyAny) -> PyResult<()> {
+        let key = hashtable_key(key)?;
+        unlocked(py, || self.map.delete(&key))
+    }
+    fn len(&self,py:Python<'_>) -> PyResult<usize> {
+        unlocked(py, || self.map.len())
+    }
+    #[pyo3(signature = (start=None, end=None, reverse=false))]
+    fn range(&self,start:Option<&PyAny>,end:Option<&PyAny>,reverse:bool) -> PyResult<HashtableIterator> {
+        // 迭代 start <= 键 < end 的 (键, 值)
+        Ok(HashtableIterator { iter : Box::new(self.map.range(key_bounds(start, end)?)), reverse, items : true })
+    }
+    #[pyo3(signature = (prefix, reverse=false))]
+    fn prefix(&self,prefix:&PyAny,reverse:bool) -> PyResult<HashtableIterator> {
+        // 迭代键以 prefix 开头的 (键, 值),tuple 匹配前几个元素相同的元组
+        let prefix = py_to_typed_key(prefix)?.encode_prefix();
+        Ok(HashtableIterator { iter : Box::new(self.map.prefix(&prefix)), reverse, items : true })
+    }
+    fn first(&self,py:Python<'_>) -> PyResult<Option<(PyObject, Vec<u8>)>> {
+        entry_to_py(py, unlocked(py, || self.map.first())?)
+    }
+    fn last(&self,py:Python<'_>) -> PyResult<Option<(PyObject, Vec<u8>)>> {
+        entry_to_py(py, unlocked(py, || self.map.last())?)
+    }
+    fn floor(&self,py:Python<'_>,key:&PyAny) -> PyResult<Option<(PyObject, Vec<u8>)>> {
+        // 键 <= key 的最大条目
+        let key = hashtable_key(key)?;
+        entry_to_py(py, unlocked(py, || self.map.floor(&key))?)
+    }
+    fn ceiling(&self,py:Python<'_>,key:&PyAny) -> PyResult<Option<(PyObject, Vec<u8>)>> {
+        // 键 >= key 的最小条目
+        let key = hashtable_key(key)?;
+        entry_to_py(py, unlocked(py, || self.map.ceiling(&key))?)
+    }
+    fn items(&self) -> HashtableIterator {
+        HashtableIterator { iter : Box::new(self.map.iter()), reverse : false, items : true }
+    }
+    fn __len__(&self,py:Python<'_>) -> PyResult<usize> {
+        self.len(py)
+    }
+    fn __getitem__(&self,py:Python<'_>,key:&PyAny) -> PyResult<PyObject> {
+        // m[key] 返回值,键不存在时抛出 KeyNotFound
+        // m[start:end] 返回 start <= 键 < end 的 [(键, 值)],m[start:end:-1] 为倒序
+        let Ok(slice) = key.downcast::<PySlice>() else {
+            let k = hashtable_key(key)?;
+            let value = unlocked(py, || self.map.get(&k))?.ok_or(DbError::NotFound(format!("Key {key}")))?;
+            return Ok(value.into_py(py))
+        };
+        let field = |name:&str| -> PyResult<Option<&PyAny>> {
+            let v = slice.getattr(name)?;
+            Ok((!v.is_none()).then_some(v))
+        };
+        let reverse = match field("step")?.map(|s| s.extract::<i64>()).transpose()? {
+            None | Some(1) => false,
+            Some(-1) => true,
+            Some(_) => return Err(PyValueError::new_err("slice step must be 1 or -1")),
+        };
+        let mut iter = HashtableIterator { iter : Box::new(self.map.range(key_bounds(field("start")?, field("stop")?)?)), reverse, items : true };
+        let mut items = Vec::new();
+        while let Some(item) = iter.__next__(py)? {
+            items.push(item);
+        }
+        Ok(items.into_py(py))
+    }
+    fn __contains__(&self,py:Python<'_>,key:&PyAny) -> PyResult<bool> {
+        let key = hashtable_key(key)?;
+        unlocked(py, || self.map.contains_key(&key))
+    }
+    fn __iter__(&self) -> HashtableIterator {
+        HashtableIterator { iter : Box::new(self.map.iter()), reverse : false, items : false }
+    }
+    fn __reversed__(&self) -> HashtableIterator {
+        HashtableIterator { iter : Box::new(self.map.iter()), reverse : true, items : false }
+    }
+}
+
+type KeyRange = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+#[pyclass]
+struct Set {
+    set : SetDb,
+}
+
+#[pymethods]
+impl Set {
+    #[new]
+    fn new(py:Python<'_>,db:PyKvDbOperaObject,name:String) -> PyResult<Self> {
+        Ok(Set { set : unlocked(py, || SetDb::new(db.db,name))? })
+    }
+    // 成员可以是 bytes | str | int | tuple,和 Hashtable 的键相同
+    fn add(&self,py:Python<'_>,member:&PyAny) -> PyResult<()> {
+        let member = hashtable_key(member)?;
+        unlocked(py, || self.set.add(&member))?;
+        Ok(())
+    }
+    fn discard(&self,py:Python<'_>,member:&PyAny) -> PyResult<()> {
+        let member = hashtable_key(member)?;
+        unlocked(py, || self.set.remove(&member))?;
+        Ok(())
+    }
+    fn remove(&self,py:Python<'_>,member:&PyAny) -> PyResult<()> {
+        // 和 set 一样,成员不存在时抛出 KeyNotFound(KeyError 的子类)
+        let m = hashtable_key(member)?;
+        if !unlocked(py, || self.set.remove(&m))? {
+            return Err(DbError::NotFound(format!("Member {member}")).into())
         }
+        Ok(())
+    }
+    fn pop(&self,py:Python<'_>) -> PyResult<PyObject> {
+        // 移除并返回最小的成员
+        let member = unlocked(py, || self.set.pop_first())?.ok_or(DbError::NotFound("pop from an empty set".to_string()))?;
+        decode_hashtable_key(py, &member)
+    }
+    fn clear(&self,py:Python<'_>) -> PyResult<()> {
+        unlocked(py, || self.set.clear())
+    }
+    fn len(&self,py:Python<'_>) -> PyResult<usize> {
+        unlocked(py, || self.set.len())
+    }
+    // 集合运算的参数可以是任意可迭代对象(包括另一个 Set),结果是 python 的 set
+    fn union(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        let mut members = set_members(py, other)?;
+        members.extend(self.members(py)?);
+        members_to_py(py, members)
+    }
+    fn intersection(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        let mut members = set_members(py, other)?;
+        let mine = self.members(py)?;
+        members.retain(|m| mine.contains(m));
+        members_to_py(py, members)
+    }
+    fn difference(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        let other = set_members(py, other)?;
+        let mut members = self.members(py)?;
+        members.retain(|m| !other.contains(m));
+        members_to_py(py, members)
+    }
+    fn symmetric_difference(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        let other = set_members(py, other)?;
+        let members = self.members(py)?;
+        members_to_py(py, members.symmetric_difference(&other).cloned())
+    }
+    fn isdisjoint(&self,py:Python<'_>,other:&PyAny) -> PyResult<bool> {
+        let other = set_members(py, other)?;
+        Ok(self.members(py)?.is_disjoint(&other))
+    }
+    fn __or__(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        self.union(py, other)
+    }
+    fn __ror__(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        self.union(py, other)
+    }
+    fn __and__(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        self.intersection(py, other)
     }
-    fn get(&self,key:String) -> Option<Vec<u8>> {
-        self.hashtable.get(&key)
+    fn __rand__(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        self.intersection(py, other)
     }
-    fn delete(&self,key:String) -> bool {
-        match self.hashtable.delete(&key) {
-            Ok(_) => true,
-            Err(_) => false,
+    fn __sub__(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        self.difference(py, other)
+    }
+    fn __rsub__(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        let mut members = set_members(py, other)?;
+        let mine = self.members(py)?;
+        members.retain(|m| !mine.contains(m));
+        members_to_py(py, members)
+    }
+    fn __xor__(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        self.symmetric_difference(py, other)
+    }
+    fn __rxor__(&self,py:Python<'_>,other:&PyAny) -> PyResult<Py<PySet>> {
+        self.symmetric_difference(py, other)
+    }
+    // 原地修改的运算直接修改数据库中的集合,每个运算在一个事务中完成
+    fn __ior__(&self,py:Python<'_>,other:&PyAny) -> PyResult<()> {
+        let other = set_members(py, other)?;
+        unlocked(py, || self.set.update(&other))
+    }
+    fn __iand__(&self,py:Python<'_>,other:&PyAny) -> PyResult<()> {
+        let other = set_members(py, other)?;
+        unlocked(py, || self.set.intersection_update(&other))
+    }
+    fn __isub__(&self,py:Python<'_>,other:&PyAny) -> PyResult<()> {
+        let other = set_members(py, other)?;
+        unlocked(py, || self.set.difference_update(&other))
+    }
+    fn __ixor__(&self,py:Python<'_>,other:&PyAny) -> PyResult<()> {
+        let other = set_members(py, other)?;
+        unlocked(py, || self.set.symmetric_difference_update(&other))
+    }
+    fn __richcmp__(&self,py:Python<'_>,other:&PyAny,op:CompareOp) -> PyResult<PyObject> {
+        // 和 collections.abc.Set 一样,只和集合比较,其他类型返回 NotImplemented
+        if !other.is_instance(py.import("collections.abc")?.getattr("Set")?)? {
+            return Ok(py.NotImplemented())
         }
+        let (mine, other) = (self.members(py)?, set_members(py, other)?);
+        let ret = match op {
+            CompareOp::Lt => mine.len() < other.len() && mine.is_subset(&other),
+            CompareOp::Le => mine.is_subset(&other),
+            CompareOp::Eq => mine == other,
+            CompareOp::Ne => mine != other,
+            CompareOp::Gt => mine.len() > other.len() && mine.is_superset(&other),
+            CompareOp::Ge => mine.is_superset(&other),
+        };
+        Ok(ret.into_py(py))
+    }
+    fn __len__(&self,py:Python<'_>) -> PyResult<usize> {
+        self.len(py)
     }
-    fn to_tuple_list(&self,number_of_entries:Option<usize>) -> Vec<(String, Vec<u8>)>{
-        self.hashtable.to_tuple_list(number_of_entries)
+    fn __contains__(&self,py:Python<'_>,member:&PyAny) -> PyResult<bool> {
+        let member = hashtable_key(member)?;
+        unlocked(py, || self.set.contains(&member))
+    }
+    fn __iter__(&self) -> SetIterator {
+        SetIterator { iter : self.set.iter(), reverse : false }
+    }
+    fn __reversed__(&self) -> SetIterator {
+        SetIterator { iter : self.set.iter(), reverse : true }
     }
 }
 
+impl Set {
+    fn members(&self,py:Python<'_>) -> PyResult<BTreeSet<Vec<u8>>> {
+        unlocked(py, || self.set.iter().collect())
+    }
+}
+
+fn set_members(py:Python<'_>,other:&PyAny) -> PyResult<BTreeSet<Vec<u8>>> {
+    // 集合运算的另一个参数: 储存的 Set 直接读取成员,其他可迭代对象逐个转换
+    if let Ok(other) = other.extract::<PyRef<'_, Set>>() {
+        return other.members(py)
+    }
+    other.iter()?.map(|m| hashtable_key(m?)).collect()
+}
+
+fn members_to_py(py:Python<'_>,members:impl IntoIterator<Item = Vec<u8>>) -> PyResult<Py<PySet>> {
+    // 集合运算的结果是 python 的 set
+    let members = members.into_iter().map(|m| decode_hashtable_key(py, &m)).collect::<PyResult<Vec<_>>>()?;
+    Ok(PySet::new(py, &members)?.into())
+}
+
+#[pyclass]
+struct SetIterator {
+    iter : SetIter,
+    reverse : bool,
+}
+
+#[pymethods]
+impl SetIterator {
+    fn __iter__(slf:PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    fn __next__(&mut self, py:Python<'_>) -> PyResult<Option<PyObject>> {
+        let item = unlocked(py, || next_item(&mut self.iter, self.reverse))?;
+        item.map(|m| decode_hashtable_key(py, &m)).transpose()
+    }
+}
+
+#[pyclass]
+struct Deque {
+    deque : DequeDb,
+}
+
+#[pymethods]
+impl Deque {
+    #[new]
+    fn new(py:Python<'_>,db:PyKvDbOperaObject,name:String) -> PyResult<Self> {
+        Ok(Deque { deque : unlocked(py, || DequeDb::new(db.db,name))? })
+    }
+    fn push_back(&self,py:Python<'_>,value:Vec<u8>) -> PyResult<()> {
+        unlocked(py, || self.deque.push_back(&value))
+    }
+    fn push_front(&self,py:Python<'_>,value:Vec<u8>) -> PyResult<()> {
+        unlocked(py, || self.deque.push_front(&value))
+    }
+    fn pop_back(&self,py:Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        unlocked(py, || self.deque.pop_back())
+    }
+    fn pop_front(&self,py:Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        unlocked(py, || self.deque.pop_front())
+    }
+    fn peek_back(&self,py:Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        unlocked(py, || self.deque.peek_back())
+    }
+    fn peek_front(&self,py:Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        unlocked(py, || self.deque.peek_front())
+    }
+    #[pyo3(signature = (timeout=None))]
+    fn pop(&self,py:Python<'_>,timeout:Option<f64>) -> PyResult<Option<Vec<u8>>> {
+        // 从队头取出一个元素,队列为空时等待,timeout 秒后返回 None;timeout 为 None 时一直等待
+        // 等待时释放 GIL,每隔一小段时间检查一次 Ctrl-C
+        const SLICE: Duration = Duration::from_millis(100);
+        let deadline = match timeout {
+            Some(t) if t < 0.0 || !t.is_finite() => return Err(PyValueError::new_err("timeout must be a non-negative number")),
+            Some(t) => Some(Instant::now() + Duration::from_secs_f64(t)),
+            None => None,
+        };
+        loop {
+            let wait = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()).min(SLICE),
+                None => SLICE,
+            };
+            if let Some(value) = unlocked(py, || self.deque.pop_front_timeout(Some(wait)))? {
+                return Ok(Some(value))
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return Ok(None)
+            }
+            py.check_signals()?;
+        }
+    }
+    fn len(&self,py:Python<'_>) -> PyResult<usize> {
+        unlocked(py, || self.deque.len())
+    }
+    fn __len__(&self,py:Python<'_>) -> PyResult<usize> {
+        self.len(py)
+    }
+    fn __iter__(&self,py:Python<'_>) -> PyResult<DequeIterator> {
+        Ok(DequeIterator { iter : unlocked(py, || self.deque.iter())?, reverse : false })
+    }
+    fn __reversed__(&self,py:Python<'_>) -> PyResult<DequeIterator> {
+        Ok(DequeIterator { iter : unlocked(py, || self.deque.iter())?, reverse : true })
+    }
+}
+
+#[pyclass]
+struct DequeIterator {
+    iter : DequeIter,
+    reverse : bool,
+}
+
+#[pymethods]
+impl DequeIterator {
+    fn __iter__(slf:PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    fn __next__(&mut self,py:Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        unlocked(py, || next_item(&mut self.iter, self.reverse))
+    }
+}
+
+fn key_bounds(start:Option<&PyAny>,end:Option<&PyAny>) -> PyResult<KeyRange> {
+    // python 的 [start, end) 转化为键的范围,None 表示没有边界
+    let bound = |key:Option<&PyAny>, f:fn(Vec<u8>) -> Bound<Vec<u8>>| -> PyResult<Bound<Vec<u8>>> {
+        Ok(match key { Some(k) => f(hashtable_key(k)?), None => Bound::Unbounded })
+    };
+    Ok((bound(start, Bound::Included)?, bound(end, Bound::Excluded)?))
+}
+
+fn entry_to_py(py:Python<'_>,entry:Option<Entry>) -> PyResult<Option<(PyObject, Vec<u8>)>> {
+    entry.map(|(key, value)| Ok((decode_hashtable_key(py, &key)?, value))).transpose()
+}
+
+fn py_to_typed_key(key:&PyAny) -> PyResult<TypedKey> {
+    // python 的 bytes | str | int | tuple 转化为类型化键
+    if let Ok(s) = key.downcast::<PyString>() {
+        return Ok(TypedKey::Str(s.to_str()?.to_string()))
+    }
+    if let Ok(b) = key.downcast::<PyBytes>() {
+        return Ok(TypedKey::Bytes(b.as_bytes().to_vec()))
+    }
+    if let Ok(t) = key.downcast::<PyTuple>() {
+        return Ok(TypedKey::Tuple(t.iter().map(py_to_typed_key).collect::<PyResult<_>>()?))
+    }
+    if key.is_instance_of::<PyLong>() {
+        return Ok(TypedKey::Int(key.extract::<i64>()?)) // 超出 i64 时抛出 OverflowError
+    }
+    Err(PyTypeError::new_err(format!("unsupported key type: {}", key.get_type().name()?)))
+}
+
+fn typed_key_to_py(py:Python<'_>,key:TypedKey) -> PyObject {
+    match key {
+        TypedKey::Bytes(b) => PyBytes::new(py, &b).into(),
+        TypedKey::Str(s) => s.into_py(py),
+        TypedKey::Int(i) => i.into_py(py),
+        TypedKey::Tuple(items) => PyTuple::new(py, items.into_iter().map(|k| typed_key_to_py(py, k))).into(),
+    }
+}
+
+fn hashtable_key(key:&PyAny) -> PyResult<Vec<u8>> {
+    Ok(py_to_typed_key(key)?.encode())
+}
+
+fn hashtable_keys(keys:&PyAny) -> PyResult<Vec<Vec<u8>>> {
+    keys.iter()?.map(|key| hashtable_key(key?)).collect()
+}
+
+fn hashtable_items(items:&PyAny) -> PyResult<Vec<(Vec<u8>, Vec<u8>)>> {
+    // items 可以是 dict 或者 (键, 值) 的可迭代对象
+    let items = match items.downcast::<PyDict>() {
+        Ok(dict) => dict.items().as_ref(),
+        Err(_) => items,
+    };
+    items.iter()?.map(|item| {
+        let (key, value): (&PyAny, Vec<u8>) = item?.extract()?;
+        Ok((hashtable_key(key)?, value))
+    }).collect()
+}
+
+fn decode_hashtable_key(py:Python<'_>,key:&[u8]) -> PyResult<PyObject> {
+    Ok(typed_key_to_py(py, TypedKey::decode(key)?))
+}
+
 #[pyfunction]
-fn archive_open(db:PyKvDbOperaObject,name:String) -> Archive {
-    // archive's open 模式的构造函数 ( 打开一个 archive )
-    Archive {archive:archive::Archive::open_object(db.db,name) }
+fn archive_open(py:Python<'_>,db:PyKvDbOperaObject,name:String) -> PyResult<Archive> {
+    // archive's open 模式的构造函数 ( 打开一个 archive ),不存在时抛出 ArchiveNotFound
+    Ok(Archive {archive:unlocked(py, || archive::Archive::open_object(db.db,name))? })
 }
 
 #[pyfunction]
-fn archive_new(db:PyKvDbOperaObject,name:String) -> Archive {
+fn archive_new(py:Python<'_>,db:PyKvDbOperaObject,name:String) -> PyResult<Archive> {
     // archive's new 模式的构造函数 ( 打开或者创建一个 archive )
-    Archive {archive:archive::Archive::new_object(db.db,name) }
+    Ok(Archive {archive:unlocked(py, || archive::Archive::new_object(db.db,name))? })
 }
 
 #[pyclass]
@@ -141,91 +1328,264 @@ struct Archive {
     archive:archive::Archive
 }
 
-fn py_index_list_to_rs_index_vec(py_index_list:Vec<(&str,Vec<String>)>) -> Result<archive::IndexVec,String> {
+fn index_list_arg(data:&[String],i:usize) -> Result<&String,DbError> {
+    // 取出索引参数,参数个数不足时报错而不是 panic
+    data.get(i).ok_or_else(|| DbError::InvalidIndexPath(format!("索引参数不足,需要第 {} 个参数",i+1)))
+}
+
+fn parse_index_arg<T:std::str::FromStr>(data:&[String],i:usize,what:&str) -> Result<T,DbError> where T::Err:std::fmt::Display {
+    // 索引参数的字符串解析
+    index_list_arg(data,i)?.parse::<T>().map_err(|e| DbError::InvalidIndexPath(format!("字符串解析失败,{what}不是整数:{e}")))
+}
+
+fn py_index_list_to_rs_index_vec(py_index_list:Vec<(&str,Vec<String>)>) -> Result<archive::IndexVec,DbError> {
     let mut iv:archive::IndexVec = vec![archive::IVE::HeadMarking];
     for item in py_index_list {
         match item {
             ("Hashtable",data) => {
-                iv.push(archive::IVE::Hashtable(data[0].clone()))
+                iv.push(archive::IVE::Hashtable(index_list_arg(&data,0)?.clone()))
+            },
+            ("SledHashtable",data) => {
+                iv.push(archive::IVE::SledHashtable(index_list_arg(&data,0)?.clone()))
+            },
+            ("Set",data) => {
+                iv.push(archive::IVE::Set(index_list_arg(&data,0)?.clone()))
             },
             ("List",data) => {
-                iv.push(archive::IVE::List(match data[0].clone().parse::<usize>() {
-                    Ok(num) => num,
-                    Err(e) => {return Err(format!("字符串解析失败,列表索引不是整数:{}",e)) }
-                })) // 列表索引的字符串解析
+                iv.push(archive::IVE::List(parse_index_arg(&data,0,"列表索引")?)) // 列表索引的字符串解析
             },
             ("TupleList",data) => {
                 iv.push(archive::IVE::TupleList(
-                    match data[0].clone().parse::<usize>() {
-                        Ok(num) => num,
-                        Err(e) => {return Err(format!("字符串解析失败,列表索引不是整数:{}",e)) }
-                    },
-                    match data[1].clone().parse::<u16>() {
-                        Ok(num) => num,
-                        Err(e) => {return Err(format!("字符串解析失败,元组索引不是整数:{}",e)) }
-                    },
-                    match data[2].clone().parse::<u16>() {
-                        Ok(num) => num,
-                        Err(e) => {return Err(format!("字符串解析失败,元组长度不是整数:{}",e)) }
-                    },
+                    parse_index_arg(&data,0,"列表索引")?,
+                    parse_index_arg(&data,1,"元组索引")?,
+                    parse_index_arg(&data,2,"元组长度")?,
                 ))
             }
-            _ => {
-                return Err("Non-existent data type".to_string())
+            (other,_) => {
+                return Err(DbError::InvalidIndexPath(format!("Non-existent data type: {other}")))
             }
         }
     };
     Ok(iv)
 }
 
+#[derive(FromPyObject)]
+enum PyIndexPath<'a> {
+    // 档案的路径: 路径字符串(例如 "/h:users/l:8"),或者 [("Hashtable",["users"]),("List",["8"])] 这样的列表
+    Path(String),
+    List(Vec<(&'a str,Vec<String>)>),
+}
+
+impl PyIndexPath<'_> {
+    fn index_vec(self) -> Result<archive::IndexVec,DbError> {
+        match self {
+            PyIndexPath::Path(path) => Ok(path.parse::<archive::IndexPath>()?.0),
+            PyIndexPath::List(list) => py_index_list_to_rs_index_vec(list),
+        }
+    }
+}
+
 #[pymethods]
 impl Archive {
-    fn new(&self,py_index_list:Vec<(&str,Vec<String>)>) -> EAR{
-        EAR{ear:self.archive.new(py_index_list_to_rs_index_vec(py_index_list).unwrap()).unwrap() }
+    #[allow(clippy::new_ret_no_self, clippy::wrong_self_convention)]
+    fn new(&self,py:Python<'_>,py_index_list:PyIndexPath<'_>) -> PyResult<EAR>{
+        let iv = py_index_list.index_vec()?;
+        Ok(EAR{ear:unlocked(py, || self.archive.new(iv))? })
+    }
+    fn open(&self,py:Python<'_>,py_index_list:PyIndexPath<'_>) -> PyResult<EAR>{
+        let iv = py_index_list.index_vec()?;
+        Ok(EAR{ear:unlocked(py, || self.archive.open(iv))? })
+    }
+    fn delete_subtree(&self,py:Python<'_>,py_index_list:PyIndexPath<'_>) -> PyResult<()>{
+        // 删除元素以及它下面的所有层
+        let iv = py_index_list.index_vec()?;
+        unlocked(py, || self.archive.delete_subtree(iv))
+    }
+    fn drop(&self,py:Python<'_>) -> PyResult<()>{
+        // 删除整个档案,之后这个对象不能再使用
+        unlocked(py, || self.archive.drop())
+    }
+    fn __getitem__(&self,key:&PyAny) -> PyResult<ArchiveNode>{
+        // archive["users"][8] 只记录路径,读写时才访问数据库
+        ArchiveNode { archive: self.archive.clone(), path: vec![archive::IVE::HeadMarking] }.__getitem__(key)
+    }
+    fn __setitem__(&self,py:Python<'_>,key:&PyAny,value:Vec<u8>) -> PyResult<()>{
+        ArchiveNode { archive: self.archive.clone(), path: vec![archive::IVE::HeadMarking] }.__setitem__(py,key,value)
+    }
+    fn __delitem__(&self,py:Python<'_>,key:&PyAny) -> PyResult<()>{
+        ArchiveNode { archive: self.archive.clone(), path: vec![archive::IVE::HeadMarking] }.__delitem__(py,key)
+    }
+    fn anew(&self,py:Python<'_>,py_index_list:PyIndexPath<'_>) -> PyResult<PyObject>{
+        let (archive, iv) = (self.archive.clone(), py_index_list.index_vec()?);
+        offload(py, move || Ok(EAR{ ear:archive.new(iv)? }))
+    }
+    fn aopen(&self,py:Python<'_>,py_index_list:PyIndexPath<'_>) -> PyResult<PyObject>{
+        let (archive, iv) = (self.archive.clone(), py_index_list.index_vec()?);
+        offload(py, move || Ok(EAR{ ear:archive.open(iv)? }))
+    }
+}
+
+fn archive_element(key:&PyAny) -> PyResult<archive::IVE> {
+    // archive[key] 的 key: str 为哈希表的键,int 为列表索引,(列表索引, 元组索引, 元组长度) 为元组列表
+    // 集合和 sled 哈希表的层没有对应的下标,只能通过路径访问
+    if let Ok(key) = key.downcast::<PyString>() {
+        return Ok(archive::IVE::Hashtable(key.to_str()?.to_string()))
+    }
+    if key.is_instance_of::<PyLong>() {
+        let index:isize = key.extract()?;
+        return match usize::try_from(index) {
+            Ok(index) => Ok(archive::IVE::List(index)),
+            Err(_) => Err(DbError::InvalidIndexPath(format!("archive list index must not be negative: {index}")).into()),
+        }
+    }
+    if let Ok((index, tuple_index, len)) = key.extract::<(usize, u16, u16)>() {
+        return Ok(archive::IVE::TupleList(index, tuple_index, len))
+    }
+    Err(PyTypeError::new_err(format!("archive key must be str, int or (index, tuple index, tuple length), not {}", key.get_type().name()?)))
+}
+
+#[pyclass]
+struct ArchiveNode {
+    // 档案中的一个位置,archive["users"][8] 返回这个对象
+    // 写入时缺少的层自动创建(同 Archive.new),已经存在的层保持不变,中间层是数据时报错
+    archive:archive::Archive,
+    path:archive::IndexVec, // 从 HeadMarking 开始
+}
+
+impl ArchiveNode {
+    fn child(&self,key:&PyAny) -> PyResult<archive::IndexVec> {
+        let mut path = self.path.clone();
+        path.push(archive_element(key)?);
+        Ok(path)
+    }
+}
+
+#[pymethods]
+impl ArchiveNode {
+    fn __getitem__(&self,key:&PyAny) -> PyResult<ArchiveNode> {
+        Ok(ArchiveNode { archive: self.archive.clone(), path: self.child(key)? })
+    }
+    fn __setitem__(&self,py:Python<'_>,key:&PyAny,value:Vec<u8>) -> PyResult<()> {
+        let path = self.child(key)?;
+        unlocked(py, || self.archive.open_or_new(path)?.overwrite(&value))
+    }
+    fn __delitem__(&self,py:Python<'_>,key:&PyAny) -> PyResult<()> {
+        // 同时删除下面的所有层,路径不存在时抛出 KeyNotFound
+        let path = self.child(key)?;
+        unlocked(py, || self.archive.delete_subtree(path))
+    }
+    fn access(&self,py:Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        // 路径不存在时抛出 KeyNotFound
+        unlocked(py, || self.archive.open(self.path.clone())?.access())
+    }
+    #[pyo3(signature = (default=None))]
+    fn get(&self,py:Python<'_>,default:Option<PyObject>) -> PyResult<Option<PyObject>> {
+        // 路径不存在或者没有数据时返回 default
+        match unlocked(py, || self.archive.open(self.path.clone())?.access()) {
+            Ok(Some(value)) => Ok(Some(value.into_py(py))),
+            Ok(None) => Ok(default),
+            Err(e) if e.is_instance_of::<PyKeyError>(py) || e.is_instance_of::<PyIndexError>(py) => Ok(default),
+            Err(e) => Err(e),
+        }
     }
-    fn open(&self,py_index_list:Vec<(&str,Vec<String>)>) -> EAR{
-        EAR{ear:self.archive.open(py_index_list_to_rs_index_vec(py_index_list).unwrap()).unwrap() }
+    fn overwrite(&self,py:Python<'_>,value:Vec<u8>) -> PyResult<()> {
+        unlocked(py, || self.archive.open_or_new(self.path.clone())?.overwrite(&value))
+    }
+    fn delete(&self,py:Python<'_>) -> PyResult<()> {
+        unlocked(py, || self.archive.delete_subtree(self.path.clone()))
+    }
+    #[getter]
+    fn path(&self) -> String {
+        // 路径字符串,可以传给 Archive.open
+        archive::IndexPath(self.path.clone()).to_string()
+    }
+    fn __repr__(&self) -> String {
+        format!("ArchiveNode({:?}, {:?})", self.archive.name(), self.path())
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[pyclass]
 struct EAR {
     ear : archive::EAR
 }
 #[pymethods]
 impl EAR{
-    fn access(&self) -> Option<Vec<u8>> {
-        self.ear.access()
+    fn access(&self,py:Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        unlocked(py, || self.ear.access())
     }
-    fn delete(&self) -> Option<bool> {
-        if let Ok(t) = self.ear.delete() {
-            Some(t)
-        } else {
-            None
-        }
+    fn delete(&self,py:Python<'_>) -> PyResult<()> {
+        unlocked(py, || self.ear.delete())
     }
-    fn overwrite(&self,data:Vec<u8>) -> Option<bool> {
-        if let Ok(t) = self.ear.overwrite(&data) {
-            Some(t)
-        } else {
-            None
-        }
+    fn overwrite(&self,py:Python<'_>,data:Vec<u8>) -> PyResult<()> {
+        unlocked(py, || self.ear.overwrite(&data))
+    }
+    fn aaccess(&self,py:Python<'_>) -> PyResult<PyObject> {
+        let ear = self.ear.clone();
+        offload(py, move || ear.access())
+    }
+    fn adelete(&self,py:Python<'_>) -> PyResult<PyObject> {
+        let ear = self.ear.clone();
+        offload(py, move || ear.delete())
+    }
+    fn aoverwrite(&self,py:Python<'_>,data:Vec<u8>) -> PyResult<PyObject> {
+        let ear = self.ear.clone();
+        offload(py, move || ear.overwrite(&data))
     }
 }
 
 #[test]
 fn test_a(){
-    let db = open_db(r"D:\tmp\welcome-to-sled".to_string());
-    let archive = archive_open(db,"878129128".to_string());
-    let list = vec![
-        ("Hashtable",vec!["lst".to_string() ]),
-        ("List",vec!["8".to_string()]),
-        ("TupleList",vec!["8".to_string(),"1".to_string(),"2".to_string()]),
-    ];
-    let my_data = archive.open(list);
-    dbg!(&my_data.overwrite(b"114514".to_vec()));
-    dbg!(&my_data.access());
-    dbg!(&my_data.delete());
-    dbg!(&my_data.access());
-
-}
\ No newline at end of file
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let db = PyKvDbOperaObject{ db : kv_operation::KvDbOpera::new(kv_operation::temporary()) };
+        assert!(archive_open(py,db.clone(),"878129128".to_string()).is_err());
+        let archive = archive_new(py,db.clone(),"878129128".to_string()).unwrap();
+        let list = vec![
+            ("Hashtable",vec!["lst".to_string() ]),
+            ("List",vec!["8".to_string()]),
+            ("TupleList",vec!["8".to_string(),"1".to_string(),"2".to_string()]),
+        ];
+        assert!(archive.open(py,PyIndexPath::List(list.clone())).is_err()); // 还没有 new
+        assert!(py_index_list_to_rs_index_vec(vec![("List",vec![])]).is_err());
+        assert!(py_index_list_to_rs_index_vec(vec![("List",vec!["-1".to_string()])]).is_err());
+        let my_data = archive.new(py,PyIndexPath::List(list)).unwrap();
+        assert!(my_data.overwrite(py,b"114514".to_vec()).is_ok());
+        assert_eq!(my_data.access(py).ok(), Some(Some(b"114514".to_vec())));
+        let same = archive.open(py,PyIndexPath::Path("/h:lst/l:8/t:8,1,2".to_string())).unwrap(); // 路径字符串
+        assert_eq!(same.access(py).ok(), Some(Some(b"114514".to_vec())));
+        let again = archive_new(py,db.clone(),"878129128".to_string()).unwrap(); // 沿用原来的档案头
+        assert_eq!(again.open(py,PyIndexPath::Path("/h:lst/l:8/t:8,1,2".to_string())).unwrap().access(py).ok(), Some(Some(b"114514".to_vec())));
+        assert_eq!(archive::gc(&db.db, false).map(|r| r.removed.len()), Ok(0));
+        assert!(my_data.delete(py).is_ok());
+        assert_eq!(my_data.access(py).ok(), Some(None)); // 删除末尾的元素后列表变短,越界同样视为不存在
+    });
+}
+
+#[test]
+fn test_tuple_list_arity(){
+    // 元组长度为 0 时抛出 TupleArityError,而不是除以零的 PanicException
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let db = PyKvDbOperaObject{ db : kv_operation::KvDbOpera::new(kv_operation::temporary()) };
+        let locals = pyo3::types::PyDict::new(py);
+        locals.set_item("db", Py::new(py, db).unwrap()).unwrap();
+        locals.set_item("TupleList", py.get_type::<TupleList>()).unwrap();
+        locals.set_item("TupleArityError", py.get_type::<TupleArityError>()).unwrap();
+        py.run(r#"
+try:
+    TupleList(db, "x", 0)
+    raise AssertionError("arity 0 accepted")
+except TupleArityError:
+    pass
+t = TupleList(db, "x", 2)
+t.append([b"a", b"b"])
+try:
+    TupleList(db, "x", 0)
+    raise AssertionError("arity 0 accepted")
+except TupleArityError:
+    pass
+assert len(t) == 1
+"#, None, Some(locals)).unwrap();
+    });
+}
@@ -1,9 +1,45 @@
-
-
 mod test;
+#[allow(dead_code)] // 这里只用到了迁移工具, sdk 的其他接口由 lib 使用
 mod sdk;
 
+use sdk::db::kv_operation::{initialization, KvDbOpera, KvDbOperaTrait};
 
 fn main() {
-
+    // 旧版本数据库的键迁移工具: other-db migrate <数据库路径>
+    let args: Vec<String> = std::env::args().collect();
+    let path = match args.as_slice() {
+        [_, cmd, path] if cmd == "migrate" => path.clone(),
+        _ => {
+            eprintln!("usage: other-db migrate <database path>");
+            std::process::exit(2);
+        }
+    };
+    let db = match initialization(path) {
+        Ok(db) => KvDbOpera::new(db),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    match sdk::migration::migrate_string_keys(&db) {
+        Ok(report) => {
+            println!("collections: {}, archives: {}, keys: {}", report.collections, report.archives, report.keys);
+            println!("upgraded metadata: {}, tagged archives: {}, catalogued collections: {}", report.metadata, report.tagged, report.catalogued);
+            for key in report.skipped {
+                println!("skipped: {}", String::from_utf8_lossy(&key));
+            }
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+    // 用固定的哈希函数重建旧版本的哈希表
+    match sdk::migration::rehash_hashtables(&db) {
+        Ok(n) => println!("rehashed hashtables: {n}"),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
 }
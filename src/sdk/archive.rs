@@ -1,15 +1,26 @@
 // 索引vec语法解析
 // 用来方便操作数据库
-use crate::sdk::db::kv_operation::{initialization, KvDbOperaObject, KvDbOperaTrait};
+use crate::sdk::catalog;
+use crate::sdk::db::key_codec;
+use crate::sdk::db::metadata::CollectionKind;
+use crate::sdk::db::kv_operation::{KvDbOperaObject, KvDbOperaTrait};
 use crate::sdk::db::list_db::ListDb;
 use crate::sdk::db::hashtable_zipper_db::Hashtable;
+use crate::sdk::db::hashtable_sled_db::Hashtable as SledHashtable;
 use crate::sdk::db::tuple_list_db::TupleList;
+use crate::sdk::db::set_db::Set;
+use crate::sdk::error::{DbError, DbResult};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
 use uuid::Uuid;
 
 #[derive(Clone)]
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum IndexVecElement { // IndexVec 支持的值
     Hashtable(String), // hashtable key
+    SledHashtable(String), // 有序哈希表(hashtable_sled_db)的 key
+    Set(String), // 集合的成员
     List(usize), // list's index
     TupleList(usize,u16,u16), // list's index & tuple's index & tuple's len
     HeadMarking, // 用于标记一个IndexVec的开始,里面包含指向档案数据的Index
@@ -19,21 +30,212 @@ pub enum IndexVecElement { // IndexVec 支持的值
 #[derive(Clone)]
 pub enum AnalysisElement { // ElementAnalysisResults 中结果的对象 , 同时也是查询结果
     Hashtable(Hashtable),
+    SledHashtable(SledHashtable),
+    Set(Set),
     List(ListDb),
     TupleList(TupleList),
     HeadMarking, // 用于标记一个IndexVec的开始
 }
 
+#[derive(Clone)]
 pub struct ElementAnalysisResults(AnalysisElement,IndexVecElement); // IndexVecOperate::AnalysisElement 返回值
 // struct EAR(AE,IVE); // as ElementAnalysisResults
 pub type IndexVec = Vec<IndexVecElement>;
 pub type AE = AnalysisElement;
+#[allow(clippy::upper_case_acronyms)]
 pub type IVE = IndexVecElement;
 type UuidIndex = String; // 这里使用uuid创建中间索引
+#[allow(clippy::upper_case_acronyms)]
 pub type EAR = ElementAnalysisResults;
 
-fn option_vec_to_string(vec: Option<Vec<u8>>) -> String {
-    String::from_utf8(vec.unwrap()).unwrap()
+const INDEX_KINDS: [CollectionKind; 5] = [
+    CollectionKind::Hashtable, CollectionKind::OrderedHashtable, CollectionKind::Set, CollectionKind::List, CollectionKind::TupleList,
+]; // 档案内部的集合可能的种类
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GcReport {
+    pub archives: usize, // 遍历的档案个数
+    pub reachable: usize, // 从档案头可以到达的集合个数
+    pub removed: Vec<(CollectionKind, String)>, // 删除(dry_run 时为将要删除)的集合
+    pub keys: usize, // 删除(dry_run 时为将要删除)的键的个数
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexPath(pub IndexVec); // IndexVec 的字符串形式,例如 /h:users/l:8/t:8,1,2
+
+// 路径语法: 每一层写作 "/种类:参数",开头的 HeadMarking 省略
+//   h:键  sh:键  s:成员  l:列表索引  t:列表索引,元组索引,元组长度
+// 分别对应 Hashtable / SledHashtable / Set / List / TupleList,键和成员中的 '/' 和 '\' 写作 "\/" 和 "\\"
+fn path_error(path:&str, pos:usize, msg:impl fmt::Display) -> DbError {
+    // pos 为字节位置,报错时换算为第几个字符
+    DbError::InvalidIndexPath(format!("{msg} at position {} in {path:?}", path[..pos].chars().count()))
+}
+
+fn parse_number<T:FromStr>(path:&str, pos:usize, text:&str, what:&str) -> DbResult<T> where T::Err:fmt::Display {
+    text.parse::<T>().map_err(|e| path_error(path, pos, format!("{what} {text:?} is not a valid number ({e})")))
+}
+
+fn escape_key(key:&str) -> String {
+    key.replace('\\', "\\\\").replace('/', "\\/")
+}
+
+impl FromStr for IndexPath {
+    type Err = DbError;
+
+    fn from_str(path:&str) -> DbResult<Self> {
+        let mut iv = vec![IVE::HeadMarking];
+        let mut chars = path.char_indices().peekable();
+        while let Some((pos, c)) = chars.next() {
+            if c != '/' {
+                return Err(path_error(path, pos, format!("expected '/' but found {c:?}")))
+            }
+            let kind_start = pos + 1;
+            let kind_end = loop {
+                match chars.next() {
+                    Some((p, ':')) => break p,
+                    Some((_, c)) if c.is_ascii_alphabetic() => {},
+                    Some((p, c)) => return Err(path_error(path, p, format!("expected ':' after the element kind but found {c:?}"))),
+                    None => return Err(path_error(path, path.len(), "expected ':' after the element kind")),
+                }
+            };
+            let value_start = kind_end + 1;
+            let mut value = String::new(); // 到下一个没有转义的 '/' 为止
+            while let Some(&(_, c)) = chars.peek() {
+                if c == '/' {
+                    break
+                }
+                chars.next();
+                if c != '\\' {
+                    value.push(c);
+                    continue
+                }
+                match chars.next() {
+                    Some((_, c @ ('/' | '\\'))) => value.push(c),
+                    Some((p, c)) => return Err(path_error(path, p, format!("invalid escape '\\{c}', only '\\/' and '\\\\' are allowed"))),
+                    None => return Err(path_error(path, path.len(), "unfinished escape at the end")),
+                }
+            }
+            iv.push(match &path[kind_start..kind_end] {
+                "h" => IVE::Hashtable(value),
+                "sh" => IVE::SledHashtable(value),
+                "s" => IVE::Set(value),
+                "l" => IVE::List(parse_number(path, value_start, &value, "list index")?),
+                "t" => {
+                    let parts = value.split(',').collect::<Vec<_>>();
+                    let [index, tuple_index, len] = parts[..] else {
+                        return Err(path_error(path, value_start, format!("TupleList needs 'index,tuple index,tuple length' but found {value:?}")))
+                    };
+                    let tuple_index_start = value_start + index.len() + 1;
+                    let len_start = tuple_index_start + tuple_index.len() + 1;
+                    let tuple_len = parse_number(path, len_start, len, "tuple length")?;
+                    if tuple_len == 0 {
+                        return Err(path_error(path, len_start, "tuple length must be at least 1"))
+                    }
+                    IVE::TupleList(
+                        parse_number(path, value_start, index, "list index")?,
+                        parse_number(path, tuple_index_start, tuple_index, "tuple index")?,
+                        tuple_len,
+                    )
+                },
+                kind => return Err(path_error(path, kind_start, format!("unknown element kind {kind:?}, expected h, sh, s, l or t"))),
+            });
+        }
+        if iv.len() < 2 {
+            return Err(path_error(path, 0, "path needs at least one element"))
+        }
+        Ok(IndexPath(iv))
+    }
+}
+
+impl fmt::Display for IndexVecElement {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        // 路径中的一层,HeadMarking 在路径中省略
+        match self {
+            IVE::Hashtable(key) => write!(f, "h:{}", escape_key(key)),
+            IVE::SledHashtable(key) => write!(f, "sh:{}", escape_key(key)),
+            IVE::Set(member) => write!(f, "s:{}", escape_key(member)),
+            IVE::List(index) => write!(f, "l:{index}"),
+            IVE::TupleList(index,tuple_index,len) => write!(f, "t:{index},{tuple_index},{len}"),
+            IVE::HeadMarking => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for IndexPath {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        for el in self.0.iter().filter(|el| !matches!(el, IVE::HeadMarking)) {
+            write!(f, "/{el}")?;
+        }
+        Ok(())
+    }
+}
+
+pub trait IntoIndexVec {
+    // Archive 的 new/open/delete 既可以传入 IndexVec,也可以传入路径字符串
+    fn into_index_vec(self) -> DbResult<IndexVec>;
+}
+
+impl IntoIndexVec for IndexVec {
+    fn into_index_vec(self) -> DbResult<IndexVec> {
+        Ok(self)
+    }
+}
+
+impl IntoIndexVec for IndexPath {
+    fn into_index_vec(self) -> DbResult<IndexVec> {
+        Ok(self.0)
+    }
+}
+
+impl IntoIndexVec for &str {
+    fn into_index_vec(self) -> DbResult<IndexVec> {
+        Ok(self.parse::<IndexPath>()?.0)
+    }
+}
+
+// 中间层储存的下一层索引写作 [POINTER][UuidIndex],用户的数据以 POINTER 或 ESCAPE 开头时在前面加一个 ESCAPE,
+// 所以以 POINTER 开头的值一定是索引,储存的数据即使看起来像 uuid 也不会被当作下一层
+const POINTER: u8 = 0xFF;
+const ESCAPE: u8 = 0xFE;
+
+fn pointer(index:&str) -> Vec<u8> {
+    [&[POINTER][..], index.as_bytes()].concat()
+}
+
+fn decode_pointer(v:&[u8]) -> Option<UuidIndex> {
+    // 不是索引(是用户的数据)时返回 None
+    match v.split_first() {
+        Some((&POINTER, index)) => String::from_utf8(index.to_vec()).ok(),
+        _ => None,
+    }
+}
+
+fn encode_data(data:&[u8]) -> Vec<u8> {
+    match data.first() {
+        Some(&(POINTER | ESCAPE)) => [&[ESCAPE][..], data].concat(),
+        _ => data.to_vec(),
+    }
+}
+
+fn decode_data(v:Vec<u8>) -> Vec<u8> {
+    // 索引原样返回
+    match v.first() {
+        Some(&ESCAPE) => v[1..].to_vec(),
+        _ => v,
+    }
+}
+
+fn option_vec_to_string(vec: Option<Vec<u8>>) -> DbResult<UuidIndex> {
+    // 中间层储存的是下一层的 UuidIndex,不存在则说明这条路径还没有被创建
+    match vec {
+        Some(v) => decode_pointer(&v).ok_or_else(|| DbError::InvalidIndexPath("the index path goes through data, not an index".to_string())),
+        None => Err(DbError::NotFound("archive index".to_string())),
+    }
+}
+
+fn head_index(name:&str, head:&[u8]) -> DbResult<UuidIndex> {
+    // 档案头储存的也是带标记的索引,旧版本的档案头没有标记
+    decode_pointer(head).ok_or_else(|| DbError::CorruptMetadata(format!("legacy archive {name}, run `other-db migrate` first")))
 }
 
 #[derive(Clone)]
@@ -47,204 +249,708 @@ impl EAR {
     fn new(ae:AE,ive:IVE) -> Self {
         ElementAnalysisResults(ae,ive)
     }
-    pub fn access(&self) -> Option<Vec<u8>> {
-        // 访问 EAR 指定的内容
-        match self.0.clone() { // 因为EAR包含多种类型,所以需要使用match语句来规定所有类型的处理方法
-            AE::HeadMarking => { None },
-            AE::Hashtable(obj) => {
-                Some(if let IVE::Hashtable(key) = self.1.clone() { // 只处理 IVE::Hashtable 类型,其他一律报错
-                    return obj.get(&key)
-                })
-            },
-            AE::List(obj) => {
-                Some(if let IVE::List(index) = self.1.clone() {
-                    return obj.access(index)
-                })
-            }
-            AE::TupleList(obj) => {
-                Some(if let IVE::TupleList(index,tindex,_) = self.1.clone() {
-                    return obj.access_tuple_elements(index,tindex)
-                })
-            }
+    fn format_error() -> DbError {
+        DbError::InvalidIndexPath("Incorrect ElementAnalysisResults (EAR) object format".to_string())
+    }
+    pub fn access(&self) -> DbResult<Option<Vec<u8>>> {
+        // 访问 EAR 指定的内容,不存在的元素(包括列表越界,删除末尾的元素会使列表变短)返回 None
+        // 中间层返回的是带标记的索引
+        Ok(self.raw()?.map(decode_data))
+    }
+    fn raw(&self) -> DbResult<Option<Vec<u8>>> {
+        // 储存的原始值
+        let in_bounds = |r: DbResult<Option<Vec<u8>>>| match r {
+            Err(DbError::OutOfBounds { .. }) => Ok(None),
+            r => r,
         };
-        None
+        match (&self.0, &self.1) { // 因为EAR包含多种类型,所以需要使用match语句来规定所有类型的处理方法
+            (AE::Hashtable(obj), IVE::Hashtable(key)) => obj.get(key.as_bytes()),
+            (AE::SledHashtable(obj), IVE::SledHashtable(key)) => obj.get(key.as_bytes()),
+            (AE::Set(obj), IVE::Set(member)) => Ok(obj.contains(member.as_bytes())?.then(Vec::new)), // 成员存在时为空值
+            (AE::List(obj), IVE::List(index)) => in_bounds(obj.access(*index)),
+            (AE::TupleList(obj), IVE::TupleList(index,tindex,len)) => match tindex < len { // 元组下标越界仍然报错
+                true => in_bounds(obj.access_tuple_elements(*index,*tindex)),
+                false => obj.access_tuple_elements(*index,*tindex),
+            },
+            _ => Err(Self::format_error()), // 类型不匹配或 HeadMarking 一律报错
+        }
     }
-    pub fn overwrite(&self,data:&Vec<u8>) -> Result<bool,String> {
+    pub fn overwrite(&self,data:&[u8]) -> DbResult<()> {
         // 覆写 EAR 指定的内容
-        match self.0.clone() {
-            AE::HeadMarking => { None },
-            AE::Hashtable(obj) => {
-                Some(if let IVE::Hashtable(key) = self.1.clone() {
-                    return obj.insert(&key,data)
-                })
-            },
-            AE::List(obj) => {
-                Some(if let IVE::List(index) = self.1.clone() {
-                    return obj.safety_overwrite(index,data)
-                })
-            }
-            AE::TupleList(obj) => {
-                Some(if let IVE::TupleList(index,tindex,_) = self.1.clone() {
-                    return obj.safety_overwrite_tuple_elements(index,tindex,data)
-                })
-            }
-        };
-        Err("Incorrect ElementAnalysisResults (EAR) object format".to_string())
+        match (&self.0, &self.1) {
+            (AE::Hashtable(obj), IVE::Hashtable(key)) => obj.insert(key.as_bytes(),&encode_data(data)),
+            (AE::SledHashtable(obj), IVE::SledHashtable(key)) => obj.insert(key.as_bytes(),&encode_data(data)),
+            (AE::Set(obj), IVE::Set(member)) => {
+                // 集合的成员没有值,覆写就是加入这个成员
+                if !data.is_empty() {
+                    return Err(DbError::InvalidIndexPath("set members have no value".to_string()))
+                }
+                obj.add(member.as_bytes()).map(|_| ())
+            },
+            (AE::List(obj), IVE::List(index)) => obj.safety_overwrite(*index,&encode_data(data)),
+            (AE::TupleList(obj), IVE::TupleList(index,tindex,_)) => obj.safety_overwrite_tuple_elements(*index,*tindex,&encode_data(data)),
+            _ => Err(Self::format_error()),
+        }
     }
-    pub fn delete(&self) -> Result<bool,String> {
+    pub fn delete(&self) -> DbResult<()> {
         // 删除 EAR 指定的内容
-        match self.0.clone() {
-            AE::HeadMarking => { None },
-            AE::Hashtable(obj) => {
-                Some(if let IVE::Hashtable(key) = self.1.clone() {
-                    return obj.delete(&key)
-                })
-            },
-            AE::List(obj) => {
-                Some(if let IVE::List(index) = self.1.clone() {
-                    return obj.delete(index)
-                })
-            }
-            AE::TupleList(obj) => {
-                Some(if let IVE::TupleList(index,tindex,_) = self.1.clone() {
-                    return obj.delete_tuple_elements(index,tindex)
-                })
-            }
+        match (&self.0, &self.1) {
+            (AE::Hashtable(obj), IVE::Hashtable(key)) => obj.delete(key.as_bytes()),
+            (AE::SledHashtable(obj), IVE::SledHashtable(key)) => obj.delete(key.as_bytes()),
+            (AE::Set(obj), IVE::Set(member)) => obj.remove(member.as_bytes()).map(|_| ()),
+            (AE::List(obj), IVE::List(index)) => obj.delete(*index),
+            (AE::TupleList(obj), IVE::TupleList(index,tindex,_)) => obj.delete_tuple_elements(*index,*tindex),
+            _ => Err(Self::format_error()),
+        }
+    }
+    pub fn bind(&self, db:&KvDbOperaObject) -> Self {
+        // 换一个数据库对象(比如事务)来操作 EAR 指定的内容
+        let ae = match &self.0 {
+            AE::Hashtable(obj) => AE::Hashtable(obj.bind(db)),
+            AE::SledHashtable(obj) => AE::SledHashtable(obj.bind(db)),
+            AE::Set(obj) => AE::Set(obj.bind(db)),
+            AE::List(obj) => AE::List(obj.bind(db)),
+            AE::TupleList(obj) => AE::TupleList(obj.bind(db)),
+            AE::HeadMarking => AE::HeadMarking,
         };
-        Err("Incorrect ElementAnalysisResults (EAR) object format".to_string())
+        EAR::new(ae, self.1.clone())
     }
     pub fn to_ive(&self) -> IVE {
         // 转化为IVE
-        return self.1.clone()
+        self.1.clone()
     }
 }
 
 impl Archive {
-    pub fn new_object(db:KvDbOperaObject,name:String) -> Self {
-        // 创建一个 Archive 对象,如果其不存在会创建
+    pub fn new_object(db:KvDbOperaObject,name:String) -> DbResult<Self> {
+        // 创建一个 Archive 对象,如果其不存在会创建,已经存在时沿用原来的档案头
         // db : 数据库对象
         // name : 档案名字
-        let head_index_uuid = Uuid::new_v4().to_string();
-        db.insert(format!("archive:{name}").as_bytes().to_vec(),head_index_uuid.clone().as_bytes().to_vec()).unwrap(); // 创建档案
-        Self { db,name, head_index_uuid}
+        let head_index_uuid = db.transaction(|tx| {
+            if let Some(head) = tx.get(key_codec::archive_key(&name))? {
+                return head_index(&name, &head)
+            }
+            let head_index_uuid = Uuid::new_v4().to_string();
+            tx.insert(key_codec::archive_key(&name),pointer(&head_index_uuid))?; // 创建档案
+            catalog::register(tx, CollectionKind::Archive, &name)?;
+            Ok(head_index_uuid)
+        })?;
+        Ok(Self { db,name, head_index_uuid})
     }
 
-    pub fn open_object(db:KvDbOperaObject,name:String) -> Self {
+    pub fn open_object(db:KvDbOperaObject,name:String) -> DbResult<Self> {
         // 打开一个 Archive 对象,如果其不存在会报错
         // db : 数据库对象
         // name : 档案名字
-        let head_index_uuid = String::from_utf8(db.get(format!("archive:{}",&name)).unwrap().unwrap().to_vec()).unwrap();
-        Self { db , name , head_index_uuid }
+        let head = db.get(key_codec::archive_key(&name))?.ok_or_else(|| DbError::ArchiveNotFound(name.clone()))?;
+        let head_index_uuid = head_index(&name, &head)?;
+        Ok(Self { db , name , head_index_uuid })
     }
 
-    fn new_database_objects(&self,el:IVE,index_uuid:UuidIndex) -> EAR {
+    pub fn bind(&self, db:&KvDbOperaObject) -> Self {
+        // 换一个数据库对象(比如事务)来操作这个档案
+        Self { db: db.clone(), name: self.name.clone(), head_index_uuid: self.head_index_uuid.clone() }
+    }
+
+    fn adopt(&self,el:&IVE,index_uuid:&str) -> DbResult<()> {
+        // 在目录中把档案内部的集合标记为属于这个档案,删除档案时一起删除
+        let kind = match el {
+            IVE::Hashtable(_) => CollectionKind::Hashtable,
+            IVE::SledHashtable(_) => CollectionKind::OrderedHashtable,
+            IVE::Set(_) => CollectionKind::Set,
+            IVE::List(_) => CollectionKind::List,
+            IVE::TupleList(..) => CollectionKind::TupleList,
+            IVE::HeadMarking => return Ok(()),
+        };
+        catalog::adopt(&self.db, kind, index_uuid, &self.name)
+    }
+    fn new_database_objects(&self,el:IVE,index_uuid:UuidIndex) -> DbResult<EAR> {
         // 获得某个元素的数据库对象,如果其不存在,则创建
-        match el {
+        self.adopt(&el, &index_uuid)?;
+        Ok(match el {
             IVE::Hashtable(key) => {
-                EAR::new(AE::Hashtable(Hashtable::new(self.db.clone(),index_uuid)),IVE::Hashtable(key))
+                EAR::new(AE::Hashtable(Hashtable::new(self.db.clone(),index_uuid)?),IVE::Hashtable(key))
+            },
+            IVE::SledHashtable(key) => {
+                EAR::new(AE::SledHashtable(SledHashtable::new(self.db.clone(),index_uuid)?),IVE::SledHashtable(key))
+            },
+            IVE::Set(member) => {
+                EAR::new(AE::Set(Set::new(self.db.clone(),index_uuid)?),IVE::Set(member))
             },
             IVE::List(index) => {
-                EAR::new(AE::List(ListDb::new(self.db.clone(),index_uuid).unwrap()),IVE::List(index))
+                EAR::new(AE::List(ListDb::new(self.db.clone(),index_uuid)?),IVE::List(index))
             },
             IVE::TupleList(index,tuple_index,len) => {
-                EAR::new(AE::TupleList(TupleList::new(self.db.clone(),index_uuid,len).unwrap()),IVE::TupleList(index,tuple_index,len))
+                EAR::new(AE::TupleList(TupleList::new(self.db.clone(),index_uuid,len)?),IVE::TupleList(index,tuple_index,len))
             },
             IVE::HeadMarking => {
                 EAR::new(AE::HeadMarking,IVE::HeadMarking)
             },
-        }
+        })
     }
-    fn get_database_objects(&self,el:IVE,index_uuid:UuidIndex) -> EAR {
+    fn get_database_objects(&self,el:IVE,index_uuid:UuidIndex) -> DbResult<EAR> {
         // 获得某个元素的数据库对象
-        match el {
+        Ok(match el {
             IVE::Hashtable(key) => {
-                EAR::new(AE::Hashtable(Hashtable::open(self.db.clone(),index_uuid)),IVE::Hashtable(key))
+                EAR::new(AE::Hashtable(Hashtable::open(self.db.clone(),index_uuid)?),IVE::Hashtable(key))
+            },
+            IVE::SledHashtable(key) => {
+                EAR::new(AE::SledHashtable(SledHashtable::open(self.db.clone(),index_uuid)?),IVE::SledHashtable(key))
+            },
+            IVE::Set(member) => {
+                EAR::new(AE::Set(Set::open(self.db.clone(),index_uuid)?),IVE::Set(member))
             },
             IVE::List(index) => {
-                EAR::new(AE::List(ListDb::open(self.db.clone(),index_uuid).unwrap()),IVE::List(index))
+                EAR::new(AE::List(ListDb::open(self.db.clone(),index_uuid)?),IVE::List(index))
             },
             IVE::TupleList(index,tuple_index,len) => {
-                EAR::new(AE::TupleList(TupleList::open(self.db.clone(),index_uuid,len).unwrap()),IVE::TupleList(index,tuple_index,len))
+                EAR::new(AE::TupleList(TupleList::open(self.db.clone(),index_uuid,len)?),IVE::TupleList(index,tuple_index,len))
             },
             IVE::HeadMarking => {
                 EAR::new(AE::HeadMarking,IVE::HeadMarking)
             },
-        }
+        })
+    }
+
+    fn set_member_index(set:&str,member:&str) -> UuidIndex {
+        // 集合成员指向的下一层,uuid 中没有 '/',不会和其他索引重复
+        format!("{set}/{member}")
+    }
+
+    fn previous_uuid(previous_parsing_result_data:Option<UuidIndex>) -> DbResult<UuidIndex> {
+        // 除了 HeadMarking 以外的元素都需要上一层的解析结果
+        previous_parsing_result_data.ok_or_else(|| DbError::InvalidIndexPath("IndexVec must start with HeadMarking".to_string()))
     }
 
-    fn analysis_element(&self,el:IVE,previous_parsing_result_data:Option<UuidIndex>) -> UuidIndex {
+    fn analysis_element(&self,el:IVE,previous_parsing_result_data:Option<UuidIndex>) -> DbResult<UuidIndex> {
         // 解析 IndexVec 中的某个元素(IVE类型),返回对应的UuidIndex
         match el {
             IVE::Hashtable(key) => {
-                option_vec_to_string(Hashtable::open(self.db.clone(),previous_parsing_result_data.unwrap()).get(&key))
+                option_vec_to_string(Hashtable::open(self.db.clone(),Self::previous_uuid(previous_parsing_result_data)?)?.get(key.as_bytes())?)
+            },
+            IVE::SledHashtable(key) => {
+                option_vec_to_string(SledHashtable::open(self.db.clone(),Self::previous_uuid(previous_parsing_result_data)?)?.get(key.as_bytes())?)
+            },
+            IVE::Set(member) => {
+                let set = Set::open(self.db.clone(),Self::previous_uuid(previous_parsing_result_data)?)?;
+                if !set.contains(member.as_bytes())? {
+                    return Err(DbError::NotFound("archive index".to_string()))
+                }
+                Ok(Self::set_member_index(set.name(), &member))
             },
             IVE::List(index) => {
-                option_vec_to_string(ListDb::open(self.db.clone(),previous_parsing_result_data.unwrap()).unwrap().access(index))
+                option_vec_to_string(ListDb::open(self.db.clone(),Self::previous_uuid(previous_parsing_result_data)?)?.access(index)?)
             },
             IVE::TupleList(index,tuple_index,len) => {
-                option_vec_to_string(TupleList::open(self.db.clone(),previous_parsing_result_data.unwrap(),len).unwrap().access_tuple_elements(index,tuple_index))
+                option_vec_to_string(TupleList::open(self.db.clone(),Self::previous_uuid(previous_parsing_result_data)?,len)?.access_tuple_elements(index,tuple_index)?)
             },
             IVE::HeadMarking => {
-                self.head_index_uuid.clone()
+                Ok(self.head_index_uuid.clone())
             },
         }
     }
-    fn establish_uuid_index(&self,el:IVE,previous_parsing_result_data:Option<UuidIndex>) -> UuidIndex {
+    fn existing_element(&self,el:&IVE,previous_parsing_result_data:Option<UuidIndex>) -> DbResult<Option<Vec<u8>>> {
+        // 读取某一层元素储存的值,只有这一层的集合或元素确实不存在时返回 None,其他错误照常返回
+        if *el == IVE::HeadMarking {
+            return Ok(Some(pointer(&self.head_index_uuid)))
+        }
+        let name = Self::previous_uuid(previous_parsing_result_data)?;
+        let missing = |e| match e {
+            DbError::NotFound(_) => Ok(None),
+            e => Err(e),
+        };
+        match el {
+            IVE::Hashtable(key) => Hashtable::open(self.db.clone(),name).map_or_else(missing, |t| t.get(key.as_bytes())),
+            IVE::SledHashtable(key) => SledHashtable::open(self.db.clone(),name).map_or_else(missing, |t| t.get(key.as_bytes())),
+            IVE::Set(member) => Set::open(self.db.clone(),name).map_or_else(missing, |set| {
+                Ok(set.contains(member.as_bytes())?.then(|| pointer(&Self::set_member_index(set.name(), member))))
+            }),
+            IVE::List(index) => ListDb::open(self.db.clone(),name).map_or_else(missing, |list| match *index < list.length()? {
+                true => list.access(*index),
+                false => Ok(None),
+            }),
+            IVE::TupleList(index,tuple_index,len) => TupleList::open(self.db.clone(),name,*len).map_or_else(missing, |list| match *index < list.length()? {
+                true => list.access_tuple_elements(*index,*tuple_index),
+                false => Ok(None),
+            }),
+            IVE::HeadMarking => unreachable!(),
+        }
+    }
+    fn establish_uuid_index(&self,el:IVE,previous_parsing_result_data:Option<UuidIndex>) -> DbResult<UuidIndex> {
         // 在某一个元素之上建立一个索引,只适用于 new 模式
         let uuid = Uuid::new_v4().to_string();
+        if let Some(previous) = &previous_parsing_result_data {
+            self.adopt(&el, previous)?;
+        }
         match el {
             IVE::Hashtable(key) => {
-                Hashtable::new(self.db.clone(), previous_parsing_result_data.unwrap()).insert(&key, &uuid.clone().as_bytes().to_vec())
+                Hashtable::new(self.db.clone(), Self::previous_uuid(previous_parsing_result_data)?)?.insert(key.as_bytes(), &pointer(&uuid))
+            },
+            IVE::SledHashtable(key) => {
+                SledHashtable::new(self.db.clone(), Self::previous_uuid(previous_parsing_result_data)?)?.insert(key.as_bytes(), &pointer(&uuid))
+            },
+            IVE::Set(member) => {
+                // 集合的成员没有值,下一层的索引由集合和成员决定
+                let set = Set::new(self.db.clone(), Self::previous_uuid(previous_parsing_result_data)?)?;
+                set.add(member.as_bytes())?;
+                return Ok(Self::set_member_index(set.name(), &member))
             },
             IVE::List(index) => {
-                let list = ListDb::new(self.db.clone(), previous_parsing_result_data.unwrap()).unwrap();
-                list.safety_overwrite(index,&uuid.clone().as_bytes().to_vec())
+                let list = ListDb::new(self.db.clone(), Self::previous_uuid(previous_parsing_result_data)?)?;
+                list.safety_overwrite(index,&pointer(&uuid))
             },
             IVE::TupleList(index,tuple_index,len) => {
-                TupleList::new(self.db.clone(), previous_parsing_result_data.unwrap(), len).unwrap().overwrite_tuple_elements(index, tuple_index, &uuid.as_bytes().to_vec())
+                TupleList::new(self.db.clone(), Self::previous_uuid(previous_parsing_result_data)?, len)?.safety_overwrite_tuple_elements(index, tuple_index, &pointer(&uuid))
             },
-            IVE::HeadMarking => { return self.head_index_uuid.clone() },
-        }.unwrap();
-        uuid
+            IVE::HeadMarking => { return Ok(self.head_index_uuid.clone()) },
+        }?;
+        Ok(uuid)
     }
-    fn _new(&self, iv:IndexVec,head:usize, index_uuid:Option<UuidIndex>) -> Option<EAR>{
+    fn _new(&self, iv:IndexVec,head:usize, index_uuid:Option<UuidIndex>) -> DbResult<EAR>{
         // 新建一个 IndexVec 对应的数据库对象
-        if head < iv.len()-1 {
-            self._new(iv.clone(),head+1,Some(self.establish_uuid_index(iv[head].clone(),index_uuid)))
+        if head+1 < iv.len() {
+            let index_uuid = self.establish_uuid_index(iv[head].clone(),index_uuid)?;
+            self._new(iv,head+1,Some(index_uuid))
         } else {
-            Some(self.new_database_objects(iv[head].clone(),index_uuid.unwrap()))
+            self.new_database_objects(iv[head].clone(),Self::previous_uuid(index_uuid)?)
         }
     }
-    fn _open(&self, iv:IndexVec,head:usize, index_uuid:Option<UuidIndex>) -> Option<EAR>{
+    fn _open(&self, iv:IndexVec,head:usize, index_uuid:Option<UuidIndex>) -> DbResult<EAR>{
         // 打开一个 IndexVec 对应的数据库对象
-        if head < iv.len()-1 {
+        if head+1 < iv.len() {
             // dbg!(&self.analysis_element(iv[head].clone(),index_uuid.clone()));
-            self._open(iv.clone(),head+1,Some(self.analysis_element(iv[head].clone(),index_uuid)))
+            let index_uuid = self.analysis_element(iv[head].clone(),index_uuid)?;
+            self._open(iv,head+1,Some(index_uuid))
         } else {
-            Some(self.get_database_objects(iv[head].clone(),index_uuid.unwrap()))
+            self.get_database_objects(iv[head].clone(),Self::previous_uuid(index_uuid)?)
         }
     }
-    pub fn new(&self, iv:IndexVec) -> Option<EAR> {
-        self._new(iv, 0, None)
+    fn _open_or_new(&self, iv:IndexVec,head:usize, index_uuid:Option<UuidIndex>) -> DbResult<EAR>{
+        // 和 _new 相同,但是已经存在的中间索引直接使用,只创建缺少的层
+        // 中间层储存的是数据而不是索引时返回 InvalidIndexPath,不覆盖用户的数据
+        if head+1 < iv.len() {
+            let index_uuid = match self.existing_element(&iv[head],index_uuid.clone())? {
+                None => self.establish_uuid_index(iv[head].clone(),index_uuid)?,
+                Some(v) => decode_pointer(&v).ok_or_else(|| DbError::InvalidIndexPath(format!("level {head} of the index path holds data, not an index")))?,
+            };
+            self._open_or_new(iv,head+1,Some(index_uuid))
+        } else {
+            self.new_database_objects(iv[head].clone(),Self::previous_uuid(index_uuid)?)
+        }
     }
-    pub fn open(&self, iv:IndexVec) -> Option<EAR> {
+    fn check_index_vec(iv:&IndexVec) -> DbResult<()> {
+        // IndexVec 至少需要 HeadMarking 和一个元素
+        if iv.len() < 2 {
+            return Err(DbError::InvalidIndexPath("IndexVec is too short".to_string()))
+        }
+        if iv.iter().any(|el| matches!(el, IVE::TupleList(_, _, 0))) {
+            return Err(DbError::InvalidIndexPath("tuple length must be at least 1".to_string()))
+        }
+        Ok(())
+    }
+    #[allow(clippy::new_ret_no_self, clippy::wrong_self_convention)]
+    pub fn new(&self, iv:impl IntoIndexVec) -> DbResult<EAR> {
+        // 整条路径在同一个事务中创建,不会留下创建到一半的中间索引
+        let iv = iv.into_index_vec()?;
+        Self::check_index_vec(&iv)?;
+        let ear = self.db.transaction(|tx| self.bind(tx)._new(iv.clone(), 0, None))?;
+        Ok(ear.bind(&self.db))
+    }
+    pub fn open_or_new(&self, iv:impl IntoIndexVec) -> DbResult<EAR> {
+        // 打开路径,缺少的层按照 new 的方式创建;和 new 不同,已经存在的中间层不会被覆盖,同一层下的其他数据保持不变
+        let iv = iv.into_index_vec()?;
+        Self::check_index_vec(&iv)?;
+        let ear = self.db.transaction(|tx| self.bind(tx)._open_or_new(iv.clone(), 0, None))?;
+        Ok(ear.bind(&self.db))
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn open(&self, iv:impl IntoIndexVec) -> DbResult<EAR> {
+        let iv = iv.into_index_vec()?;
+        Self::check_index_vec(&iv)?;
         self._open(iv, 0, None)
     }
-    pub fn delete(&self, iv:IndexVec) -> Result<bool,String> {
-        self.open(iv).unwrap().delete()
+    pub fn delete(&self, iv:impl IntoIndexVec) -> DbResult<()> {
+        self.open(iv)?.delete()
+    }
+    pub fn delete_subtree(&self, iv:impl IntoIndexVec) -> DbResult<()> {
+        // 删除元素,以及它指向的下一层开始的所有中间索引和集合
+        let iv = iv.into_index_vec()?;
+        Self::check_index_vec(&iv)?;
+        let mut purged = BTreeSet::new();
+        if !self.db.in_transaction() { // 先在事务外分批删除下层集合的元素
+            if let Some(child) = Self::child(&self._open(iv.clone(), 0, None)?)? {
+                purged = reachable(&self.db, vec![child])?;
+                for (kind, name) in &purged {
+                    catalog::purge_collection(&self.db, *kind, name)?;
+                }
+            }
+        }
+        self.db.transaction(|tx| {
+            // 元素已经删除的集合不再指向下一层,事务中重新标记的结果要加上之前的集合
+            let archive = self.bind(tx);
+            let ear = archive._open(iv.clone(), 0, None)?;
+            let mut collections = purged.clone();
+            if let Some(child) = Self::child(&ear)? {
+                collections.extend(reachable(tx, vec![child])?);
+            }
+            for (kind, name) in collections {
+                catalog::drop_collection(tx, kind, &name)?;
+            }
+            ear.delete()
+        })
+    }
+    pub fn drop(&self) -> DbResult<()> {
+        // 删除整个档案: 从档案头可以到达的集合、目录中属于这个档案的集合和档案头
+        // 先在事务外分批删除这些集合的元素,再在一个事务中删除剩下的键
+        let collections = |tx: &KvDbOperaObject| -> DbResult<BTreeSet<(CollectionKind, String)>> {
+            let head = tx.get(key_codec::archive_key(&self.name))?.ok_or_else(|| DbError::ArchiveNotFound(self.name.clone()))?;
+            let mut collections = reachable(tx, vec![head_index(&self.name, &head)?])?;
+            collections.extend(catalog::owned(tx, &self.name)?);
+            Ok(collections)
+        };
+        let mut purged = BTreeSet::new();
+        if !self.db.in_transaction() {
+            purged = collections(&self.db)?;
+            for (kind, name) in &purged {
+                catalog::purge_collection(&self.db, *kind, name)?;
+            }
+        }
+        self.db.transaction(|tx| {
+            for (kind, name) in collections(tx)?.into_iter().chain(purged.clone()) {
+                catalog::drop_collection(tx, kind, &name)?;
+            }
+            catalog::drop_collection(tx, CollectionKind::Archive, &self.name).map(|_| ())
+        })
+    }
+    fn child(ear:&EAR) -> DbResult<Option<UuidIndex>> {
+        // 元素指向的下一层,叶子元素(储存的是数据)返回 None
+        if let (AE::Set(obj), IVE::Set(member)) = (&ear.0, &ear.1) {
+            return Ok(obj.contains(member.as_bytes())?.then(|| Self::set_member_index(obj.name(), member)))
+        }
+        Ok(ear.raw()?.and_then(|v| decode_pointer(&v)))
+    }
+}
+
+fn children(db:&KvDbOperaObject, kind:CollectionKind, name:&str) -> DbResult<Vec<UuidIndex>> {
+    // 档案内部一个集合的元素指向的下一层,逐个读取元素使事务记录这些读取
+    let mut values = vec![];
+    match kind {
+        CollectionKind::Hashtable => {
+            let table = Hashtable::open(db.clone(), name.to_string())?;
+            for key in table.keys()? {
+                values.extend(table.get(&key?)?);
+            }
+        },
+        CollectionKind::OrderedHashtable => {
+            let table = SledHashtable::open(db.clone(), name.to_string())?;
+            for kv in table.iter() {
+                values.extend(table.get(&kv?.0)?);
+            }
+        },
+        CollectionKind::Set => {
+            let set = Set::open(db.clone(), name.to_string())?;
+            set.len()?; // 记录元数据的读取,成员被修改时重试
+            return set.iter().map(|m| Ok(Archive::set_member_index(name, &String::from_utf8_lossy(&m?)))).collect()
+        },
+        CollectionKind::List => {
+            let list = ListDb::open(db.clone(), name.to_string())?;
+            for i in 0..list.length()? {
+                values.extend(list.access(i)?);
+            }
+        },
+        CollectionKind::TupleList => {
+            // 直接读取底层列表的元素,迁移来的元组列表在第一次打开之前元组长度为 0
+            let list = ListDb::open_with_kind(db.clone(), name.to_string(), kind)?;
+            for i in 0..list.length()? {
+                values.extend(list.access(i)?);
+            }
+        },
+        _ => {},
+    }
+    Ok(values.iter().filter_map(|v| decode_pointer(v)).collect())
+}
+
+fn reachable(db:&KvDbOperaObject, roots:Vec<UuidIndex>) -> DbResult<BTreeSet<(CollectionKind, String)>> {
+    // 从 roots 出发可以到达的所有档案内部的集合,同一个名字可能对应几种集合
+    let mut ret = BTreeSet::new();
+    let mut seen = BTreeSet::new();
+    let mut stack = roots;
+    while let Some(name) = stack.pop() {
+        if !seen.insert(name.clone()) {
+            continue
+        }
+        for kind in INDEX_KINDS {
+            if catalog::exists(db, kind, &name)? && !catalog::is_top_level(db, kind, &name)? {
+                stack.extend(children(db, kind, &name)?);
+                ret.insert((kind, name.clone()));
+            }
+        }
+    }
+    Ok(ret)
+}
+
+fn upgrade_values(tx:&KvDbOperaObject, kind:CollectionKind, name:&str) -> DbResult<Vec<UuidIndex>> {
+    // 改写旧版本档案内部一个集合的值: 指向已有集合的 uuid 改为带标记的索引,以 POINTER 或 ESCAPE 开头的数据加上 ESCAPE
+    // 返回指向的下一层
+    let mut next = vec![];
+    let mut upgrade = |v:Vec<u8>| -> DbResult<Option<Vec<u8>>> {
+        if let Some(index) = String::from_utf8(v.clone()).ok().filter(|v| catalog::is_internal(v)) {
+            if INDEX_KINDS.iter().map(|&kind| catalog::exists(tx, kind, &index)).collect::<DbResult<Vec<_>>>()?.contains(&true) {
+                let ret = pointer(&index);
+                next.push(index);
+                return Ok(Some(ret))
+            }
+        }
+        Ok((encode_data(&v) != v).then(|| encode_data(&v)))
+    };
+    match kind {
+        CollectionKind::Hashtable => {
+            let table = Hashtable::open(tx.clone(), name.to_string())?;
+            for key in table.keys()?.collect::<DbResult<Vec<_>>>()? {
+                if let Some(v) = upgrade(table.get(&key)?.unwrap_or_default())? {
+                    table.insert(&key, &v)?;
+                }
+            }
+        },
+        CollectionKind::OrderedHashtable => {
+            let table = SledHashtable::open(tx.clone(), name.to_string())?;
+            for (key, value) in table.iter().collect::<DbResult<Vec<_>>>()? {
+                if let Some(v) = upgrade(value)? {
+                    table.insert(&key, &v)?;
+                }
+            }
+        },
+        CollectionKind::Set => {
+            let set = Set::open(tx.clone(), name.to_string())?;
+            return set.iter().map(|m| Ok(Archive::set_member_index(name, &String::from_utf8_lossy(&m?)))).collect()
+        },
+        CollectionKind::List | CollectionKind::TupleList => {
+            let list = ListDb::open_with_kind(tx.clone(), name.to_string(), kind)?;
+            for i in 0..list.length()? {
+                if let Some(v) = list.access(i)?.map(&mut upgrade).transpose()?.flatten() {
+                    list.overwrite(i, &v)?;
+                }
+            }
+        },
+        _ => {},
     }
+    Ok(next)
+}
+
+pub(crate) fn upgrade_legacy(db:&KvDbOperaObject) -> DbResult<usize> {
+    // 旧版本的档案头和中间层储存的是没有标记的 uuid,从档案头出发逐层改写,最后给档案头加上标记;返回改写的档案个数
+    // 每个档案在一个事务中改写,档案头有标记说明已经改写过
+    let mut keys = vec![];
+    for kv in db.scan_prefix([key_codec::TAG_ARCHIVE]) {
+        let (key, head) = kv?;
+        if decode_pointer(&head).is_none() {
+            keys.push(key);
+        }
+    }
+    for key in &keys {
+        db.transaction(|tx| {
+            let Some(head) = tx.get(key)?.filter(|head| decode_pointer(head).is_none()) else { return Ok(()) };
+            let head = String::from_utf8(head.to_vec()).map_err(|e| DbError::CorruptMetadata(format!("archive head: {e}")))?;
+            let (mut stack, mut seen) = (vec![head.clone()], BTreeSet::new());
+            while let Some(name) = stack.pop() {
+                if !seen.insert(name.clone()) {
+                    continue
+                }
+                for kind in INDEX_KINDS {
+                    if catalog::exists(tx, kind, &name)? {
+                        stack.extend(upgrade_values(tx, kind, &name)?);
+                    }
+                }
+            }
+            tx.insert(key, pointer(&head))
+        })?;
+    }
+    Ok(keys.len())
+}
+
+pub fn gc(db:&KvDbOperaObject, dry_run:bool) -> DbResult<GcReport> {
+    // 标记-清除: 从每一个档案头出发标记可以到达的集合,删除其余以 uuid 命名的集合
+    // 覆写中间层或删除元素之后,原来的下层集合就无法到达了
+    // 标记在一个事务中,期间档案被修改会重试;无法到达的集合不会再变得可以到达,逐个分批删除
+    // dry_run 时只统计不删除
+    let (mut report, garbage) = db.transaction(|tx| {
+        let mut heads = vec![];
+        for kv in tx.scan_prefix([key_codec::TAG_ARCHIVE]) {
+            let key = kv?.0;
+            if let Some(head) = tx.get(&key)? {
+                heads.push(head_index(&String::from_utf8_lossy(&key[1..]), &head)?); // 有旧版本的档案时不清理
+            }
+        }
+        let reachable = reachable(tx, heads.clone())?;
+        let report = GcReport { archives: heads.len(), reachable: reachable.len(), ..Default::default() };
+        let garbage = catalog::internal_collections(tx, &INDEX_KINDS)?.into_iter()
+            .filter(|c| !reachable.contains(c)).collect::<Vec<_>>();
+        Ok((report, garbage))
+    })?;
+    for (kind, name) in garbage {
+        report.keys += match dry_run {
+            true => catalog::key_count(db, kind, &name)?,
+            false => catalog::purge_collection(db, kind, &name)? + db.transaction(|tx| catalog::drop_collection(tx, kind, &name))?,
+        };
+        report.removed.push((kind, name));
+    }
+    Ok(report)
 }
 
 #[test]
 fn test1() {
-    let a = Archive::open_object(KvDbOperaObject::new(initialization("/tmp/welcome-to-sled".to_string()))
-                                , "12_+_514".to_string());
+    use crate::sdk::db::kv_operation::temporary;
+    let a = Archive::new_object(KvDbOperaObject::new(temporary())
+                                , "12_+_514".to_string()).unwrap();
     let iv = vec![IVE::HeadMarking, IVE::List(3), IVE::Hashtable("lst".to_string())];
-    let lev = a.open(iv.clone()).unwrap();
-    dbg!(&lev.overwrite(&vec![1, 1, 4, 5, 1, 4]));
-    dbg!(&lev.access());
+    assert!(matches!(a.open(iv.clone()), Err(DbError::NotFound(_)))); // 还没有 new
+    let lev = a.new(iv.clone()).unwrap();
+    dbg!(&lev.overwrite(&[1, 1, 4, 5, 1, 4]));
+    assert_eq!(a.open(iv.clone()).unwrap().access(), Ok(Some(vec![1, 1, 4, 5, 1, 4])));
     dbg!(&a.delete(iv.clone()));
     // || dbg!(&lev.delete())
-    dbg!(&lev.access());
+    assert_eq!(lev.access(), Ok(None));
+    assert!(matches!(a.open(vec![IVE::HeadMarking]), Err(DbError::InvalidIndexPath(_))));
+
+    let iv = vec![IVE::HeadMarking, IVE::SledHashtable("user".to_string()), IVE::TupleList(0, 1, 2)];
+    a.new(iv.clone()).unwrap().overwrite(b"sled").unwrap();
+    assert_eq!(a.open(iv).unwrap().access(), Ok(Some(b"sled".to_vec())));
+
+    let iv = vec![IVE::HeadMarking, IVE::Set("tag".to_string()), IVE::Set("member".to_string())];
+    assert!(a.open(iv.clone()).is_err());
+    let ear = a.new(iv.clone()).unwrap();
+    assert_eq!(ear.access(), Ok(None));
+    ear.overwrite(&[]).unwrap();
+    assert!(ear.overwrite(b"x").is_err());
+    assert_eq!(a.open(iv.clone()).unwrap().access(), Ok(Some(vec![])));
+    a.delete(iv.clone()).unwrap();
+    assert_eq!(a.open(iv).unwrap().access(), Ok(None));
     // dbg!(&l);
-}
\ No newline at end of file
+}
+
+#[test]
+fn gc_test() {
+    use crate::sdk::db::kv_operation::temporary;
+    use crate::sdk::db::metadata::Metadata;
+    let db = KvDbOperaObject::new(temporary());
+    ListDb::new(db.clone(), "plain".to_string()).unwrap().append(b"1").unwrap();
+    let a = Archive::new_object(db.clone(), "a".to_string()).unwrap();
+    let alice = vec![IVE::HeadMarking, IVE::Hashtable("alice".to_string()), IVE::List(0), IVE::Set("tags".to_string())];
+    let bob = vec![IVE::HeadMarking, IVE::Hashtable("bob".to_string()), IVE::List(0)];
+    a.new(alice.clone()).unwrap().overwrite(&[]).unwrap();
+    a.new(bob.clone()).unwrap().overwrite(b"1").unwrap();
+    assert_eq!(gc(&db, false).map(|r| (r.archives, r.reachable, r.removed.len())), Ok((1, 4, 0)));
+
+    // 覆写中间层后,原来的列表和集合无法到达
+    a.new(vec![IVE::HeadMarking, IVE::Hashtable("alice".to_string()), IVE::List(1)]).unwrap().overwrite(b"2").unwrap();
+    let dry = gc(&db, true).unwrap();
+    assert_eq!(dry.removed.iter().map(|(kind, _)| *kind).collect::<Vec<_>>(), vec![CollectionKind::List, CollectionKind::Set]);
+    assert!(dry.keys > 0);
+    assert_eq!(gc(&db, false), Ok(dry));
+    assert_eq!(gc(&db, false).map(|r| r.removed.len()), Ok(0));
+    assert_eq!(a.open(bob.clone()).unwrap().access(), Ok(Some(b"1".to_vec())));
+
+    // 目录出现之前创建的 uuid 集合也会被清理,用户直接创建的不会
+    let orphan = Uuid::new_v4().to_string();
+    ListDb::new(db.clone(), orphan.clone()).unwrap();
+    assert_eq!(gc(&db, false).map(|r| r.removed.len()), Ok(0));
+    db.delete(catalog::entry_key(CollectionKind::List, &orphan)).unwrap();
+    assert_eq!(gc(&db, false).map(|r| r.removed), Ok(vec![(CollectionKind::List, orphan)]));
+
+    // 迁移来的元组列表在第一次打开之前元组长度为 0,标记时直接读取底层列表
+    let tuples = vec![IVE::HeadMarking, IVE::Hashtable("carol".to_string()), IVE::TupleList(0, 1, 2), IVE::List(0)];
+    a.new(tuples.clone()).unwrap().overwrite(b"3").unwrap();
+    let tuple_list = option_vec_to_string(a.open(tuples[..2].to_vec()).unwrap().access().unwrap()).unwrap();
+    let raw = ListDb::open_with_kind(db.clone(), tuple_list, CollectionKind::TupleList).unwrap();
+    raw.write_metadata(&Metadata { arity: 0, ..raw.metadata().unwrap() }).unwrap();
+    assert_eq!(gc(&db, false).map(|r| r.removed.len()), Ok(0));
+    a.delete_subtree(tuples[..2].to_vec()).unwrap();
+    assert!(matches!(a.open(vec![IVE::HeadMarking, IVE::TupleList(0, 0, 0)]), Err(DbError::InvalidIndexPath(_))));
+
+    // 删除子树
+    let alice = vec![IVE::HeadMarking, IVE::Hashtable("alice".to_string())];
+    a.delete_subtree(alice.clone()).unwrap();
+    assert!(matches!(a.open([alice, vec![IVE::List(1)]].concat()), Err(DbError::NotFound(_))));
+    assert_eq!(gc(&db, false).map(|r| (r.reachable, r.removed.len())), Ok((2, 0)));
+
+    // 再次创建同名档案沿用原来的档案头,什么也不会被清理
+    let b = Archive::new_object(db.clone(), "b".to_string()).unwrap();
+    b.new(bob.clone()).unwrap().overwrite(b"old").unwrap();
+    let b = Archive::new_object(db.clone(), "b".to_string()).unwrap();
+    assert_eq!(b.open(bob.clone()).unwrap().access(), Ok(Some(b"old".to_vec())));
+    assert_eq!(gc(&db, false).map(|r| r.removed.len()), Ok(0));
+
+    // 删除档案后只剩下用户的列表
+    a.drop().unwrap();
+    b.drop().unwrap();
+    assert!(matches!(a.drop(), Err(DbError::ArchiveNotFound(_))));
+    let keys = db.scan_prefix([]).map(|kv| kv.unwrap().0.to_vec()).collect::<Vec<_>>();
+    assert_eq!(keys.len(), 3); // 列表 plain 的元数据、元素和目录项
+    assert_eq!(gc(&db, false), Ok(GcReport::default()));
+}
+
+#[test]
+fn index_path_test() {
+    use crate::sdk::db::kv_operation::temporary;
+    let path = r"/h:users/sh:a\/b\\c/s:标签/l:8/t:8,1,2";
+    let iv = path.parse::<IndexPath>().unwrap();
+    assert_eq!(iv.0, vec![IVE::HeadMarking, IVE::Hashtable("users".to_string()), IVE::SledHashtable(r"a/b\c".to_string()),
+        IVE::Set("标签".to_string()), IVE::List(8), IVE::TupleList(8, 1, 2)]);
+    assert_eq!(iv.to_string(), path);
+    assert_eq!("/h:".parse::<IndexPath>().map(|p| p.0), Ok(vec![IVE::HeadMarking, IVE::Hashtable(String::new())]));
+
+    let error = |path: &str| match path.parse::<IndexPath>() {
+        Err(DbError::InvalidIndexPath(e)) => e,
+        other => panic!("{path}: {other:?}"),
+    };
+    assert_eq!(error(""), r#"path needs at least one element at position 0 in """#);
+    assert_eq!(error("h:users"), r#"expected '/' but found 'h' at position 0 in "h:users""#);
+    assert_eq!(error("/x:1"), r#"unknown element kind "x", expected h, sh, s, l or t at position 1 in "/x:1""#);
+    assert_eq!(error("/h"), r#"expected ':' after the element kind at position 2 in "/h""#);
+    assert_eq!(error("/用户:1"), r#"expected ':' after the element kind but found '用' at position 1 in "/用户:1""#);
+    assert_eq!(error(r"/h:a\b"), r#"invalid escape '\b', only '\/' and '\\' are allowed at position 5 in "/h:a\\b""#);
+    assert_eq!(error("/l:-1"), r#"list index "-1" is not a valid number (invalid digit found in string) at position 3 in "/l:-1""#);
+    assert!(error("/t:1,2,0").contains("tuple length must be at least 1") && error("/t:1,2,0").contains("position 7"));
+    assert_eq!(error("/t:1,2"), r#"TupleList needs 'index,tuple index,tuple length' but found "1,2" at position 3 in "/t:1,2""#);
+    assert!(error("/t:1,2,x").contains("tuple length \"x\"") && error("/t:1,2,x").contains("position 7"));
+
+    let a = Archive::new_object(KvDbOperaObject::new(temporary()), "path".to_string()).unwrap();
+    a.new(r"/h:a\/b/l:3").unwrap().overwrite(b"1").unwrap();
+    assert_eq!(a.open(vec![IVE::HeadMarking, IVE::Hashtable("a/b".to_string()), IVE::List(3)]).unwrap().access(), Ok(Some(b"1".to_vec())));
+    assert!(matches!(a.open("/h:a/b/l:3"), Err(DbError::InvalidIndexPath(_)))); // b 不是种类
+}
+
+#[test]
+fn open_or_new_test() {
+    use crate::sdk::db::kv_operation::temporary;
+    let a = Archive::new_object(KvDbOperaObject::new(temporary()), "nested".to_string()).unwrap();
+    a.open_or_new("/h:users/l:8").unwrap().overwrite(b"a").unwrap();
+    a.open_or_new("/h:users/l:9").unwrap().overwrite(b"b").unwrap();
+    a.open_or_new("/h:users/l:8").unwrap().overwrite(b"c").unwrap();
+    assert_eq!(a.open("/h:users/l:8").unwrap().access(), Ok(Some(b"c".to_vec())));
+    assert_eq!(a.open("/h:users/l:9").unwrap().access(), Ok(Some(b"b".to_vec()))); // new 会覆盖 users,open_or_new 不会
+    a.new("/h:users/l:1").unwrap();
+    assert_eq!(a.open("/h:users/l:9").unwrap().access(), Ok(None));
+
+    // 中间层是数据时报错,数据保持不变
+    a.open_or_new("/h:name").unwrap().overwrite(b"alice").unwrap();
+    assert!(matches!(a.open_or_new("/h:name/h:first"), Err(DbError::InvalidIndexPath(_))));
+    assert!(matches!(a.open_or_new("/h:name/l:0/s:x"), Err(DbError::InvalidIndexPath(_))));
+    assert_eq!(a.open("/h:name").unwrap().access(), Ok(Some(b"alice".to_vec())));
+
+    // 储存的数据看起来像 uuid(甚至就是档案内部一个集合的名字)时仍然是数据,以标记字节开头的数据原样读出
+    let users = decode_pointer(&a.open("/h:users").unwrap().access().unwrap().unwrap()).unwrap();
+    a.open_or_new("/h:u").unwrap().overwrite(users.as_bytes()).unwrap();
+    assert!(matches!(a.open_or_new("/h:u/l:1"), Err(DbError::InvalidIndexPath(_))));
+    assert!(matches!(a.open("/h:u/l:1"), Err(DbError::InvalidIndexPath(_))));
+    assert_eq!(a.open("/h:u").unwrap().access(), Ok(Some(users.into_bytes())));
+    for data in [&[POINTER, b'x'][..], &[ESCAPE], &[ESCAPE, ESCAPE, 1]] {
+        a.open_or_new("/h:bin").unwrap().overwrite(data).unwrap();
+        assert_eq!(a.open("/h:bin").unwrap().access(), Ok(Some(data.to_vec())));
+        assert!(matches!(a.open_or_new("/h:bin/l:0"), Err(DbError::InvalidIndexPath(_))));
+    }
+    a.open_or_new("/h:other/t:0,0,2/t:0,0,3").unwrap();
+    assert!(matches!(a.open_or_new("/h:other/t:0,0,3"), Err(DbError::TupleArity { .. })));
+}
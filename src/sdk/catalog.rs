@@ -0,0 +1,472 @@
+// 集合目录
+// 每个集合(和档案)创建时登记 [TAG_CATALOG][种类][转义后的名字] -> [创建时间 u64][所属档案的名字]
+// 同一个名字可以被不同种类的集合使用,所以目录以 (种类, 名字) 为键
+// 档案内部以 uuid 命名的集合属于这个档案,不出现在 collections() 中,删除档案时一起删除
+use std::collections::BTreeSet;
+use crate::sdk::archive::Archive;
+use crate::sdk::db::key_codec;
+use crate::sdk::db::kv_operation::{KvDbOperaObject, KvDbOperaTrait};
+use crate::sdk::db::metadata::{now_millis, CollectionKind, Metadata};
+use crate::sdk::db::list_db::ListDb;
+use crate::sdk::db::tuple_list_db::TupleList;
+use crate::sdk::db::hashtable_zipper_db::Hashtable;
+use crate::sdk::db::hashtable_sled_db::Hashtable as SledHashtable;
+use crate::sdk::db::sorted_map_db::SortedMap;
+use crate::sdk::db::set_db::Set;
+use crate::sdk::db::deque_db::Deque;
+use crate::sdk::error::{DbError, DbResult};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectionInfo {
+    pub name: String,
+    pub kind: CollectionKind,
+    pub arity: u16, // 元组长度,不是元组列表时为 0
+    pub created_at: u64, // 创建时间(unix 毫秒),从旧格式迁移的集合为 0
+    pub size: usize, // 和 python 的 len() 相同,档案为其内部集合的个数
+    pub owner: Option<String>, // 所属的档案
+}
+
+const BATCH: usize = 1000; // 分批删除大集合的键时,每个事务最多删除的键的个数
+
+#[derive(Debug, Clone)]
+struct Entry {
+    kind: CollectionKind,
+    name: String,
+    created_at: u64,
+    owner: Option<String>,
+}
+
+const KINDS: [CollectionKind; 8] = [
+    CollectionKind::List, CollectionKind::TupleList, CollectionKind::Hashtable, CollectionKind::OrderedHashtable,
+    CollectionKind::SortedMap, CollectionKind::Set, CollectionKind::Deque, CollectionKind::Archive,
+]; // 登记在目录中的种类,哈希表内部的拉链不登记
+
+pub(crate) fn entry_key(kind: CollectionKind, name: &str) -> Vec<u8> {
+    let mut ret = vec![key_codec::TAG_CATALOG, kind as u8];
+    key_codec::escape_into(&mut ret, name.as_bytes());
+    ret
+}
+
+fn tag(kind: CollectionKind) -> u8 {
+    // 集合的键的类型标记,有序哈希表用的是 TAG_KV
+    match kind {
+        CollectionKind::OrderedHashtable => key_codec::TAG_KV,
+        CollectionKind::Archive => key_codec::TAG_ARCHIVE,
+        kind => key_codec::kind_tag(kind),
+    }
+}
+
+impl Entry {
+    fn encode(&self) -> Vec<u8> {
+        [&self.created_at.to_be_bytes()[..], self.owner.as_deref().unwrap_or("").as_bytes()].concat()
+    }
+
+    fn decode(key: &[u8], value: &[u8]) -> DbResult<Self> {
+        let corrupt = || DbError::CorruptMetadata(format!("catalog entry {key:?}"));
+        let kind = CollectionKind::from_u8(*key.get(1).ok_or_else(corrupt)?)?;
+        let (name, _) = key_codec::unescape(&key[2..]).ok_or_else(corrupt)?;
+        let created_at = u64::from_be_bytes(value.get(..8).ok_or_else(corrupt)?.try_into().map_err(|_| corrupt())?);
+        let owner = String::from_utf8(value[8..].to_vec()).map_err(|_| corrupt())?;
+        Ok(Entry {
+            kind,
+            name: String::from_utf8(name).map_err(|_| corrupt())?,
+            created_at,
+            owner: (!owner.is_empty()).then_some(owner),
+        })
+    }
+
+    fn get(db: &KvDbOperaObject, kind: CollectionKind, name: &str) -> DbResult<Option<Self>> {
+        match db.get(entry_key(kind, name))? {
+            Some(value) => Ok(Some(Self::decode(&entry_key(kind, name), &value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn save(&self, db: &KvDbOperaObject) -> DbResult<()> {
+        db.insert(entry_key(self.kind, &self.name), self.encode())
+    }
+}
+
+pub(crate) fn register(db: &KvDbOperaObject, kind: CollectionKind, name: &str) -> DbResult<()> {
+    // 在创建集合的事务中调用,已经登记过的保持不变
+    if KINDS.contains(&kind) && Entry::get(db, kind, name)?.is_none() {
+        Entry { kind, name: name.to_string(), created_at: now_millis(), owner: None }.save(db)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn adopt(db: &KvDbOperaObject, kind: CollectionKind, name: &str, owner: &str) -> DbResult<()> {
+    // 标记为档案内部的集合
+    let mut entry = Entry::get(db, kind, name)?.unwrap_or(Entry { kind, name: name.to_string(), created_at: now_millis(), owner: None });
+    if entry.owner.as_deref() != Some(owner) {
+        entry.owner = Some(owner.to_string());
+        entry.save(db)?;
+    }
+    Ok(())
+}
+
+fn entries(db: &KvDbOperaObject) -> DbResult<Vec<Entry>> {
+    db.scan_prefix([key_codec::TAG_CATALOG]).map(|kv| {
+        let (key, value) = kv?;
+        Entry::decode(&key, &value)
+    }).collect()
+}
+
+fn find(db: &KvDbOperaObject, name: &str, kind: Option<CollectionKind>) -> DbResult<Entry> {
+    // 没有指定种类时,名字只能对应一个集合
+    let kinds = match kind {
+        Some(kind) => vec![kind],
+        None => KINDS.to_vec(),
+    };
+    let mut found = vec![];
+    for kind in kinds {
+        if let Some(entry) = Entry::get(db, kind, name)? {
+            found.push(entry);
+        }
+    }
+    match found.len() {
+        0 => Err(DbError::NotFound(format!("collection {name}"))),
+        1 => Ok(found.remove(0)),
+        _ => {
+            let kinds = found.iter().map(|e| e.kind.name()).collect::<Vec<_>>().join(", ");
+            Err(DbError::NameConflict(format!("{name} is used by several collections ({kinds}), specify the kind")))
+        }
+    }
+}
+
+fn info(db: &KvDbOperaObject, entry: Entry, owned: usize) -> DbResult<CollectionInfo> {
+    let (db, name) = (db.clone(), entry.name.clone());
+    let meta = |db: &KvDbOperaObject| -> DbResult<Metadata> {
+        let key = key_codec::meta_key(&key_codec::prefix(tag(entry.kind), &entry.name));
+        let v = db.get(key)?.ok_or_else(|| DbError::NotFound(format!("{} {}", entry.kind.name(), entry.name)))?;
+        Metadata::decode(&v)?.current()
+    };
+    let (arity, created_at, size) = match entry.kind {
+        CollectionKind::Archive => (0, entry.created_at, owned),
+        CollectionKind::List => {
+            let list = ListDb::open(db, name)?;
+            (0, list.metadata()?.created_at, list.length()?)
+        }
+        CollectionKind::TupleList => {
+            let m = meta(&db)?;
+            match m.arity {
+                0 => (0, m.created_at, m.length as usize), // 迁移来的元组列表第一次打开前不知道元组长度,返回元素个数
+                arity => (arity, m.created_at, TupleList::open(db, name, arity)?.length()?),
+            }
+        }
+        CollectionKind::Hashtable => (0, meta(&db)?.created_at, Hashtable::open(db, name)?.len()?),
+        CollectionKind::OrderedHashtable => (0, meta(&db)?.created_at, SledHashtable::open(db, name)?.len()?),
+        CollectionKind::SortedMap => (0, meta(&db)?.created_at, SortedMap::open(db, name)?.len()?),
+        CollectionKind::Set => (0, meta(&db)?.created_at, Set::open(db, name)?.len()?),
+        CollectionKind::Deque => (0, meta(&db)?.created_at, Deque::open(db, name)?.len()?),
+        kind => return Err(DbError::CorruptMetadata(format!("{} should not be in the catalog", kind.name()))),
+    };
+    Ok(CollectionInfo { name: entry.name, kind: entry.kind, arity, created_at, size, owner: entry.owner })
+}
+
+fn owned_by(entries: &[Entry], archive: &str) -> usize {
+    entries.iter().filter(|e| e.owner.as_deref() == Some(archive)).count()
+}
+
+fn key_spaces(db: &KvDbOperaObject, kind: CollectionKind, name: &str) -> DbResult<Vec<(u8, String)>> {
+    // 集合的所有键都以 key_codec::prefix(标记, 名字) 开头,返回 (标记, 名字) 的列表
+    // 拉链法哈希表还包括所有拉链,拉链的名字以 "{哈希表的名字}:" 开头
+    let mut ret = vec![(tag(kind), name.to_string())];
+    if kind == CollectionKind::Hashtable && exists(db, kind, name)? {
+        for (kind, name) in Hashtable::open(db.clone(), name.to_string())?.chains()? {
+            ret.push((key_codec::kind_tag(kind), name));
+        }
+    }
+    Ok(ret)
+}
+
+fn delete_prefix(tx: &KvDbOperaObject, prefix: &[u8]) -> DbResult<usize> {
+    let mut count = 0;
+    for kv in tx.scan_prefix(prefix) { // 扫描的范围提交前被修改时重试
+        let key = kv?.0;
+        tx.delete(&key)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn move_prefix(tx: &KvDbOperaObject, from: &[u8], to: &[u8]) -> DbResult<()> {
+    for kv in tx.scan_prefix(from) {
+        let (key, value) = kv?;
+        tx.insert([to, &key[from.len()..]].concat(), value)?;
+        tx.delete(&key)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn purge_collection(db: &KvDbOperaObject, kind: CollectionKind, name: &str) -> DbResult<usize> {
+    // 在事务外分批删除集合的键,每批最多 BATCH 个键,只留下集合自己的元数据,之后 drop_collection 在事务中删除元数据和目录项
+    // 先删除拉链(包括拉链的元数据),最后删除集合自己的元素;返回删除的键的个数
+    // 已经在事务中时什么也不做,所有的键由调用者在同一个事务中删除
+    if db.in_transaction() || kind == CollectionKind::Archive { // 档案只有档案头一个键
+        return Ok(0)
+    }
+    let mut count = 0;
+    for (i, (tag, space)) in key_spaces(db, kind, name)?.iter().enumerate().rev() {
+        let prefix = key_codec::prefix(*tag, space);
+        let meta = key_codec::meta_key(&prefix);
+        loop {
+            let keys = db.scan_prefix(&prefix).map(|kv| kv.map(|(k, _)| k))
+                .filter(|k| i != 0 || !matches!(k, Ok(k) if *k == meta))
+                .take(BATCH).collect::<DbResult<Vec<_>>>()?;
+            if keys.is_empty() {
+                break
+            }
+            db.transaction(|tx| keys.iter().try_for_each(|key| tx.delete(key)))?;
+            count += keys.len();
+        }
+    }
+    Ok(count)
+}
+
+pub(crate) fn exists(db: &KvDbOperaObject, kind: CollectionKind, name: &str) -> DbResult<bool> {
+    // 集合的元数据(档案为档案头)是否存在
+    let key = match kind {
+        CollectionKind::Archive => key_codec::archive_key(name),
+        kind => key_codec::meta_key(&key_codec::prefix(tag(kind), name)),
+    };
+    Ok(db.get(key)?.is_some())
+}
+
+pub(crate) fn is_internal(name: &str) -> bool {
+    // 档案内部的集合以 uuid 命名,集合成员指向的下一层为 "{uuid}/{成员}"
+    name.get(..36).is_some_and(|id| uuid::Uuid::parse_str(id).is_ok())
+}
+
+pub(crate) fn is_top_level(db: &KvDbOperaObject, kind: CollectionKind, name: &str) -> DbResult<bool> {
+    // 用户直接创建的集合(目录中没有所属的档案)
+    Ok(Entry::get(db, kind, name)?.is_some_and(|e| e.owner.is_none()))
+}
+
+pub(crate) fn owned(db: &KvDbOperaObject, archive: &str) -> DbResult<Vec<(CollectionKind, String)>> {
+    Ok(entries(db)?.into_iter().filter(|e| e.owner.as_deref() == Some(archive)).map(|e| (e.kind, e.name)).collect())
+}
+
+pub(crate) fn key_count(db: &KvDbOperaObject, kind: CollectionKind, name: &str) -> DbResult<usize> {
+    let mut count = 0;
+    for (tag, name) in key_spaces(db, kind, name)? {
+        count += db.scan_prefix(key_codec::prefix(tag, &name)).count();
+    }
+    Ok(count)
+}
+
+pub(crate) fn drop_collection(tx: &KvDbOperaObject, kind: CollectionKind, name: &str) -> DbResult<usize> {
+    // 删除集合的所有键和目录项,返回删除的键的个数(不包括目录项)
+    let mut count = 0;
+    for (tag, name) in key_spaces(tx, kind, name)? {
+        count += delete_prefix(tx, &key_codec::prefix(tag, &name))?;
+    }
+    tx.delete(entry_key(kind, name))?;
+    Ok(count)
+}
+
+fn stored(db: &KvDbOperaObject, kind: CollectionKind) -> DbResult<Vec<String>> {
+    // 扫描数据库中某一种集合的元数据(档案为档案头),返回所有名字
+    let mut ret = vec![];
+    for kv in db.scan_prefix([tag(kind)]) {
+        let key = kv?.0;
+        let Some((name, rest)) = key_codec::unescape(&key[1..]) else { continue };
+        let is_head = match kind {
+            CollectionKind::Archive => rest.is_empty(), // 档案头直接储存在前缀上
+            _ => rest == key_codec::meta_key(&[]).as_slice(),
+        };
+        if let (true, Ok(name)) = (is_head, String::from_utf8(name)) {
+            ret.push(name);
+        }
+    }
+    Ok(ret)
+}
+
+pub(crate) fn internal_collections(db: &KvDbOperaObject, kinds: &[CollectionKind]) -> DbResult<BTreeSet<(CollectionKind, String)>> {
+    // 所有以 uuid 命名的集合(包括目录出现之前创建的),用户直接创建的除外;目录中属于某个档案的集合即使已经没有键也包括在内
+    let mut ret = BTreeSet::new();
+    for &kind in kinds {
+        for name in stored(db, kind)? {
+            if is_internal(&name) && !is_top_level(db, kind, &name)? {
+                ret.insert((kind, name));
+            }
+        }
+    }
+    ret.extend(entries(db)?.into_iter().filter(|e| e.owner.is_some()).map(|e| (e.kind, e.name)));
+    Ok(ret)
+}
+
+pub trait Catalog {
+    fn collections(&self) -> DbResult<Vec<CollectionInfo>>; // 所有集合和档案(不包括档案内部的集合),按种类和名字排序
+    fn describe(&self, name: &str, kind: Option<CollectionKind>) -> DbResult<CollectionInfo>;
+    fn drop(&self, name: &str, kind: Option<CollectionKind>) -> DbResult<()>;
+    fn rename(&self, old: &str, new: &str, kind: Option<CollectionKind>) -> DbResult<()>;
+}
+
+impl Catalog for KvDbOperaObject {
+    fn collections(&self) -> DbResult<Vec<CollectionInfo>> {
+        let entries = entries(self)?;
+        entries.iter().filter(|e| e.owner.is_none())
+            .map(|e| info(self, e.clone(), owned_by(&entries, &e.name)))
+            .collect()
+    }
+
+    fn describe(&self, name: &str, kind: Option<CollectionKind>) -> DbResult<CollectionInfo> {
+        let entry = find(self, name, kind)?;
+        let owned = match entry.kind {
+            CollectionKind::Archive => owned_by(&entries(self)?, name),
+            _ => 0,
+        };
+        info(self, entry, owned)
+    }
+
+    fn drop(&self, name: &str, kind: Option<CollectionKind>) -> DbResult<()> {
+        // 删除集合的所有键,删除档案时同时删除它内部的所有集合
+        // 先在事务外分批删除元素,再在一个事务中删除剩下的键和目录项
+        let entry = find(self, name, kind)?;
+        if entry.kind == CollectionKind::Archive && exists(self, entry.kind, name)? {
+            return Archive::open_object(self.clone(), name.to_string())?.drop()
+        }
+        for (kind, name) in owned(self, name)? {
+            purge_collection(self, kind, &name)?;
+        }
+        purge_collection(self, entry.kind, name)?;
+        self.transaction(|tx| {
+            let entry = find(tx, name, kind)?;
+            if entry.kind == CollectionKind::Archive && exists(tx, entry.kind, name)? {
+                return Archive::open_object(tx.clone(), name.to_string())?.drop()
+            }
+            for (kind, name) in owned(tx, name)? {
+                drop_collection(tx, kind, &name)?;
+            }
+            drop_collection(tx, entry.kind, name).map(|_| ())
+        })
+    }
+
+    fn rename(&self, old: &str, new: &str, kind: Option<CollectionKind>) -> DbResult<()> {
+        // 把集合的所有键移动到新名字下;档案内部的集合被上一层引用,不能改名
+        // 整个改名在一个事务中(不分批),读取者和写入者不会看到移动到一半的集合
+        self.transaction(|tx| {
+            let entry = find(tx, old, kind)?;
+            if let Some(owner) = &entry.owner {
+                return Err(DbError::NameConflict(format!("{old} belongs to archive {owner} and cannot be renamed")))
+            }
+            let target = key_codec::prefix(tag(entry.kind), new);
+            if Entry::get(tx, entry.kind, new)?.is_some() || tx.scan_prefix(&target).next().is_some() {
+                return Err(DbError::NameConflict(format!("{} {new} already exists", entry.kind.name())))
+            }
+            for (tag, name) in key_spaces(tx, entry.kind, old)? {
+                let renamed = format!("{new}{}", &name[old.len()..]); // 拉链 "{old}:x" 改为 "{new}:x"
+                move_prefix(tx, &key_codec::prefix(tag, &name), &key_codec::prefix(tag, &renamed))?;
+            }
+            tx.delete(entry_key(entry.kind, old))?;
+            Entry { name: new.to_string(), ..entry.clone() }.save(tx)?;
+            if entry.kind == CollectionKind::Archive {
+                for (kind, name) in owned(tx, old)? {
+                    adopt(tx, kind, &name, new)?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+pub fn register_existing(db: &KvDbOperaObject) -> DbResult<usize> {
+    // 登记目录出现之前创建的集合和档案,返回新登记的个数
+    // 需要扫描整个数据库;档案内部以 uuid 命名的集合无法知道属于哪个档案,不登记(由 archive::gc 清理)
+    let mut count = 0;
+    for kind in KINDS {
+        for name in stored(db, kind)? {
+            if !is_internal(&name) && Entry::get(db, kind, &name)?.is_none() {
+                register(db, kind, &name)?;
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+#[test]
+fn catalog_test() {
+    use crate::sdk::archive::{Archive, IVE};
+    use crate::sdk::db::kv_operation::temporary;
+    let db = KvDbOperaObject::new(temporary());
+    ListDb::new(db.clone(), "x".to_string()).unwrap().extend(&[b"1", b"2"]).unwrap();
+    TupleList::new(db.clone(), "t".to_string(), 3).unwrap().append(&[&b"a"[..], b"b", b"c"]).unwrap();
+    let table = Hashtable::new(db.clone(), "x".to_string()).unwrap();
+    table.set_load_factor(100).unwrap();
+    for i in 0..40u32 {
+        table.insert(&i.to_be_bytes(), b"v").unwrap();
+    }
+    let archive = Archive::new_object(db.clone(), "users".to_string()).unwrap();
+    archive.new(vec![IVE::HeadMarking, IVE::Hashtable("alice".to_string()), IVE::List(0)]).unwrap().overwrite(b"1").unwrap();
+
+    let listed = db.collections().unwrap().into_iter().map(|c| (c.kind, c.name, c.arity, c.size)).collect::<Vec<_>>();
+    assert_eq!(listed, vec![
+        (CollectionKind::List, "x".to_string(), 0, 2),
+        (CollectionKind::TupleList, "t".to_string(), 3, 1),
+        (CollectionKind::Hashtable, "x".to_string(), 0, 40),
+        (CollectionKind::Archive, "users".to_string(), 0, 2), // 档案内部的哈希表和列表
+    ]);
+    assert!(matches!(db.describe("x", None), Err(DbError::NameConflict(_))));
+    assert_eq!(db.describe("x", Some(CollectionKind::List)).map(|c| c.size), Ok(2));
+    assert!(db.describe("t", None).unwrap().created_at > 0);
+    assert!(matches!(db.describe("nope", None), Err(DbError::NotFound(_))));
+
+    // 改名移动所有键(包括哈希表的拉链)
+    db.rename("x", "y", Some(CollectionKind::Hashtable)).unwrap();
+    assert!(matches!(db.rename("t", "x", None), Ok(())));
+    assert!(matches!(db.rename("x", "y", Some(CollectionKind::List)), Ok(())));
+    assert!(matches!(db.rename("y", "x", Some(CollectionKind::TupleList)), Err(DbError::NotFound(_))));
+    assert!(matches!(db.rename("y", "z", None), Err(DbError::NameConflict(_)))); // 列表 y 和哈希表 y
+    ListDb::new(db.clone(), "z".to_string()).unwrap();
+    assert!(matches!(db.rename("z", "y", None), Err(DbError::NameConflict(_)))); // 列表 y 已存在
+    let table = Hashtable::open(db.clone(), "y".to_string()).unwrap();
+    assert_eq!((table.len(), table.get(&7u32.to_be_bytes())), (Ok(40), Ok(Some(b"v".to_vec()))));
+    assert!(Hashtable::open(db.clone(), "x".to_string()).is_err());
+    db.rename("users", "people", None).unwrap();
+    let archive = Archive::open_object(db.clone(), "people".to_string()).unwrap();
+    assert_eq!(archive.open(vec![IVE::HeadMarking, IVE::Hashtable("alice".to_string()), IVE::List(0)]).unwrap().access(), Ok(Some(b"1".to_vec())));
+    assert_eq!(db.describe("people", None).map(|c| c.size), Ok(2));
+
+    // 删除集合和档案的所有键,只剩下列表 y
+    db.drop("y", Some(CollectionKind::Hashtable)).unwrap();
+    db.drop("x", None).unwrap();
+    db.drop("people", None).unwrap();
+    db.drop("z", None).unwrap();
+    assert_eq!(db.collections().map(|c| c.len()), Ok(1));
+    let list_tag = key_codec::kind_tag(CollectionKind::List);
+    let remaining = db.scan_prefix([]).filter(|kv| kv.as_ref().unwrap().0[0] != list_tag).count();
+    assert_eq!(remaining, 1); // 列表 y 的目录项
+    assert!(matches!(db.drop("people", None), Err(DbError::NotFound(_))));
+
+    // 目录出现之前创建的集合
+    db.delete(entry_key(CollectionKind::List, "y")).unwrap();
+    assert_eq!(db.collections().map(|c| c.len()), Ok(0));
+    assert_eq!((register_existing(&db), register_existing(&db)), (Ok(1), Ok(0)));
+    assert_eq!(db.describe("y", None).map(|c| (c.kind, c.size)), Ok((CollectionKind::List, 2)));
+}
+
+#[test]
+fn batch_test() {
+    // 超过 BATCH 个键的集合改名,以及分批删除
+    use crate::sdk::db::kv_operation::temporary;
+    let db = KvDbOperaObject::new(temporary());
+    let items = (0..2500u32).map(|i| i.to_be_bytes()).collect::<Vec<_>>();
+    ListDb::new(db.clone(), "big".to_string()).unwrap().extend(&items.iter().map(|i| &i[..]).collect::<Vec<_>>()).unwrap();
+    let table = Hashtable::new(db.clone(), "big".to_string()).unwrap();
+    for i in &items {
+        table.insert(i, b"v").unwrap();
+    }
+    db.rename("big", "large", Some(CollectionKind::List)).unwrap();
+    db.rename("big", "large", Some(CollectionKind::Hashtable)).unwrap();
+    let list = ListDb::open(db.clone(), "large".to_string()).unwrap();
+    assert_eq!((list.length(), list.access(2499)), (Ok(2500), Ok(Some(2499u32.to_be_bytes().to_vec()))));
+    let table = Hashtable::open(db.clone(), "large".to_string()).unwrap();
+    assert_eq!((table.len(), table.get(&1234u32.to_be_bytes())), (Ok(2500), Ok(Some(b"v".to_vec()))));
+    assert_eq!(db.collections().map(|c| c.into_iter().map(|c| c.name).collect::<Vec<_>>()), Ok(vec!["large".to_string(); 2]));
+    assert!(matches!(db.rename("large", "large", Some(CollectionKind::List)), Err(DbError::NameConflict(_))));
+    db.drop("large", Some(CollectionKind::List)).unwrap();
+    db.drop("large", None).unwrap();
+    assert_eq!(db.scan_prefix([]).count(), 0);
+}
@@ -1,64 +1,527 @@
-fn ivec_to_string(v:sled::IVec) -> String{
-    String::from_utf8(v.to_vec()).expect("code error")
-}
-
 pub mod kv_operation {
-    use sled::{Db, IVec};
-
-    pub fn initialization(path:String) -> Db{
+    use std::collections::{BTreeMap, HashMap};
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+    use std::ops::{Bound, RangeBounds};
+    use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+    use sled::transaction::ConflictableTransactionError;
+    use sled::{Batch, Db, IVec};
+    use crate::sdk::error::{DbError, DbResult};
+
+    pub fn initialization(path:String) -> DbResult<Db>{
         // 创建一个储存引擎对象
-        sled::open(path).expect("Failed to open database")
+        open(path, &Config::default())
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Mode {
+        LowSpace, // 节省磁盘空间(sled 的默认值)
+        HighThroughput, // 写入更快,占用更多磁盘空间
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Config {
+        // 打开数据库的参数
+        pub cache_capacity : Option<u64>, // 页缓存的字节数,None 使用 sled 的默认值(1GB)
+        pub flush_every_ms : Option<u64>, // 后台定期写入硬盘的间隔,None 时只在调用 flush 时写入
+        pub mode : Mode,
+        pub temporary : bool, // 关闭后删除数据库文件
+        pub compression : bool, // zstd 压缩,需要开启 compression feature
+    }
+
+    impl Default for Config {
+        fn default() -> Self {
+            Config { cache_capacity: None, flush_every_ms: Some(500), mode: Mode::LowSpace, temporary: false, compression: false }
+        }
+    }
+
+    pub fn open(path:String, config:&Config) -> DbResult<Db> {
+        // 按照 config 打开数据库,不支持的参数(比如没有开启 compression feature 时压缩)返回 Storage 错误
+        let mut sled_config = sled::Config::new()
+            .path(path)
+            .flush_every_ms(config.flush_every_ms)
+            .mode(match config.mode {
+                Mode::LowSpace => sled::Mode::LowSpace,
+                Mode::HighThroughput => sled::Mode::HighThroughput,
+            })
+            .temporary(config.temporary)
+            .use_compression(config.compression);
+        if let Some(capacity) = config.cache_capacity {
+            sled_config = sled_config.cache_capacity(capacity);
+        }
+        Ok(sled_config.open()?)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn temporary() -> Db {
+        // 测试用的临时数据库,关闭后自动删除
+        sled::Config::new().temporary(true).open().expect("Failed to open temporary database")
+    }
+
+    #[derive(Debug)]
+    #[derive(Default)]
+    struct TxState {
+        // 事务的读写集合
+        // 写入先缓存在 writes 中,提交时在一个 sled 事务里检查 reads 是否被别人改过,然后一次性写入
+        reads : HashMap<Vec<u8>,Option<IVec>>, // 第一次读到的值
+        scans : Vec<(ScanRange,u64)>, // 扫描过的范围和扫描到的内容的摘要,提交时范围内有任何变化都算冲突
+        writes : BTreeMap<Vec<u8>,Option<IVec>>, // None 表示删除
+        finished : bool, // 已提交或已回滚
     }
 
     #[derive(Clone)]
     #[derive(Debug)]
     pub struct KvDbOpera {
         // 键值对操作对象
-        db : Db
+        // 所有 clone 共用一个 sled::Db,close 之后变为 None
+        db : Arc<RwLock<Option<Db>>>,
+        tree : Option<Arc<str>>, // 命名空间,对应一个单独的 sled Tree;None 为默认的 Tree
+        tx : Option<Arc<Mutex<TxState>>>, // 不为 None 时,所有读写都经过这个事务
+        commit_lock : Arc<RwLock<()>>, // 非事务的写入持有读锁,提交持有写锁;sled 事务中不能扫描,检查扫描范围时不能有其他写入
     }
 
+    type ScanRange = (Bound<Vec<u8>>,Bound<Vec<u8>>);
+
+    const DEFAULT_TREE : &[u8] = b"__sled__default"; // sled 默认 Tree 的名字
+
     pub type KvDbOperaObject = KvDbOpera; // 创建类型(方便读)
 
+    pub struct KvIter(KvIterInner); // 扫描结果,可以从两端迭代;数据库已关闭时只返回一个错误
+
+    enum KvIterInner {
+        Sled(Box<sled::Iter>),
+        Buffered(std::vec::IntoIter<(IVec,IVec)>), // 事务中的扫描,已经合并了事务自己的写入
+        Failed(Option<DbError>),
+    }
+
+    impl Iterator for KvIter {
+        type Item = DbResult<(IVec,IVec)>;
+        fn next(&mut self) -> Option<Self::Item> {
+            match &mut self.0 {
+                KvIterInner::Sled(iter) => iter.next().map(|r| r.map_err(DbError::from)),
+                KvIterInner::Buffered(iter) => iter.next().map(Ok),
+                KvIterInner::Failed(e) => e.take().map(Err),
+            }
+        }
+    }
+
+    impl DoubleEndedIterator for KvIter {
+        fn next_back(&mut self) -> Option<Self::Item> {
+            match &mut self.0 {
+                KvIterInner::Sled(iter) => iter.next_back().map(|r| r.map_err(DbError::from)),
+                KvIterInner::Buffered(iter) => iter.next_back().map(Ok),
+                KvIterInner::Failed(e) => e.take().map(Err),
+            }
+        }
+    }
+
+    impl From<DbResult<KvIter>> for KvIter {
+        fn from(r: DbResult<KvIter>) -> Self {
+            r.unwrap_or_else(|e| KvIter(KvIterInner::Failed(Some(e))))
+        }
+    }
+
+    fn scan_digest(tree:&sled::Tree, range:&ScanRange) -> DbResult<(u64,Vec<(IVec,IVec)>)> {
+        // 读取范围内的所有键值,返回内容的摘要
+        let mut hasher = DefaultHasher::new();
+        let mut items = Vec::new();
+        for item in tree.range::<Vec<u8>,_>(range.clone()) {
+            let (k, v) = item?;
+            hasher.write_usize(k.len());
+            hasher.write(&k);
+            hasher.write_usize(v.len());
+            hasher.write(&v);
+            items.push((k, v));
+        }
+        hasher.write_usize(items.len());
+        Ok((hasher.finish(), items))
+    }
+
     pub trait KvDbOperaTrait {
         type Output;
         fn new(db: Db) -> <Self as KvDbOperaTrait>::Output;
-        fn insert<T: AsRef<[u8]>,V:Into<IVec>>(&self, key: T, value: V) -> Result<bool, String>;
-        fn get<T: AsRef<[u8]>>(&self, key: T) -> Result<Option<IVec>, String>;
-        fn delete<T: AsRef<[u8]>>(&self, key: T) -> Result<bool, String>;
+        fn insert<T: AsRef<[u8]>,V:Into<IVec>>(&self, key: T, value: V) -> DbResult<()>;
+        fn get<T: AsRef<[u8]>>(&self, key: T) -> DbResult<Option<IVec>>;
+        fn delete<T: AsRef<[u8]>>(&self, key: T) -> DbResult<()>;
+    }
+
+    impl KvDbOpera {
+        fn sled(&self) -> DbResult<Db> {
+            // 取出 sled::Db(只是增加引用计数),数据库已关闭时返回 Closed
+            self.db.read().unwrap_or_else(|e| e.into_inner()).clone().ok_or(DbError::Closed)
+        }
+
+        fn tree(&self) -> DbResult<sled::Tree> {
+            // 当前命名空间的 Tree,被 drop_tree 删除后再次使用时会重新创建一个空的 Tree
+            let db = self.sled()?;
+            match &self.tree {
+                Some(name) => Ok(db.open_tree(name.as_bytes())?),
+                None => Ok((*db).clone()),
+            }
+        }
+
+        pub fn open_tree(&self, name:&str) -> DbResult<KvDbOpera> {
+            // 打开(不存在则创建)一个命名空间,其中的集合和默认命名空间的集合互不影响
+            // 返回的对象不在事务中,事务只能作用于一个命名空间
+            if name.as_bytes() == DEFAULT_TREE {
+                return Err(DbError::Storage(format!("reserved tree name: {name}")))
+            }
+            self.sled()?.open_tree(name)?;
+            Ok(KvDbOpera { db: self.db.clone(), tree: Some(name.into()), tx: None, commit_lock: self.commit_lock.clone() })
+        }
+
+        pub fn drop_tree(&self, name:&str) -> DbResult<bool> {
+            // 删除整个命名空间(O(1)),返回是否存在
+            if name.as_bytes() == DEFAULT_TREE {
+                return Err(DbError::Storage(format!("reserved tree name: {name}")))
+            }
+            Ok(self.sled()?.drop_tree(name)?)
+        }
+
+        pub fn list_trees(&self) -> DbResult<Vec<String>> {
+            // 所有命名空间的名字,不包括默认的 Tree
+            Ok(self.sled()?.tree_names().into_iter()
+                .filter(|name| name.as_ref() != DEFAULT_TREE)
+                .map(|name| String::from_utf8_lossy(&name).to_string())
+                .collect())
+        }
+
+        pub fn tree_name(&self) -> Option<&str> {
+            self.tree.as_deref()
+        }
+
+        pub fn flush(&self) -> DbResult<usize> {
+            // 阻塞直到所有写入都保存到硬盘,返回写入的字节数
+            Ok(self.sled()?.flush()?)
+        }
+
+        pub fn close(&self) -> DbResult<()> {
+            // 写入硬盘并关闭数据库,所有 clone 出来的对象(包括集合对象)之后的操作都返回 Closed
+            // 重复关闭不做任何事;正在进行的扫描结束后 sled 才会真正释放文件
+            let db = self.db.write().unwrap_or_else(|e| e.into_inner()).take();
+            match db {
+                Some(db) => { db.flush()?; Ok(()) },
+                None => Ok(()),
+            }
+        }
+
+        pub fn is_closed(&self) -> bool {
+            self.db.read().unwrap_or_else(|e| e.into_inner()).is_none()
+        }
+
+        fn tx_state(&self) -> DbResult<Option<MutexGuard<'_,TxState>>> {
+            // 取出当前事务的状态,已结束的事务不能再使用
+            match &self.tx {
+                None => Ok(None),
+                Some(tx) => {
+                    let state = tx.lock().unwrap_or_else(|e| e.into_inner());
+                    if state.finished {
+                        return Err(DbError::TransactionClosed)
+                    }
+                    Ok(Some(state))
+                }
+            }
+        }
+
+        pub fn in_transaction(&self) -> bool {
+            self.tx.is_some()
+        }
+
+        pub fn begin(&self) -> KvDbOpera {
+            // 开启一个事务,返回绑定了这个事务的数据库对象
+            // 已经在事务中时直接返回自己,也就是加入外层事务
+            if self.tx.is_some() {
+                return self.clone()
+            }
+            KvDbOpera { db: self.db.clone(), tree: self.tree.clone(), tx: Some(Arc::new(Mutex::new(TxState::default()))), commit_lock: self.commit_lock.clone() }
+        }
+
+        pub fn commit(&self) -> DbResult<()> {
+            // 提交事务,读过的键被其他人修改时返回 TransactionConflict,此时什么都不会写入
+            let mut state = match self.tx_state()? {
+                Some(state) => state,
+                None => return Ok(()), // 不在事务中,没有需要提交的内容
+            };
+            state.finished = true;
+            let mut batch = Batch::default();
+            for (k, v) in &state.writes {
+                match v {
+                    Some(v) => batch.insert(k.as_slice(), v.clone()),
+                    None => batch.remove(k.as_slice()),
+                }
+            }
+            let tree = self.tree()?;
+            // 检查扫描范围时不能有其他写入,持有写锁;只有写入时和非事务的写入一样持有读锁;只读的事务不需要锁
+            let _exclusive = (!state.scans.is_empty()).then(|| self.commit_lock.write().unwrap_or_else(|e| e.into_inner()));
+            let _shared = (state.scans.is_empty() && !state.writes.is_empty()).then(|| self.commit_lock.read().unwrap_or_else(|e| e.into_inner()));
+            for (range, digest) in &state.scans {
+                if scan_digest(&tree, range)?.0 != *digest {
+                    state.reads.clear();
+                    state.scans.clear();
+                    state.writes.clear();
+                    return Err(DbError::TransactionConflict)
+                }
+            }
+            let ret = tree.transaction(|t| {
+                for (k, v) in &state.reads {
+                    if t.get(k)? != *v {
+                        return Err(ConflictableTransactionError::Abort(DbError::TransactionConflict))
+                    }
+                }
+                t.apply_batch(&batch)?;
+                Ok(())
+            });
+            state.reads.clear();
+            state.scans.clear();
+            state.writes.clear();
+            Ok(ret?)
+        }
+
+        fn is_stale(&self) -> DbResult<bool> {
+            // 事务读到的值是否已经被其他人修改,修改过说明读到的内容可能前后不一致
+            match self.tx_state()? {
+                Some(state) => {
+                    let db = self.tree()?;
+                    for (k, v) in &state.reads {
+                        if db.get(k)? != *v {
+                            return Ok(true)
+                        }
+                    }
+                    for (range, digest) in &state.scans {
+                        if scan_digest(&db, range)?.0 != *digest {
+                            return Ok(true)
+                        }
+                    }
+                    Ok(false)
+                }
+                None => Ok(false),
+            }
+        }
+
+        pub fn rollback(&self) -> DbResult<()> {
+            // 丢弃事务中的所有写入
+            if let Some(mut state) = self.tx_state()? {
+                state.finished = true;
+                state.reads.clear();
+                state.scans.clear();
+                state.writes.clear();
+            }
+            Ok(())
+        }
+
+        pub fn scan_prefix<P: AsRef<[u8]>>(&self, prefix:P) -> KvIter {
+            // 前缀扫描,结果按键排序
+            let prefix = prefix.as_ref();
+            let end = crate::sdk::db::key_codec::prefix_end(prefix);
+            let end = if end.is_empty() { Bound::Unbounded } else { Bound::Excluded(end) };
+            self.range::<Vec<u8>,_>((Bound::Included(prefix.to_vec()), end))
+        }
+
+        pub fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range:R) -> KvIter {
+            // 范围扫描,结果按键排序
+            // 在事务中时一次读出整个范围,合并事务自己的写入,并记录下来用于提交时检查冲突
+            let owned = |b:Bound<&K>| match b {
+                Bound::Included(k) => Bound::Included(k.as_ref().to_vec()),
+                Bound::Excluded(k) => Bound::Excluded(k.as_ref().to_vec()),
+                Bound::Unbounded => Bound::Unbounded,
+            };
+            let range:ScanRange = (owned(range.start_bound()), owned(range.end_bound()));
+            self.scan_range(range).into()
+        }
+
+        fn scan_range(&self, range:ScanRange) -> DbResult<KvIter> {
+            let tree = self.tree()?;
+            let mut state = match self.tx_state()? {
+                Some(state) => state,
+                None => return Ok(KvIter(KvIterInner::Sled(Box::new(tree.range::<Vec<u8>,_>(range))))),
+            };
+            let (digest, items) = scan_digest(&tree, &range)?;
+            let mut merged:BTreeMap<Vec<u8>,(IVec,IVec)> = items.into_iter().map(|(k, v)| (k.to_vec(), (k, v))).collect();
+            for (k, v) in state.writes.range::<Vec<u8>,_>(range.clone()) {
+                match v {
+                    Some(v) => { merged.insert(k.clone(), (k.as_slice().into(), v.clone())); },
+                    None => { merged.remove(k); },
+                }
+            }
+            state.scans.push((range, digest));
+            Ok(KvIter(KvIterInner::Buffered(merged.into_values().collect::<Vec<_>>().into_iter())))
+        }
+
+        pub fn watch_prefix<P: AsRef<[u8]>>(&self, prefix:P) -> DbResult<sled::Subscriber> {
+            // 订阅以 prefix 开头的键的修改,只能收到已经提交的写入
+            Ok(self.tree()?.watch_prefix(prefix.as_ref()))
+        }
+
+        pub fn transaction<F,R>(&self, f:F) -> DbResult<R> where F:Fn(&KvDbOpera) -> DbResult<R> {
+            // 在事务中执行 f,要么全部写入,要么什么都不写
+            // 发生冲突时会重新执行 f;已经在事务中时直接加入外层事务
+            if self.tx.is_some() {
+                return f(self)
+            }
+            loop {
+                let tx = self.begin();
+                let ret = match f(&tx) {
+                    Ok(ret) => ret,
+                    Err(e) => {
+                        // 读到了其他事务写了一半的状态(比如拉链刚被回收)也会出错,这时重试而不是返回错误
+                        let stale = tx.is_stale()?;
+                        tx.rollback()?;
+                        if stale {
+                            continue
+                        }
+                        return Err(e)
+                    }
+                };
+                match tx.commit() {
+                    Ok(_) => return Ok(ret),
+                    Err(DbError::TransactionConflict) => continue, // 重试
+                    Err(e) => return Err(e),
+                }
+            }
+        }
     }
 
     impl KvDbOperaTrait for KvDbOpera {
         type Output = Self;
         fn new(db: Db) -> <KvDbOpera as KvDbOperaTrait>::Output {
-            KvDbOpera { db }
+            KvDbOpera { db: Arc::new(RwLock::new(Some(db))), tree: None, tx: None, commit_lock: Arc::new(RwLock::new(())) }
         }
-        fn insert<T: AsRef<[u8]>,V:Into<IVec>>(&self, key: T, value: V) -> Result<bool, String> {
-            match self.db.insert(key, value) {
-                Ok(_) => Ok(true),
-                Err(e) => Err(format!("{:?}", e)),
+        fn insert<T: AsRef<[u8]>,V:Into<IVec>>(&self, key: T, value: V) -> DbResult<()> {
+            match self.tx_state()? {
+                Some(mut state) => { state.writes.insert(key.as_ref().to_vec(), Some(value.into())); },
+                None => {
+                    let _guard = self.commit_lock.read().unwrap_or_else(|e| e.into_inner());
+                    self.tree()?.insert(key, value)?;
+                },
             }
+            Ok(())
         }
-        fn get<T: AsRef<[u8]>>(&self, key: T) -> Result<Option<IVec>, String> {
-            match self.db.get(key) {
-                Ok(value) => Ok(value),
-                Err(e) => Err(format!("{:?}", e)),
+        fn get<T: AsRef<[u8]>>(&self, key: T) -> DbResult<Option<IVec>> {
+            match self.tx_state()? {
+                Some(mut state) => {
+                    // 先读事务自己写入的值,再读数据库并记录下来用于提交时检查冲突
+                    if let Some(v) = state.writes.get(key.as_ref()) {
+                        return Ok(v.clone())
+                    }
+                    if let Some(v) = state.reads.get(key.as_ref()) {
+                        return Ok(v.clone())
+                    }
+                    let v = self.tree()?.get(key.as_ref())?;
+                    state.reads.insert(key.as_ref().to_vec(), v.clone());
+                    Ok(v)
+                },
+                None => Ok(self.tree()?.get(key)?),
             }
         }
-        fn delete<T: AsRef<[u8]>>(&self, key: T) -> Result<bool, String> {
-            match self.db.remove(key) {
-                Ok(_) => Ok(true),
-                Err(e) => Err(format!("{:?}", e))
+        fn delete<T: AsRef<[u8]>>(&self, key: T) -> DbResult<()> {
+            match self.tx_state()? {
+                Some(mut state) => { state.writes.insert(key.as_ref().to_vec(), None); },
+                None => {
+                    let _guard = self.commit_lock.read().unwrap_or_else(|e| e.into_inner());
+                    self.tree()?.remove(key)?;
+                },
             }
+            Ok(())
         }
     }
 
     #[test]
     fn test1(){
-        let a = KvDbOpera::new(initialization("/tmp/welcome-to-sled".to_string()));
-        dbg!(&a.insert(&[1,3,5,7,9], &[1,1,4,5,1,4]).expect("")) ;
-        dbg!(&a.get(&[1,3,5,7,9]));
-        dbg!(&a.delete(&[1, 3, 5, 7, 9]));
-        dbg!(&a.get(&"114514"));
+        let a = KvDbOpera::new(temporary());
+        dbg!(&a.insert([1,3,5,7,9], &[1,1,4,5,1,4]).expect("")) ;
+        dbg!(&a.get([1,3,5,7,9]));
+        dbg!(&a.delete([1, 3, 5, 7, 9]));
+        assert_eq!(a.get("114514"), Ok(None));
+    }
+
+    #[test]
+    fn transaction_test(){
+        let a = KvDbOpera::new(temporary());
+        // 失败的事务什么都不会写入
+        let ret: DbResult<()> = a.transaction(|tx| {
+            tx.insert("k1", "v1")?;
+            assert_eq!(tx.get("k1"), Ok(Some(IVec::from("v1")))); // 事务内可以读到自己的写入
+            Err(DbError::NotFound("k2".to_string()))
+        });
+        assert!(ret.is_err());
+        assert_eq!(a.get("k1"), Ok(None));
+        a.transaction(|tx| { tx.insert("k1", "v1")?; tx.insert("k2", "v2") }).unwrap();
+        assert_eq!(a.get("k2"), Ok(Some(IVec::from("v2"))));
+
+        // 读过的键在提交前被修改,提交失败
+        let tx = a.begin();
+        assert_eq!(tx.get("k1"), Ok(Some(IVec::from("v1"))));
+        tx.insert("k3", "v3").unwrap();
+        a.insert("k1", "changed").unwrap();
+        assert_eq!(tx.commit(), Err(DbError::TransactionConflict));
+        assert_eq!(a.get("k3"), Ok(None));
+        assert_eq!(tx.insert("k3", "v3"), Err(DbError::TransactionClosed));
+
+        // 事务中的扫描能看到自己的写入,扫描过的范围内新增了键,提交失败
+        let tx = a.begin();
+        tx.insert("k4", "v4").unwrap();
+        tx.delete("k2").unwrap();
+        let keys: Vec<IVec> = tx.scan_prefix("k").map(|r| r.unwrap().0).collect();
+        assert_eq!(keys, vec![IVec::from("k1"), IVec::from("k4")]);
+        assert_eq!(tx.range("k3".."k9").next_back().map(|r| r.unwrap().0), Some(IVec::from("k4")));
+        a.insert("k5", "v5").unwrap();
+        assert_eq!(tx.commit(), Err(DbError::TransactionConflict));
+        assert_eq!(a.get("k4"), Ok(None));
+        // 没有冲突时正常提交
+        let tx = a.begin();
+        assert_eq!(tx.scan_prefix("k").count(), 3);
+        tx.insert("k6", "v6").unwrap();
+        a.insert("j", "outside").unwrap();
+        tx.commit().unwrap();
+        assert_eq!(a.get("k6"), Ok(Some(IVec::from("v6"))));
+    }
+
+    #[test]
+    fn close_test(){
+        let path = std::env::temp_dir().join(format!("other-db-close-{}", uuid::Uuid::new_v4())).to_string_lossy().to_string();
+        let config = Config { cache_capacity: Some(1 << 20), flush_every_ms: None, mode: Mode::HighThroughput, ..Config::default() };
+        let a = KvDbOpera::new(open(path.clone(), &config).unwrap());
+        let b = a.clone();
+        a.insert("k", "v").unwrap();
+        assert!(a.flush().is_ok());
+        // 关闭后所有 clone 都不能再使用,重复关闭没有影响
+        a.close().unwrap();
+        assert!(b.is_closed());
+        assert_eq!(b.get("k"), Err(DbError::Closed));
+        assert_eq!(b.transaction(|tx| tx.insert("k", "v2")), Err(DbError::Closed));
+        assert_eq!(b.scan_prefix("k").collect::<Vec<_>>(), vec![Err(DbError::Closed)]);
+        assert_eq!(b.close(), Ok(()));
+        // 关闭时已经写入硬盘,重新打开可以读到
+        let c = KvDbOpera::new(initialization(path.clone()).unwrap());
+        assert_eq!(c.get("k"), Ok(Some(IVec::from("v"))));
+        c.close().unwrap();
+        std::fs::remove_dir_all(&path).unwrap();
+        #[cfg(not(feature = "compression"))]
+        assert!(matches!(open(path, &Config { compression: true, temporary: true, ..Config::default() }), Err(DbError::Storage(_))));
+    }
+
+    #[test]
+    fn tree_test(){
+        use crate::sdk::db::list_db::ListDb;
+        let a = KvDbOpera::new(temporary());
+        let ns = a.open_tree("ns").unwrap();
+        assert_eq!((a.tree_name(), ns.tree_name()), (None, Some("ns")));
+        // 不同命名空间中同名的键和集合互不影响
+        a.insert("k", "default").unwrap();
+        ns.insert("k", "ns").unwrap();
+        assert_eq!((a.get("k"), ns.get("k")), (Ok(Some(IVec::from("default"))), Ok(Some(IVec::from("ns")))));
+        ListDb::new(a.clone(), "x:1".to_string()).unwrap().append(b"1").unwrap();
+        let list = ListDb::new(ns.clone(), "x:1".to_string()).unwrap();
+        list.extend(&[b"2", b"3"]).unwrap();
+        assert_eq!(list.length(), Ok(2));
+        ns.transaction(|tx| tx.insert("k2", "v2")).unwrap();
+        assert_eq!((a.get("k2"), ns.scan_prefix("k").count()), (Ok(None), 2));
+        assert_eq!(a.list_trees(), Ok(vec!["ns".to_string()]));
+        // 删除命名空间
+        assert_eq!((a.drop_tree("ns"), a.drop_tree("ns")), (Ok(true), Ok(false)));
+        assert_eq!(a.list_trees(), Ok(vec![]));
+        assert!(a.drop_tree("__sled__default").is_err());
+        assert_eq!(ListDb::new(a, "x:1".to_string()).unwrap().length(), Ok(1));
     }
 }
 
@@ -106,439 +569,2555 @@ pub mod data_conversion {
 }
 
 
-pub mod list_db {
-    use sled::IVec;
-    use crate::sdk::db::kv_operation::{initialization, KvDbOpera, KvDbOperaObject};
-    use crate::sdk::db::kv_operation::KvDbOperaTrait;
-
-    #[derive(Debug)]
-    #[derive(Clone)]
-    pub struct ListDb {
-        // 数据库列表对象
-        pub(crate) db: KvDbOperaObject,
-        pub(crate) name: String,
+pub mod metadata {
+    // 集合的元数据记录
+    // 二进制格式(大端): [MAGIC][版本][种类][元组长度 u16][长度 u64][元素个数 u64][创建时间 u64][附加数据...]
+    // 旧版本的数据库把列表长度储存为十进制字符串,需要先用迁移工具(other-db migrate)改写为新格式,读到旧格式时报错
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use crate::sdk::error::{DbError, DbResult};
+
+    const MAGIC: u8 = 0xB7; // 十进制字符串的第一个字节一定是数字,用这个字节区分新旧格式
+    pub const VERSION: u8 = 1;
+    const HEADER_LEN: usize = 29;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum CollectionKind {
+        List = 1,
+        TupleList = 2,
+        Hashtable = 3,
+        HashtableChain = 4, // 哈希表内部的拉链(旧版本,以第一个键命名)
+        HashtableBucket = 5, // 哈希表内部的拉链(以桶号命名)
+        OrderedHashtable = 6, // hashtable_sled_db 的有序哈希表
+        SortedMap = 7,
+        Set = 8,
+        Deque = 9,
+        Archive = 10, // 档案,没有元数据记录,只出现在目录中
     }
 
-    impl ListDb {
-        // 以Vec为主的原始列表
-        pub(crate) fn get_key(&self, index: usize) -> String {
-            format!("List:{}:{}", &self.name, index)
+    impl CollectionKind {
+        pub(crate) fn from_u8(v: u8) -> DbResult<Self> {
+            match v {
+                1 => Ok(CollectionKind::List),
+                2 => Ok(CollectionKind::TupleList),
+                3 => Ok(CollectionKind::Hashtable),
+                4 => Ok(CollectionKind::HashtableChain),
+                5 => Ok(CollectionKind::HashtableBucket),
+                6 => Ok(CollectionKind::OrderedHashtable),
+                7 => Ok(CollectionKind::SortedMap),
+                8 => Ok(CollectionKind::Set),
+                9 => Ok(CollectionKind::Deque),
+                10 => Ok(CollectionKind::Archive),
+                _ => Err(DbError::CorruptMetadata(format!("unknown collection kind {v}"))),
+            }
         }
 
-        pub(crate) fn length(&self) -> Option<usize> {
-            match self.db.get(format!("List:{}", self.name)) {
-                Ok(v) => match v {
-                    None => None,
-                    Some(v1) => {
-                        Some(crate::sdk::string_to_usize(String::from_utf8(v1.to_vec()).unwrap()))
-                    }
-                },
-                _ => None,
+        pub fn name(&self) -> &'static str {
+            // python 中的类名,有序哈希表和 IndexVec 中一样叫 SledHashtable
+            match self {
+                CollectionKind::List => "List",
+                CollectionKind::TupleList => "TupleList",
+                CollectionKind::Hashtable => "Hashtable",
+                CollectionKind::HashtableChain => "HashtableChain",
+                CollectionKind::HashtableBucket => "HashtableBucket",
+                CollectionKind::OrderedHashtable => "SledHashtable",
+                CollectionKind::SortedMap => "SortedMap",
+                CollectionKind::Set => "Set",
+                CollectionKind::Deque => "Deque",
+                CollectionKind::Archive => "Archive",
             }
         }
-        pub(crate) fn change_length(&self, len:usize) -> Result<bool,String> {
-            // 更改列表长度
-            let alen = self.length().unwrap(); // 原始列表长度
-            match self.db.insert(format!("List:{}",&self.name),&*(len.to_string())) {
-                Ok(_) => Ok(true), // 更改长度成功
-                Err(e) => match self.db.insert(format!("List:{}",&self.name),&*(alen.to_string())) {
-                    Ok(_) => Ok(true), // 改回原来的
-                    Err(e1) => Err(format!("{},{}",e,e1)), // 抢救失败
-                },
-            }
+
+        pub fn from_name(name: &str) -> Option<Self> {
+            (1..=10).filter_map(|v| Self::from_u8(v).ok()).find(|kind| kind.name() == name)
         }
     }
 
-    impl ListDb {
-        pub(crate) fn new(db:KvDbOperaObject, name: String) -> Result<Self,String> {
-            // 创建一个新的对象
-            let key = format!("List:{name}");
-            match Self::open(db.clone(),name.clone()) {
-                Some(t) => Ok(t), // 列表已存在,则不创建,直接返回
-                None => {
-                    match db.insert(&key,&*(0.to_string())) {
-                        Ok(_) => Ok(ListDb {db,name}), // 返回正确的对象
-                        Err(e) => match db.delete(&key) { // 创建列表失败,开始收拾残局(也就是删掉创建到一半的列表)
-                            Ok(_) => Err(e),
-                            Err(e1) => Err(format!("{},{}",e,e1)) // 收拾残局失败
-                        }
-                    }
-                }
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Metadata {
+        pub kind: CollectionKind,
+        pub arity: u16, // 元组长度,不是元组列表时为 0
+        pub length: u64, // 列表长度(包括被删除的位置)
+        pub count: u64, // 实际储存的元素个数
+        pub created_at: u64, // 创建时间(unix 毫秒),从旧格式迁移的集合为 0
+        pub extra: Vec<u8>, // 各类集合自己的附加数据
+    }
+
+    pub enum Decoded {
+        Current(Metadata),
+        Legacy(usize), // 旧格式,只有列表长度
+    }
+
+    impl Decoded {
+        pub fn current(self) -> DbResult<Metadata> {
+            // 旧格式要先用迁移工具(other-db migrate)改写,读到旧格式时返回错误
+            match self {
+                Decoded::Current(meta) => Ok(meta),
+                Decoded::Legacy(_) => Err(DbError::CorruptMetadata("legacy metadata, run `other-db migrate` first".to_string())),
             }
         }
+    }
 
-        pub(crate) fn open(db:KvDbOperaObject, name: String) -> Option<Self> {
-            // 打开一个哈希表
-            let key = format!("List:{name}");
-            match db.get(&key).expect("Data acquisition failed") {
-                Some(_) => Some(ListDb {db,name}), // 列表已存在,直接返回
-                None => None
-            }
+    pub fn now_millis() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+    }
+
+    impl Metadata {
+        pub fn new(kind: CollectionKind, arity: u16) -> Self {
+            Metadata { kind, arity, length: 0, count: 0, created_at: now_millis(), extra: vec![] }
         }
 
-        pub(crate) fn append(&self, value: &Vec<u8>) -> Result<bool,String> {
-            // 追加
-            let index = match self.length() { // 获取原来列表长度
-                None => { return Err("Failed to obtain List length".to_string()) }
-                Some(l) => l
-            };
-            match self.change_length(index+1) { // 先给列表长度加一,这是为了通过覆写的列表长度检查
-                Ok(_) => self.overwrite(index, value), // 覆写新index对应的数据
-                Err(e) => Err(e),
-            }
+        pub fn encode(&self) -> Vec<u8> {
+            let mut ret = Vec::with_capacity(HEADER_LEN + self.extra.len());
+            ret.push(MAGIC);
+            ret.push(VERSION);
+            ret.push(self.kind as u8);
+            ret.extend_from_slice(&self.arity.to_be_bytes());
+            ret.extend_from_slice(&self.length.to_be_bytes());
+            ret.extend_from_slice(&self.count.to_be_bytes());
+            ret.extend_from_slice(&self.created_at.to_be_bytes());
+            ret.extend_from_slice(&self.extra);
+            ret
         }
-        pub(crate) fn access(&self, index: usize) -> Option<Vec<u8>> {
-            // 访问数据
-            match self.db.get(self.get_key(index)) {
-                Ok(v) => match v {
-                    Some(t) => Some(t.to_vec()), // 把IVec转化为常用的Vec
-                    _ => None
-                },
-                _ => None
+
+        pub fn decode(bytes: &[u8]) -> DbResult<Decoded> {
+            if bytes.first() != Some(&MAGIC) {
+                let s = std::str::from_utf8(bytes).map_err(|e| DbError::CorruptMetadata(format!("legacy length: {e}")))?;
+                return Ok(Decoded::Legacy(crate::sdk::string_to_usize(s)?))
+            }
+            if bytes.len() < HEADER_LEN {
+                return Err(DbError::CorruptMetadata(format!("metadata record too short: {} bytes", bytes.len())))
             }
+            if bytes[1] != VERSION {
+                return Err(DbError::CorruptMetadata(format!("unsupported metadata version {}", bytes[1])))
+            }
+            let u64_at = |i: usize| {
+                let mut b = [0u8; 8];
+                b.copy_from_slice(&bytes[i..i + 8]);
+                u64::from_be_bytes(b)
+            };
+            Ok(Decoded::Current(Metadata {
+                kind: CollectionKind::from_u8(bytes[2])?,
+                arity: u16::from_be_bytes([bytes[3], bytes[4]]),
+                length: u64_at(5),
+                count: u64_at(13),
+                created_at: u64_at(21),
+                extra: bytes[HEADER_LEN..].to_vec(),
+            }))
         }
+    }
 
-        pub(crate) fn overwrite(&self, index: usize, value: &Vec<u8>) -> Result<bool,String> {
-            // 覆写数据
-            if self.length().unwrap() >= index + 1 { // 检查index是否超过列表长度
-                match self.db.insert(self.get_key(index), IVec::from(value.clone())) { // 调用数据库执行写入
-                    Ok(_) => Ok(true),
-                    Err(e) => match self.delete(index) { // 覆写失败,收拾残局
-                        Ok(_) => Err(e),
-                        Err(e1) => Err(format!("{e},{e1}")) // 收拾残局失败,没救了
-                    },
-                }
-            } else {
-                Err("Index too large".to_string()) // index超过了列表长度,不符合列表元素覆写规则
-            }
+    #[test]
+    fn metadata_test() {
+        let mut m = Metadata::new(CollectionKind::TupleList, 2);
+        m.length = 114514;
+        m.count = 1919;
+        m.extra = vec![8, 1, 0];
+        match Metadata::decode(&m.encode()).unwrap() {
+            Decoded::Current(m1) => assert_eq!(m1, m),
+            Decoded::Legacy(_) => panic!("should be current format"),
+        }
+        assert!(matches!(Metadata::decode(b"42"), Ok(Decoded::Legacy(42))));
+        assert!(matches!(Metadata::decode(b"4x2"), Err(DbError::CorruptMetadata(_))));
+        assert!(matches!(Metadata::decode(&m.encode()[..10]), Err(DbError::CorruptMetadata(_))));
+    }
+}
+
+pub mod key_codec {
+    // 键编码
+    // 所有集合的键都是 [类型标记][转义后的名字][0x00 0x01] 开头,后面跟着:
+    //   元数据 : 0x00
+    //   元素   : 0x01 + 大端 u64 index (列表类) 或者 0x01 + 原始键 (键值类)
+    // 名字中的 0x00 转义为 0x00 0xFF,所以前缀之间互不包含,同一个集合的元素在 sled 中是连续并且按 index 排好序的
+    use crate::sdk::db::metadata::CollectionKind;
+
+    pub const TAG_ARCHIVE: u8 = 0x10; // 档案头
+    pub const TAG_KV: u8 = 0x11; // hashtable_sled_db 的键值对
+    pub const TAG_CATALOG: u8 = 0x12; // 集合目录,见 sdk::catalog
+    const ESCAPE: u8 = 0xFF;
+    const TERMINATOR: u8 = 0x01;
+    const META: u8 = 0x00;
+    const ELEMENT: u8 = 0x01;
+
+    pub fn kind_tag(kind: CollectionKind) -> u8 {
+        // 集合的类型标记就是元数据中的种类
+        kind as u8
+    }
 
+    pub fn escape_into(out: &mut Vec<u8>, bytes: &[u8]) {
+        // 转义并在末尾加上结束符
+        for b in bytes {
+            out.push(*b);
+            if *b == 0x00 {
+                out.push(ESCAPE);
+            }
         }
-        pub(crate) fn safety_overwrite(&self, index: usize, value: &Vec<u8>) -> Result<bool,String> {
-            // 带有边界检查的覆写
-            if self.length().unwrap() != 0{
-                if self.length().unwrap()-1 < index {
-                    self.change_length(index+1).unwrap();
-                    self.overwrite(index, value)
-                } else {
-                    self.overwrite(index, value)
+        out.push(0x00);
+        out.push(TERMINATOR);
+    }
+
+    pub fn unescape(bytes: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+        // 解析 escape_into 写入的一段,返回内容和剩下的字节
+        let mut ret = vec![];
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == 0x00 {
+                match bytes.get(i + 1) {
+                    Some(&ESCAPE) => ret.push(0x00),
+                    Some(&TERMINATOR) => return Some((ret, &bytes[i + 2..])),
+                    _ => return None,
                 }
+                i += 2;
             } else {
-                self.change_length(index+1).unwrap();
-                self.overwrite(index, value)
+                ret.push(bytes[i]);
+                i += 1;
             }
-
         }
-        pub(crate) fn delete(&self, index: usize) -> Result<bool,String> {
-            if index != 0 {
-                if self.length().unwrap()-1 == index {
-                    match self.change_length(index-1) {
-                        Ok(_) => {},
-                        Err(e) =>  {dbg!(e);todo!();} // 没救了
-                    }
-                };
-            }
+        None
+    }
+
+    pub fn prefix(tag: u8, name: &str) -> Vec<u8> {
+        // 集合的键前缀
+        let mut ret = vec![tag];
+        escape_into(&mut ret, name.as_bytes());
+        ret
+    }
+
+    pub fn meta_key(prefix: &[u8]) -> Vec<u8> {
+        let mut ret = prefix.to_vec();
+        ret.push(META);
+        ret
+    }
+
+    pub fn elements_prefix(prefix: &[u8]) -> Vec<u8> {
+        // 集合所有元素共同的前缀
+        let mut ret = prefix.to_vec();
+        ret.push(ELEMENT);
+        ret
+    }
+
+    pub fn element_key(prefix: &[u8], index: u64) -> Vec<u8> {
+        let mut ret = elements_prefix(prefix);
+        ret.extend_from_slice(&index.to_be_bytes());
+        ret
+    }
+
+    pub fn element_index(prefix: &[u8], key: &[u8]) -> Option<u64> {
+        // element_key 的逆运算
+        let rest = key.strip_prefix(elements_prefix(prefix).as_slice())?;
+        Some(u64::from_be_bytes(rest.try_into().ok()?))
+    }
+
+    pub fn entry_key(prefix: &[u8], key: &[u8]) -> Vec<u8> {
+        // 键值类集合的键,原始键放在最后,不需要转义也能保持顺序
+        let mut ret = elements_prefix(prefix);
+        ret.extend_from_slice(key);
+        ret
+    }
 
-            self.db.delete(self.get_key(index))
+    pub fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+        // 大于所有以 prefix 开头的键的最小的键(prefix 全是 0xFF 时没有上界,返回空)
+        let mut ret = prefix.to_vec();
+        while let Some(last) = ret.pop() {
+            if last != 0xFF {
+                ret.push(last + 1);
+                break
+            }
         }
+        ret
+    }
+
+    pub fn archive_key(name: &str) -> Vec<u8> {
+        prefix(TAG_ARCHIVE, name)
     }
 
-    // impl Iterator for ListDB<String> {
-    //     type Item = Option<String>;
-    //
-    //     fn next(&mut self) -> Option<Self::Item> {
-    //         if (self.length().unwrap() <= self.iteration){
-    //             None
-    //         } else {
-    //             Some(self.access(self.iteration))
-    //         }
-    //     }
-    // }
     #[test]
-    fn list_test_string(){
-        let a = ListDb::open(
-            KvDbOpera::new(initialization("/tmp/welcome-to-sled".to_string())),
-            "113314".to_string()
-        ).expect("");
-        // dbg!(&a.append(&"很可爱?".to_string()));
-        // dbg!(&a.append(&"Yes,cute?".to_string()));
-
-        // for _ in 0..1000{
-        //     &a.append(&"kawaii?".to_string());
-        // }
-        // dbg!(&a.access(0));
-        // dbg!(&a.delete(0));
-        // dbg!(&a.access(0));
-        // dbg!("开始遍历");
-        // for i in a {
-        //     dbg!(i);
-        // }
-        for i in 0..a.length().unwrap() {
-            println!("{:?}",a.delete(i))
-        }
-        a.change_length(0+1).unwrap();
-        a.overwrite(0,&vec![1]).unwrap();
-        dbg!(a.access(0)) ;
+    fn key_codec_test() {
+        let p = prefix(1, "a");
+        // 数字顺序和字节顺序一致
+        assert!(element_key(&p, 2) < element_key(&p, 10));
+        assert!(meta_key(&p) < element_key(&p, 0));
+        assert_eq!(element_index(&p, &element_key(&p, 114514)), Some(114514));
+        // 名字互为前缀的集合不会混在一起
+        let p1 = prefix(1, "a\0b");
+        let p2 = prefix(1, "ab");
+        assert!(!element_key(&p1, 0).starts_with(&p) && !element_key(&p2, 0).starts_with(&p));
+        assert_eq!(unescape(&p1[1..]), Some(("a\0b".as_bytes().to_vec(), &[][..])));
     }
 }
 
-pub mod tuple_list_db {
-    use crate::sdk::db::list_db as list;
-    use crate::sdk::db::kv_operation::{initialization, KvDbOperaObject};
-
-    #[derive(Debug)]
-    #[derive(Clone)]
-    pub struct TupleList {
-        list : list::ListDb,
-        name : String,
-        len : u16,
+pub mod typed_key {
+    // 哈希表类型化键(python 的 bytes | str | int | tuple)的规范编码
+    // 顶层的 str 编码为它的 UTF-8 字节(和旧版本的字符串键一致),其他类型编码为 0xFF + 带类型标记的编码,
+    // 0xFF 不会出现在 UTF-8 中,所以两者不会混淆
+    // 带类型标记的编码(元组的元素也用这种编码,可以自我分隔):
+    //   bytes : 0x01 + 转义后的字节(和 key_codec::escape_into 相同)
+    //   str   : 0x02 + 转义后的 UTF-8 字节
+    //   int   : 0x03 + 符号位取反的大端 i64 (字节顺序和数字顺序一致)
+    //   tuple : 0x04 + 各元素的编码 + 0x00
+    use crate::sdk::db::key_codec;
+    use crate::sdk::error::{DbError, DbResult};
+
+    const TYPED: u8 = 0xFF;
+    const BYTES: u8 = 0x01;
+    const STR: u8 = 0x02;
+    const INT: u8 = 0x03;
+    const TUPLE: u8 = 0x04;
+    const TUPLE_END: u8 = 0x00;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum TypedKey {
+        Bytes(Vec<u8>),
+        Str(String),
+        Int(i64),
+        Tuple(Vec<TypedKey>),
     }
 
-    impl  TupleList {
-        // 元组列表的思想是利用列表来储存固定长度的元组
-        // 也就是类似于: 元组列表([(1,2),(3,4)]),实际列表([1,2,3,4])
-        // 因此元组列表的实际列表的长度有 2*元组列表长度 的关系
-        pub(crate) fn new(db: KvDbOperaObject, name: String, len: u16) -> Result<Self, String> {
-            // 创建一个元组列表对象
-            match list::ListDb::new(db,format!("Tuple:{name}")) { // 构建列表对象
-                Ok(list) => Ok(TupleList { list,name, len}),
-                Err(e) => Err(format!("Failed to create List : {e}")),
+    impl TypedKey {
+        pub fn encode(&self) -> Vec<u8> {
+            match self {
+                TypedKey::Str(s) => s.as_bytes().to_vec(),
+                _ => {
+                    let mut out = vec![TYPED];
+                    self.encode_tagged(&mut out);
+                    out
+                }
             }
         }
 
-        pub(crate) fn open(db: KvDbOperaObject, name: String, len: u16) -> Option<Self> {
-            // 打开一个元组列表,并且创建对象
-            match list::ListDb::open(db,format!("Tuple:{name}")) {
-                Some(list) => Some(TupleList { list,name, len}),
-                _ => None,
+        pub fn encode_prefix(&self) -> Vec<u8> {
+            // 前缀查询用的编码: 以这个键开头的键的编码都以它开头
+            // str 和 bytes 匹配字符串前缀,tuple 匹配前几个元素相同的元组,int 只匹配它自己
+            let mut out = self.encode();
+            match self {
+                TypedKey::Bytes(_) => out.truncate(out.len() - 2), // 去掉结束符 0x00 0x01
+                TypedKey::Tuple(_) => { out.pop(); }, // 去掉 TUPLE_END
+                _ => {}
             }
+            out
         }
 
-        pub(crate) fn append(&self, value: &Vec<&Vec<u8>>) -> Result<bool, String> {
-            // 追加元组元素
-            self.list.change_length(self.list.length().unwrap() + value.len()).unwrap();
-            self.overwrite(self.length().unwrap()-1,value)
+        fn encode_tagged(&self, out: &mut Vec<u8>) {
+            match self {
+                TypedKey::Bytes(b) => {
+                    out.push(BYTES);
+                    key_codec::escape_into(out, b);
+                }
+                TypedKey::Str(s) => {
+                    out.push(STR);
+                    key_codec::escape_into(out, s.as_bytes());
+                }
+                TypedKey::Int(i) => {
+                    out.push(INT);
+                    out.extend_from_slice(&((*i as u64) ^ (1 << 63)).to_be_bytes());
+                }
+                TypedKey::Tuple(items) => {
+                    out.push(TUPLE);
+                    for item in items {
+                        item.encode_tagged(out);
+                    }
+                    out.push(TUPLE_END);
+                }
+            }
         }
 
-        pub(crate) fn access(&self, index: usize) -> Option<Vec<Option<Vec<u8>>>> {
-            // 访问,如果返回结果为 [] ,则就等于没有
-            let mut ret:Vec<Option<Vec<u8>>> = vec![];
-            for i in index*(self.len as usize)..(index*(self.len as usize))+self.len as usize{
-                ret.push(self.list.access(i));
-            }
-            return if ret == vec![] {
-                None
-            } else {
-                Option::from(ret)
+        pub fn decode(bytes: &[u8]) -> DbResult<Self> {
+            // encode 的逆运算;不是 0xFF 开头并且不是 UTF-8 的键(从 rust 直接写入的字节键)解析为 Bytes
+            match bytes.split_first() {
+                Some((&TYPED, rest)) => match Self::decode_tagged(rest)? {
+                    (key, []) => Ok(key),
+                    _ => Err(Self::corrupt(bytes)),
+                },
+                _ => Ok(match std::str::from_utf8(bytes) {
+                    Ok(s) => TypedKey::Str(s.to_string()),
+                    Err(_) => TypedKey::Bytes(bytes.to_vec()),
+                }),
             }
         }
 
-        pub(crate) fn overwrite(&self, index: usize, value: &Vec<&Vec<u8>>) -> Result<bool, String> {
-            // 覆写元组
-            if value.len() != (self.len as usize)  { return Err("Value length error".to_string()) } // 输入长度错误
-            for i in  0..value.len(){
-                match self.list.overwrite(index*(self.len as usize)+i, value[i]) { // 覆写原始数据
-                    Ok(_) => {  },
-                    Err(e) => {
-                        for j in (index*(self.len as usize))..(index*(self.len as usize))+i{ // 收拾残局(注意当前覆写的残局已被 self.List.overwrite 收拾干净了,所以不需要再收拾一遍了)
-                            let _ = &self.list.delete(j);
-                        }return Err(e); // 输出错误
-                    }
+        fn decode_tagged(bytes: &[u8]) -> DbResult<(Self, &[u8])> {
+            let corrupt = || Self::corrupt(bytes);
+            let (tag, rest) = bytes.split_first().ok_or_else(corrupt)?;
+            match *tag {
+                BYTES => {
+                    let (b, rest) = key_codec::unescape(rest).ok_or_else(corrupt)?;
+                    Ok((TypedKey::Bytes(b), rest))
                 }
-            }
-            Ok(true)
-        }
-        pub(crate) fn safety_overwrite(&self, index: usize, value: &Vec<&Vec<u8>>) -> Result<bool, String> {
-            if value.len() != (self.len as usize)  { return Err("Value length error".to_string()) } // 输入长度错误
-            for i in  0..value.len(){
-                match self.list.safety_overwrite(index*(self.len as usize)+i, value[i]) { // 覆写原始数据
-                    Ok(_) => {  },
-                    Err(e) => {
-                        for j in (index*(self.len as usize))..(index*(self.len as usize))+i{ // 收拾残局(注意当前覆写的残局已被 self.List.overwrite 收拾干净了,所以不需要再收拾一遍了)
-                            let _ = &self.list.delete(j);
-                        }return Err(e); // 输出错误
+                STR => {
+                    let (b, rest) = key_codec::unescape(rest).ok_or_else(corrupt)?;
+                    Ok((TypedKey::Str(String::from_utf8(b).map_err(|_| corrupt())?), rest))
+                }
+                INT => {
+                    let raw = rest.get(..8).ok_or_else(corrupt)?;
+                    let i = u64::from_be_bytes(raw.try_into().map_err(|_| corrupt())?) ^ (1 << 63);
+                    Ok((TypedKey::Int(i as i64), &rest[8..]))
+                }
+                TUPLE => {
+                    let mut items = vec![];
+                    let mut rest = rest;
+                    loop {
+                        match rest.split_first() {
+                            Some((&TUPLE_END, r)) => return Ok((TypedKey::Tuple(items), r)),
+                            Some(_) => {
+                                let (item, r) = Self::decode_tagged(rest)?;
+                                items.push(item);
+                                rest = r;
+                            }
+                            None => return Err(corrupt()),
+                        }
                     }
                 }
+                _ => Err(corrupt()),
             }
-            Ok(true)
         }
-        pub(crate) fn safety_overwrite_tuple_elements(&self, index: usize,tuple_index:u16,value : &Vec<u8>) -> Result<bool, String> {
-            // 列表index和列表index对应的元组index
-            // 覆写列表中的元组中的某个元素的值
-            if tuple_index > self.len { return Err("Value length error".to_string()) } // 输入长度错误
-            match self.list.safety_overwrite(self.get_list_index(index,tuple_index), value) { // 覆写原始数据
-                Ok(_) => { Ok(true) },
-                Err(e) => {
-                        let _ = self.delete_tuple_elements(index,tuple_index).unwrap(); // 收拾残局,失败即报错
-                        return Err(e); // 输出错误
-                }
-            }
+
+        fn corrupt(bytes: &[u8]) -> DbError {
+            DbError::CorruptMetadata(format!("typed key {bytes:?}"))
         }
+    }
 
-        pub(crate) fn overwrite_tuple_elements(&self, index: usize,tuple_index:u16,value : &Vec<u8>) -> Result<bool, String> {
+    #[test]
+    fn typed_key_test() {
+        let keys = [
+            TypedKey::Str("用户".to_string()),
+            TypedKey::Bytes(vec![0, 0xFF, 1]),
+            TypedKey::Int(-5),
+            TypedKey::Tuple(vec![TypedKey::Int(1), TypedKey::Str("a\0b".to_string()), TypedKey::Tuple(vec![])]),
+        ];
+        for key in &keys {
+            assert_eq!(TypedKey::decode(&key.encode()).as_ref(), Ok(key));
+        }
+        assert_eq!(keys[0].encode(), "用户".as_bytes()); // 和旧版本的字符串键一致
+        assert_ne!(TypedKey::Bytes(b"a".to_vec()).encode(), TypedKey::Str("a".to_string()).encode());
+        assert!(TypedKey::Int(-1).encode() < TypedKey::Int(0).encode() && TypedKey::Int(0).encode() < TypedKey::Int(i64::MAX).encode());
+        assert_eq!(TypedKey::decode(&[0x80]), Ok(TypedKey::Bytes(vec![0x80])));
+        assert!(TypedKey::decode(&[TYPED, TUPLE]).is_err());
+
+        let bytes_prefix = TypedKey::Bytes(b"ab".to_vec()).encode_prefix();
+        assert!(TypedKey::Bytes(b"ab\0c".to_vec()).encode().starts_with(&bytes_prefix));
+        assert!(!TypedKey::Bytes(b"a".to_vec()).encode().starts_with(&bytes_prefix));
+        let tuple_prefix = TypedKey::Tuple(vec![TypedKey::Int(1)]).encode_prefix();
+        assert!(TypedKey::Tuple(vec![TypedKey::Int(1), TypedKey::Str("x".to_string())]).encode().starts_with(&tuple_prefix));
+        assert!(!TypedKey::Tuple(vec![TypedKey::Int(2)]).encode().starts_with(&tuple_prefix));
+    }
+}
+
+pub mod list_db {
+    use sled::IVec;
+    use crate::sdk::catalog;
+    use crate::sdk::db::key_codec;
+    use crate::sdk::db::kv_operation::{KvDbOperaObject, KvIter};
+    use crate::sdk::db::metadata::{CollectionKind, Metadata};
+    use crate::sdk::db::kv_operation::KvDbOperaTrait;
+    use crate::sdk::error::{DbError, DbResult};
+
+    #[derive(Debug)]
+    #[derive(Clone)]
+    pub struct ListDb {
+        // 数据库列表对象
+        pub(crate) db: KvDbOperaObject,
+        pub(crate) name: String,
+        pub(crate) kind: CollectionKind, // 同时也是键的类型标记
+    }
+
+    impl ListDb {
+        // 以Vec为主的原始列表
+        pub(crate) fn prefix(&self) -> Vec<u8> {
+            key_codec::prefix(key_codec::kind_tag(self.kind), &self.name)
+        }
+
+        pub(crate) fn get_key(&self, index: usize) -> Vec<u8> {
+            key_codec::element_key(&self.prefix(), index as u64)
+        }
+
+        pub(crate) fn meta_key(&self) -> Vec<u8> {
+            key_codec::meta_key(&self.prefix())
+        }
+
+        pub fn metadata(&self) -> DbResult<Metadata> {
+            // 读取元数据,旧格式需要先用迁移工具改写
+            let v = self.db.get(self.meta_key())?.ok_or_else(|| DbError::NotFound(format!("List {}", self.name)))?;
+            Metadata::decode(&v)?.current()
+        }
+
+        pub(crate) fn write_metadata(&self, meta:&Metadata) -> DbResult<()> {
+            self.db.insert(self.meta_key(), meta.encode())
+        }
+
+        pub fn length(&self) -> DbResult<usize> {
+            Ok(self.metadata()?.length as usize)
+        }
+
+        pub fn count(&self) -> DbResult<usize> {
+            // 实际储存的元素个数(不包括被删除的位置)
+            Ok(self.metadata()?.count as usize)
+        }
+
+        pub(crate) fn change_length(&self, len:usize) -> DbResult<()> {
+            // 更改列表长度
+            let mut meta = self.metadata()?;
+            meta.length = len as u64;
+            self.write_metadata(&meta)
+        }
+
+        pub fn bind(&self, db:&KvDbOperaObject) -> Self {
+            // 换一个数据库对象(比如事务)来操作这个列表
+            ListDb { db: db.clone(), name: self.name.clone(), kind: self.kind }
+        }
+
+        pub(crate) fn remove_element(&self, index: usize) -> DbResult<()> {
+            // 删除一个元素,不改变列表长度
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                let mut meta = list.metadata()?;
+                let length = meta.length as usize;
+                if index >= length {
+                    return Err(DbError::OutOfBounds { index, length })
+                }
+                if tx.get(list.get_key(index))?.is_some() {
+                    meta.count = meta.count.saturating_sub(1);
+                    list.write_metadata(&meta)?;
+                    tx.delete(list.get_key(index))?;
+                }
+                Ok(())
+            })
+        }
+
+        pub fn truncate(&self, len: usize) -> DbResult<()> {
+            // 删除 index >= len 的所有元素(包括储存的键),列表长度变为 len(只会变短)
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                let mut meta = list.metadata()?;
+                for i in len..meta.length as usize {
+                    if tx.get(list.get_key(i))?.is_some() {
+                        meta.count = meta.count.saturating_sub(1);
+                        tx.delete(list.get_key(i))?;
+                    }
+                }
+                meta.length = meta.length.min(len as u64);
+                list.write_metadata(&meta)
+            })
+        }
+
+        pub(crate) fn destroy(&self) -> DbResult<()> {
+            // 删除整个列表(元数据和所有元素)
+            // 逐个删除 [0, length) 使事务中尚未提交的元素也被删除,再扫描删除超出长度的遗留元素
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                for i in 0..list.length()? {
+                    tx.delete(list.get_key(i))?;
+                }
+                for kv in tx.scan_prefix(key_codec::elements_prefix(&list.prefix())) {
+                    tx.delete(kv?.0)?;
+                }
+                tx.delete(list.meta_key())
+            })
+        }
+    }
+
+    impl ListDb {
+        pub fn new(db:KvDbOperaObject, name: String) -> DbResult<Self> {
+            // 创建一个新的对象
+            Self::new_with_kind(db, name, CollectionKind::List, 0)
+        }
+
+        pub(crate) fn new_with_kind(db:KvDbOperaObject, name: String, kind: CollectionKind, arity: u16) -> DbResult<Self> {
+            // 创建一个新的对象,并在元数据中记录它属于哪种集合
+            db.transaction(|tx| {
+                match Self::open_with_kind(tx.clone(),name.clone(),kind) {
+                    Ok(_) => Ok(()), // 列表已存在,则不创建,直接返回
+                    Err(DbError::NotFound(_)) => {
+                        ListDb {db: tx.clone(), name: name.clone(), kind}.write_metadata(&Metadata::new(kind, arity))?;
+                        catalog::register(tx, kind, &name) // 登记到集合目录(哈希表的拉链不登记)
+                    }
+                    Err(e) => Err(e),
+                }
+            })?;
+            Ok(ListDb {db,name,kind})
+        }
+
+        pub fn open(db:KvDbOperaObject, name: String) -> DbResult<Self> {
+            // 打开一个列表,不存在时返回 NotFound
+            Self::open_with_kind(db, name, CollectionKind::List)
+        }
+
+        pub(crate) fn open_with_kind(db:KvDbOperaObject, name: String, kind: CollectionKind) -> DbResult<Self> {
+            let list = ListDb {db,name,kind};
+            match list.db.get(list.meta_key())? {
+                Some(_) => Ok(list), // 列表已存在,直接返回
+                None => Err(DbError::NotFound(format!("List {}", list.name)))
+            }
+        }
+
+        pub fn append(&self, value: &[u8]) -> DbResult<()> {
+            // 追加,长度和元素在同一个事务中写入
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                let index = list.length()?; // 获取原来列表长度
+                list.change_length(index+1)?; // 先给列表长度加一,这是为了通过覆写的列表长度检查
+                list.overwrite(index, value) // 覆写新index对应的数据
+            })
+        }
+        pub fn access(&self, index: usize) -> DbResult<Option<Vec<u8>>> {
+            // 访问数据,越界返回 OutOfBounds,被删除的位置返回 None
+            let length = self.length()?;
+            if index >= length {
+                return Err(DbError::OutOfBounds { index, length })
+            }
+            Ok(self.db.get(self.get_key(index))?.map(|t| t.to_vec())) // 把IVec转化为常用的Vec
+        }
+
+        pub fn overwrite(&self, index: usize, value: &[u8]) -> DbResult<()> {
+            // 覆写数据
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                let mut meta = list.metadata()?;
+                let length = meta.length as usize;
+                if index >= length { // 检查index是否超过列表长度
+                    return Err(DbError::OutOfBounds { index, length }) // index超过了列表长度,不符合列表元素覆写规则
+                }
+                if tx.get(self.get_key(index))?.is_none() { // 写入原本为空的位置,元素个数加一
+                    meta.count += 1;
+                    list.write_metadata(&meta)?;
+                }
+                tx.insert(self.get_key(index), IVec::from(value)) // 调用数据库执行写入
+            })
+        }
+        pub fn safety_overwrite(&self, index: usize, value: &[u8]) -> DbResult<()> {
+            // 带有边界检查的覆写,index超过列表长度时会先扩充列表
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                if index >= list.length()? {
+                    list.change_length(index+1)?;
+                }
+                list.overwrite(index, value)
+            })
+        }
+        pub fn delete(&self, index: usize) -> DbResult<()> {
+            // 删除一个元素,其他元素的 index 不变(中间留下空位)
+            // 删除最后一个元素时列表长度缩短到剩下的最后一个元素之后,所以
+            // append/overwrite/delete 之后总是满足: length == 0 或者 index length-1 处有元素
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                let mut meta = list.metadata()?;
+                let length = meta.length as usize;
+                if index >= length {
+                    return Err(DbError::OutOfBounds { index, length })
+                }
+                if tx.get(list.get_key(index))?.is_some() { // 删除已有的元素,元素个数减一
+                    meta.count = meta.count.saturating_sub(1);
+                    tx.delete(list.get_key(index))?;
+                }
+                if index + 1 == length {
+                    let mut last = index;
+                    while last > 0 && tx.get(list.get_key(last - 1))?.is_none() {
+                        last -= 1; // 跳过末尾的空位
+                    }
+                    meta.length = last as u64;
+                }
+                list.write_metadata(&meta)
+            })
+        }
+
+        fn move_element(&self, from: usize, to: usize) -> DbResult<()> {
+            // 把 from 位置的元素(或者空位)移动到 to
+            match self.db.get(self.get_key(from))? {
+                Some(v) => self.db.insert(self.get_key(to), v)?,
+                None => self.db.delete(self.get_key(to))?,
+            }
+            self.db.delete(self.get_key(from))
+        }
+
+        pub fn insert(&self, index: usize, value: &[u8]) -> DbResult<()> {
+            // 在 index 处插入,后面的元素(包括空位)依次后移,index 等于长度时相当于 append
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                let mut meta = list.metadata()?;
+                let length = meta.length as usize;
+                if index > length {
+                    return Err(DbError::OutOfBounds { index, length })
+                }
+                for i in (index..length).rev() {
+                    list.move_element(i, i + 1)?;
+                }
+                tx.insert(list.get_key(index), value)?;
+                meta.length += 1;
+                meta.count += 1;
+                list.write_metadata(&meta)
+            })
+        }
+
+        pub fn pop(&self, index: usize) -> DbResult<Option<Vec<u8>>> {
+            // 取出 index 处的元素,后面的元素依次前移,列表长度减一;取出的是空位时返回 None
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                let mut meta = list.metadata()?;
+                let length = meta.length as usize;
+                if index >= length {
+                    return Err(DbError::OutOfBounds { index, length })
+                }
+                let value = tx.get(list.get_key(index))?;
+                if value.is_some() {
+                    meta.count = meta.count.saturating_sub(1);
+                }
+                tx.delete(list.get_key(index))?;
+                for i in index + 1..length {
+                    list.move_element(i, i - 1)?;
+                }
+                meta.length -= 1;
+                list.write_metadata(&meta)?;
+                Ok(value.map(|v| v.to_vec()))
+            })
+        }
+
+        pub fn remove(&self, value: &[u8]) -> DbResult<bool> {
+            // 取出第一个等于 value 的元素,返回是否找到
+            // 逐个读取而不是扫描,这样也能找到事务中尚未提交的元素
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                for i in 0..list.length()? {
+                    if tx.get(list.get_key(i))?.is_some_and(|v| v == value) {
+                        list.pop(i)?;
+                        return Ok(true)
+                    }
+                }
+                Ok(false)
+            })
+        }
+
+        pub fn compact(&self) -> DbResult<usize> {
+            // 去掉所有空位,元素保持原来的顺序,返回去掉的空位个数
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                let mut meta = list.metadata()?;
+                let mut next = 0; // 下一个元素应该放的位置
+                for i in 0..meta.length as usize {
+                    if tx.get(list.get_key(i))?.is_some() {
+                        if i != next {
+                            list.move_element(i, next)?;
+                        }
+                        next += 1;
+                    }
+                }
+                let holes = meta.length as usize - next;
+                meta.length = next as u64;
+                meta.count = next as u64;
+                list.write_metadata(&meta)?;
+                Ok(holes)
+            })
+        }
+
+        pub fn clear(&self) -> DbResult<()> {
+            // 删除所有元素,列表本身仍然存在
+            self.truncate(0)
+        }
+
+        pub fn extend<V: AsRef<[u8]>>(&self, values: &[V]) -> DbResult<()> {
+            // 批量追加,元数据只读写一次,所有元素在一个事务中写入(提交时是一个 sled::Batch)
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                let mut meta = list.metadata()?;
+                for v in values {
+                    tx.insert(list.get_key(meta.length as usize), v.as_ref())?;
+                    meta.length += 1;
+                    meta.count += 1;
+                }
+                list.write_metadata(&meta)
+            })
+        }
+
+        pub fn get_range(&self, start: usize, end: usize) -> DbResult<Vec<Option<Vec<u8>>>> {
+            // 读取 [start, end) 的元素,end 超过长度时只读到末尾,被删除的位置为 None
+            // 长度和元素在一个事务中用一次范围扫描读取,结果来自同一个版本
+            self.db.transaction(|tx| {
+                let list = self.bind(tx);
+                let end = end.min(list.length()?);
+                if start >= end {
+                    return Ok(vec![])
+                }
+                let mut ret = vec![None; end - start];
+                let iter = ListIter { prefix: list.prefix(), inner: tx.range(list.get_key(start)..list.get_key(end)) };
+                for item in iter {
+                    let (index, value) = item?;
+                    ret[index - start] = Some(value);
+                }
+                Ok(ret)
+            })
+        }
+    }
+
+    pub struct ListIter {
+        // 列表迭代器,用范围扫描代替逐个 get,跳过被删除的位置
+        prefix: Vec<u8>,
+        inner: KvIter,
+    }
+
+    impl Iterator for ListIter {
+        type Item = DbResult<(usize, Vec<u8>)>; // (index, 值)
+        fn next(&mut self) -> Option<Self::Item> {
+            let kv = self.inner.next()?;
+            Some(kv.and_then(|kv| self.decode(kv)))
+        }
+    }
+
+    impl DoubleEndedIterator for ListIter {
+        fn next_back(&mut self) -> Option<Self::Item> {
+            let kv = self.inner.next_back()?;
+            Some(kv.and_then(|kv| self.decode(kv)))
+        }
+    }
+
+    impl ListIter {
+        fn decode(&self, (key, value): (IVec, IVec)) -> DbResult<(usize, Vec<u8>)> {
+            match key_codec::element_index(&self.prefix, &key) {
+                Some(index) => Ok((index as usize, value.to_vec())),
+                None => Err(DbError::CorruptMetadata(format!("List element key {key:?}"))),
+            }
+        }
+    }
+
+    impl ListDb {
+        pub fn iter(&self) -> DbResult<ListIter> {
+            // 按 index 顺序迭代,只扫描 [0, length) 范围内的元素
+            // 在事务中时能看到事务自己的写入
+            let length = self.length()?;
+            let prefix = self.prefix();
+            let inner = self.db.range(self.get_key(0)..self.get_key(length));
+            Ok(ListIter { prefix, inner })
+        }
+
+        pub fn contains(&self, value: &[u8]) -> DbResult<bool> {
+            for item in self.iter()? {
+                if item?.1 == value {
+                    return Ok(true)
+                }
+            }
+            Ok(false)
+        }
+    }
+
+    #[test]
+    fn list_test_string(){
+        use crate::sdk::db::kv_operation::{temporary, KvDbOpera};
+        let a = ListDb::new(
+            KvDbOpera::new(temporary()),
+            "113314".to_string()
+        ).expect("");
+        a.append("很可爱?".as_bytes()).unwrap();
+        a.append("Yes,cute?".as_bytes()).unwrap();
+        dbg!(&a.access(0));
+        for i in 0..a.length().unwrap() {
+            println!("{:?}",a.delete(i))
+        }
+        a.change_length(1).unwrap();
+        a.overwrite(0,&[1]).unwrap();
+        assert_eq!(a.access(0), Ok(Some(vec![1])));
+        assert_eq!(a.access(5), Err(DbError::OutOfBounds { index: 5, length: 1 }));
+        assert!(matches!(ListDb::open(a.db.clone(), "not exist".to_string()), Err(DbError::NotFound(_))));
+
+        // 跨多个列表的事务,失败时所有列表都不变
+        let ret: DbResult<()> = a.db.transaction(|tx| {
+            a.bind(tx).append(&[2])?;
+            ListDb::new(tx.clone(), "113315".to_string())?.append(&[3])?;
+            a.bind(tx).access(9).map(|_| ())
+        });
+        assert!(ret.is_err());
+        assert_eq!(a.length(), Ok(1));
+        assert!(ListDb::open(a.db.clone(), "113315".to_string()).is_err());
+        assert_eq!(a.count(), Ok(1));
+
+        // 旧格式的元数据由迁移工具改写,读取时不会迁移
+        let legacy = ListDb { db: a.db.clone(), name: "legacy".to_string(), kind: CollectionKind::List };
+        a.db.insert(legacy.meta_key(), "3").unwrap();
+        assert!(matches!(legacy.metadata(), Err(DbError::CorruptMetadata(_))));
+        let sparse = ListDb::new(a.db.clone(), "sparse".to_string()).unwrap();
+        sparse.extend(&[[0], [1], [2]]).unwrap();
+        sparse.delete(1).unwrap();
+
+        // 迭代跳过被删除的位置,可以反向迭代
+        assert_eq!(sparse.iter().unwrap().collect::<DbResult<Vec<_>>>(), Ok(vec![(0, vec![0]), (2, vec![2])]));
+        assert_eq!(sparse.iter().unwrap().rev().map(|r| r.unwrap().0).collect::<Vec<_>>(), vec![2, 0]);
+        sparse.change_length(2).unwrap(); // 超出长度的元素不会被迭代
+        assert_eq!(sparse.iter().unwrap().count(), 1);
+        assert_eq!((sparse.contains(&[0]), sparse.contains(&[2])), (Ok(true), Ok(false)));
+    }
+
+    #[cfg(test)]
+    fn check_invariants(list: &ListDb) {
+        // 元数据和储存的键一致:
+        //   count == [0, length) 中储存的元素个数
+        //   index >= length 的位置没有储存的键
+        let meta = list.metadata().unwrap();
+        let stored = list.db.scan_prefix(key_codec::elements_prefix(&list.prefix()))
+            .map(|kv| key_codec::element_index(&list.prefix(), &kv.unwrap().0).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(stored.len() as u64, meta.count, "count {meta:?}, stored {stored:?}");
+        assert!(stored.iter().all(|i| *i < meta.length), "length {meta:?}, stored {stored:?}");
+    }
+
+    #[test]
+    fn list_test_delete() {
+        use crate::sdk::db::kv_operation::{temporary, KvDbOpera};
+        let a = ListDb::new(KvDbOpera::new(temporary()), "delete".to_string()).unwrap();
+        assert_eq!(a.delete(0), Err(DbError::OutOfBounds { index: 0, length: 0 }));
+        for v in 0..4 {
+            a.append(&[v]).unwrap();
+        }
+        a.delete(3).unwrap(); // 删除最后一个元素只缩短一位
+        assert_eq!((a.length(), a.count()), (Ok(3), Ok(3)));
+        a.delete(1).unwrap(); // 中间留下空位
+        assert_eq!((a.length(), a.count(), a.access(1)), (Ok(3), Ok(2), Ok(None)));
+        a.delete(2).unwrap(); // 末尾的空位一起去掉
+        assert_eq!((a.length(), a.count()), (Ok(1), Ok(1)));
+        a.delete(0).unwrap();
+        assert_eq!((a.length(), a.count()), (Ok(0), Ok(0)));
+        check_invariants(&a);
+    }
+
+    #[cfg(test)]
+    mod proptests {
+        use proptest::prelude::*;
+        use super::{check_invariants, ListDb};
+        use crate::sdk::db::kv_operation::{temporary, KvDbOpera, KvDbOperaTrait};
+        use crate::sdk::db::metadata::CollectionKind;
+        use crate::sdk::db::tuple_list_db::TupleList;
+
+        #[derive(Debug, Clone)]
+        enum Op {
+            Append(u8),
+            Overwrite(usize, u8),
+            SafetyOverwrite(usize, u8),
+            Delete(usize),
+            Insert(usize, u8),
+            Pop(usize),
+            Compact,
+            Truncate(usize),
+        }
+
+        fn op() -> impl Strategy<Value = Op> {
+            prop_oneof![
+                3 => any::<u8>().prop_map(Op::Append),
+                1 => (0..12usize, any::<u8>()).prop_map(|(i, v)| Op::Overwrite(i, v)),
+                1 => (0..12usize, any::<u8>()).prop_map(|(i, v)| Op::SafetyOverwrite(i, v)),
+                3 => (0..12usize).prop_map(Op::Delete),
+                1 => (0..12usize, any::<u8>()).prop_map(|(i, v)| Op::Insert(i, v)),
+                1 => (0..12usize).prop_map(Op::Pop),
+                1 => Just(Op::Compact),
+                1 => (0..12usize).prop_map(Op::Truncate),
+            ]
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(64))]
+
+            #[test]
+            fn list_matches_model(ops in proptest::collection::vec(op(), 1..40)) {
+                // 和 Vec<Option<u8>> 模型对比,None 表示空位
+                let list = ListDb::new(KvDbOpera::new(temporary()), "prop".to_string()).unwrap();
+                let mut model: Vec<Option<u8>> = Vec::new();
+                let mut shifted = false; // insert/pop/truncate 可能留下末尾的空位
+                for op in ops {
+                    let ok = match op {
+                        Op::Append(v) => { model.push(Some(v)); list.append(&[v]).is_ok() },
+                        Op::Overwrite(i, v) => {
+                            let ok = i < model.len();
+                            if ok { model[i] = Some(v); }
+                            list.overwrite(i, &[v]).is_ok() == ok
+                        },
+                        Op::SafetyOverwrite(i, v) => {
+                            if i >= model.len() { model.resize(i + 1, None); }
+                            model[i] = Some(v);
+                            list.safety_overwrite(i, &[v]).is_ok()
+                        },
+                        Op::Delete(i) => {
+                            let ok = i < model.len();
+                            if ok {
+                                model[i] = None;
+                                if i + 1 == model.len() {
+                                    while model.last() == Some(&None) { model.pop(); }
+                                }
+                            }
+                            list.delete(i).is_ok() == ok
+                        },
+                        Op::Insert(i, v) => {
+                            let ok = i <= model.len();
+                            if ok { model.insert(i, Some(v)); }
+                            list.insert(i, &[v]).is_ok() == ok
+                        },
+                        Op::Pop(i) => {
+                            let ok = i < model.len();
+                            let expected = if ok { shifted = true; model.remove(i).map(|v| vec![v]) } else { None };
+                            match list.pop(i) { Ok(v) => ok && v == expected, Err(_) => !ok }
+                        },
+                        Op::Compact => { model.retain(Option::is_some); list.compact().is_ok() },
+                        Op::Truncate(n) => { shifted = true; model.truncate(n); list.truncate(n).is_ok() },
+                    };
+                    prop_assert!(ok);
+                    check_invariants(&list);
+                    prop_assert_eq!(list.length().unwrap(), model.len());
+                    prop_assert_eq!(list.count().unwrap(), model.iter().flatten().count());
+                    if !shifted {
+                        // 只用 append/overwrite/delete 时,最后一个位置总是有元素
+                        let length = list.length().unwrap();
+                        prop_assert!(length == 0 || list.access(length - 1).unwrap().is_some());
+                    }
+                }
+                let stored = (0..model.len()).map(|i| list.access(i).unwrap().map(|v| v[0])).collect::<Vec<_>>();
+                prop_assert_eq!(stored, model);
+            }
+        }
+
+        #[derive(Debug, Clone)]
+        enum TupleOp {
+            Append(u8),
+            Delete(usize),
+            DeleteElement(usize, u16),
+        }
+
+        fn tuple_op() -> impl Strategy<Value = TupleOp> {
+            prop_oneof![
+                2 => any::<u8>().prop_map(TupleOp::Append),
+                1 => (0..6usize).prop_map(TupleOp::Delete),
+                2 => (0..6usize, 0..3u16).prop_map(|(i, t)| TupleOp::DeleteElement(i, t)),
+            ]
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(64))]
+
+            #[test]
+            fn tuple_list_keeps_arity(ops in proptest::collection::vec(tuple_op(), 1..30)) {
+                // 删除元组或元组中的元素之后,底层列表的长度仍然是元组长度的倍数,之后的 append 不会覆盖剩下的元组
+                let db = KvDbOpera::new(temporary());
+                let tuples = TupleList::new(db.clone(), "prop".to_string(), 3).unwrap();
+                let list = ListDb::open_with_kind(db, "prop".to_string(), CollectionKind::TupleList).unwrap();
+                let mut model: Vec<[Option<u8>; 3]> = Vec::new();
+                for op in ops {
+                    let last = model.len().checked_sub(1);
+                    let ok = match op {
+                        TupleOp::Append(v) => {
+                            let v = [v, v.wrapping_add(1), v.wrapping_add(2)];
+                            model.push(v.map(Some));
+                            tuples.append(&[&v[0..1], &v[1..2], &v[2..3]]).is_ok()
+                        },
+                        TupleOp::Delete(i) => {
+                            let ok = i < model.len();
+                            if ok { model[i] = [None; 3]; }
+                            tuples.delete(i).is_ok() == ok
+                        },
+                        TupleOp::DeleteElement(i, t) => {
+                            let ok = i < model.len();
+                            if ok { model[i][t as usize] = None; }
+                            tuples.delete_tuple_elements(i, t).is_ok() == ok
+                        },
+                    };
+                    prop_assert!(ok);
+                    // 删除了最后一个元组的最后一个元素时,末尾空的元组一起去掉
+                    if matches!(op, TupleOp::Delete(i) | TupleOp::DeleteElement(i, 2) if Some(i) == last) {
+                        while model.last() == Some(&[None; 3]) { model.pop(); }
+                    }
+                    check_invariants(&list);
+                    prop_assert_eq!(list.length().unwrap(), model.len() * 3);
+                    let stored = (0..model.len()).map(|i| tuples.access(i).unwrap().into_iter().map(|v| v.map(|v| v[0])).collect::<Vec<_>>()).collect::<Vec<_>>();
+                    prop_assert_eq!(stored, model.iter().map(|t| t.to_vec()).collect::<Vec<_>>());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn list_test_shift() {
+        use crate::sdk::db::kv_operation::{temporary, KvDbOpera};
+        let a = ListDb::new(KvDbOpera::new(temporary()), "shift".to_string()).unwrap();
+        let all = |a: &ListDb| (0..a.length().unwrap()).map(|i| a.access(i).unwrap().map(|v| v[0])).collect::<Vec<_>>();
+        for v in [1, 2, 4] {
+            a.append(&[v]).unwrap();
+        }
+        a.insert(2, &[3]).unwrap();
+        a.insert(0, &[0]).unwrap();
+        a.insert(5, &[5]).unwrap();
+        assert_eq!(a.insert(7, &[7]), Err(DbError::OutOfBounds { index: 7, length: 6 }));
+        assert_eq!(all(&a), [0, 1, 2, 3, 4, 5].map(Some));
+        assert_eq!(a.pop(0), Ok(Some(vec![0])));
+        assert_eq!((a.remove(&[3]), a.remove(&[3])), (Ok(true), Ok(false)));
+        assert_eq!(all(&a), [1, 2, 4, 5].map(Some));
+        assert_eq!((a.length(), a.count()), (Ok(4), Ok(4)));
+
+        // 空位跟着移动,compact 去掉空位
+        a.safety_overwrite(6, &[6]).unwrap();
+        a.remove_element(1).unwrap();
+        a.insert(0, &[0]).unwrap();
+        assert_eq!(all(&a), [Some(0), Some(1), None, Some(4), Some(5), None, None, Some(6)]);
+        assert_eq!(a.pop(2), Ok(None));
+        assert_eq!(a.compact(), Ok(2));
+        assert_eq!(all(&a), [0, 1, 4, 5, 6].map(Some));
+        assert_eq!((a.length(), a.count()), (Ok(5), Ok(5)));
+
+        // truncate 和 clear 会删除储存的键
+        a.truncate(2).unwrap();
+        assert_eq!(a.db.scan_prefix(key_codec::elements_prefix(&a.prefix())).count(), 2);
+        a.clear().unwrap();
+        assert_eq!((a.length(), a.count()), (Ok(0), Ok(0)));
+        assert_eq!(a.db.scan_prefix(key_codec::elements_prefix(&a.prefix())).count(), 0);
+
+        a.extend(&[[7], [8], [9]]).unwrap();
+        assert_eq!((a.length(), a.count()), (Ok(3), Ok(3)));
+        a.delete(1).unwrap();
+        assert_eq!(a.get_range(1, 10), Ok(vec![None, Some(vec![9])]));
+        assert_eq!(a.get_range(5, 10), Ok(vec![]));
+
+        // 同时改写所有元素时,get_range 读到的元素来自同一个版本
+        a.clear().unwrap();
+        a.extend(&[[0]; 10]).unwrap();
+        let writer = a.clone();
+        let handle = std::thread::spawn(move || {
+            for round in 1..=255u8 {
+                writer.db.transaction(|tx| (0..10).try_for_each(|i| writer.bind(tx).overwrite(i, &[round]))).unwrap();
+            }
+        });
+        while !handle.is_finished() {
+            let range = a.get_range(0, 10).unwrap();
+            assert!(range.iter().all(|v| *v == range[0]), "{range:?}");
+        }
+        handle.join().unwrap();
+    }
+}
+
+pub mod tuple_list_db {
+    use crate::sdk::db::list_db as list;
+    use crate::sdk::db::metadata::CollectionKind;
+    use crate::sdk::db::kv_operation::KvDbOperaObject;
+    use crate::sdk::error::{DbError, DbResult};
+
+    #[derive(Debug)]
+    #[derive(Clone)]
+    pub struct TupleList {
+        list : list::ListDb,
+        name : String,
+        len : u16,
+    }
+
+    impl  TupleList {
+        // 元组列表的思想是利用列表来储存固定长度的元组
+        // 也就是类似于: 元组列表([(1,2),(3,4)]),实际列表([1,2,3,4])
+        // 因此元组列表的实际列表的长度有 2*元组列表长度 的关系
+        pub fn new(db: KvDbOperaObject, name: String, len: u16) -> DbResult<Self> {
+            // 创建一个元组列表对象
+            Self::new_with_kind(db, name, len, CollectionKind::TupleList)
+        }
+
+        pub(crate) fn new_with_kind(db: KvDbOperaObject, name: String, len: u16, kind: CollectionKind) -> DbResult<Self> {
+            Self::check_len(len)?;
+            let list = list::ListDb::new_with_kind(db,name.clone(),kind,len)?; // 构建列表对象
+            Self::check_stored_arity(&list, len)?;
+            Ok(TupleList { list,name, len})
+        }
+
+        pub fn open(db: KvDbOperaObject, name: String, len: u16) -> DbResult<Self> {
+            // 打开一个元组列表,并且创建对象
+            Self::open_with_kind(db, name, len, CollectionKind::TupleList)
+        }
+
+        pub(crate) fn open_with_kind(db: KvDbOperaObject, name: String, len: u16, kind: CollectionKind) -> DbResult<Self> {
+            Self::check_len(len)?;
+            let list = list::ListDb::open_with_kind(db,name.clone(),kind)?;
+            Self::check_stored_arity(&list, len)?;
+            Ok(TupleList { list,name, len})
+        }
+
+        fn check_len(len: u16) -> DbResult<()> {
+            // 元组长度至少为 1,长度为 0 时无法换算列表的下标
+            if len == 0 {
+                return Err(DbError::TupleArity { expected: 1, found: 0 })
+            }
+            Ok(())
+        }
+
+        fn check_stored_arity(list: &list::ListDb, len: u16) -> DbResult<()> {
+            // 元数据中记录的元组长度必须和打开时给出的一致,从旧格式迁移来的元组列表在这里补上元组长度
+            let mut meta = list.metadata()?;
+            if meta.arity == 0 {
+                meta.arity = len;
+                return list.write_metadata(&meta)
+            }
+            if meta.arity != len {
+                return Err(DbError::TupleArity { expected: meta.arity as usize, found: len as usize })
+            }
+            Ok(())
+        }
+
+        pub fn bind(&self, db:&KvDbOperaObject) -> Self {
+            // 换一个数据库对象(比如事务)来操作这个元组列表
+            TupleList { list: self.list.bind(db), name: self.name.clone(), len: self.len }
+        }
+
+        fn check_arity(&self, value: &[&[u8]]) -> DbResult<()> {
+            // 检查输入的元组长度
+            if value.len() != (self.len as usize) {
+                return Err(DbError::TupleArity { expected: self.len as usize, found: value.len() })
+            }
+            Ok(())
+        }
+
+        pub fn append(&self, value: &[&[u8]]) -> DbResult<()> {
+            // 追加元组元素
+            self.check_arity(value)?;
+            self.list.db.transaction(|tx| {
+                let tuple_list = self.bind(tx);
+                tuple_list.list.change_length(tuple_list.list.length()? + value.len())?;
+                tuple_list.overwrite(tuple_list.length()?-1,value)
+            })
+        }
+
+        pub fn access(&self, index: usize) -> DbResult<Vec<Option<Vec<u8>>>> {
+            // 访问,元组中被删除的元素为 None
+            let length = self.length()?;
+            if index >= length {
+                return Err(DbError::OutOfBounds { index, length })
+            }
+            let mut ret:Vec<Option<Vec<u8>>> = vec![];
+            for i in index*(self.len as usize)..(index*(self.len as usize))+self.len as usize{
+                ret.push(self.list.access(i)?);
+            }
+            Ok(ret)
+        }
+
+        pub fn overwrite(&self, index: usize, value: &[&[u8]]) -> DbResult<()> {
+            // 覆写元组,整个元组在同一个事务中写入
+            self.check_arity(value)?; // 输入长度错误
+            self.list.db.transaction(|tx| {
+                let list = self.list.bind(tx);
+                for (i, v) in value.iter().enumerate() {
+                    list.overwrite(index*(self.len as usize)+i, v)?; // 覆写原始数据
+                }
+                Ok(())
+            })
+        }
+        pub fn safety_overwrite(&self, index: usize, value: &[&[u8]]) -> DbResult<()> {
+            self.check_arity(value)?; // 输入长度错误
+            self.list.db.transaction(|tx| {
+                let list = self.list.bind(tx);
+                for (i, v) in value.iter().enumerate() {
+                    list.safety_overwrite(index*(self.len as usize)+i, v)?; // 覆写原始数据
+                }
+                Ok(())
+            })
+        }
+
+        fn check_tuple_index(&self, tuple_index:u16) -> DbResult<()> {
+            // 检查元组index是否越界
+            if tuple_index >= self.len {
+                return Err(DbError::OutOfBounds { index: tuple_index as usize, length: self.len as usize })
+            }
+            Ok(())
+        }
+
+        pub fn safety_overwrite_tuple_elements(&self, index: usize,tuple_index:u16,value : &[u8]) -> DbResult<()> {
+            // 列表index和列表index对应的元组index
+            // 覆写列表中的元组中的某个元素的值
+            self.check_tuple_index(tuple_index)?;
+            self.list.safety_overwrite(self.get_list_index(index,tuple_index), value) // 覆写原始数据
+        }
+
+        pub fn overwrite_tuple_elements(&self, index: usize,tuple_index:u16,value : &[u8]) -> DbResult<()> {
             // 列表index和列表index对应的元组index
             // 覆写列表中的元组中的某个元素的值
-            if tuple_index > self.len { return Err("Value length error".to_string()) } // 输入长度错误
-            match self.list.overwrite(self.get_list_index(index,tuple_index), value) { // 覆写原始数据
-                Ok(_) => { Ok(true) },
-                Err(e) => {
-                    let _ = self.delete_tuple_elements(index,tuple_index).unwrap(); // 收拾残局,失败即报错
-                    return Err(e); // 输出错误
+            self.check_tuple_index(tuple_index)?;
+            self.list.overwrite(self.get_list_index(index,tuple_index), value) // 覆写原始数据
+        }
+
+        pub fn access_tuple_elements(&self, index: usize,tuple_index:u16) -> DbResult<Option<Vec<u8>>> {
+            self.check_tuple_index(tuple_index)?;
+            self.list.access(self.get_list_index(index,tuple_index))
+        }
+
+        pub fn delete_tuple_elements(&self, index: usize,tuple_index:u16) -> DbResult<()> {
+            // 列表index和列表index对应的元组index
+            // 删除列表中的元组中的某个元素的值
+            self.check_tuple_index(tuple_index)?;
+            self.list.db.transaction(|tx| self.bind(tx).delete_list_element(self.get_list_index(index,tuple_index)))
+        }
+
+        fn delete_list_element(&self, j: usize) -> DbResult<()> {
+            // 删除底层列表的一个元素
+            // 列表的 delete 会去掉末尾的空位,这里把长度向上取整到元组长度的倍数,不截断前面的元组
+            self.list.delete(j)?;
+            let (len, length) = (self.len as usize, self.list.length()?);
+            if length % len != 0 {
+                self.list.change_length(length.div_ceil(len) * len)?;
+            }
+            Ok(())
+        }
+
+        fn get_list_index(&self, index: usize,tuple_index:u16) -> usize {
+            // 列表index和列表index对应的元组index
+            index*(self.len as usize)+(tuple_index as usize)
+        }
+
+        pub fn delete(&self, index: usize) -> DbResult<()> {
+            // 删除列表中的某个元组
+            self.list.db.transaction(|tx| {
+                let tuple_list = self.bind(tx);
+                let length = tuple_list.length()?;
+                if index >= length {
+                    return Err(DbError::OutOfBounds { index, length })
+                }
+                for j in (index*(self.len as usize))..(index*(self.len as usize))+(self.len as usize) {
+                    tuple_list.delete_list_element(j)?;
+                }
+                Ok(())
+            })
+        }
+
+        pub fn length(&self) -> DbResult<usize> {
+            Ok(self.list.length()?/(self.len as usize))
+        }
+
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        pub(crate) fn destroy(&self) -> DbResult<()> {
+            // 删除整个元组列表
+            self.list.destroy()
+        }
+
+        pub(crate) fn swap_remove(&self, index: usize) -> DbResult<()> {
+            // 用最后一个元组覆盖 index 处的元组,再删除最后一个元组,元组列表中不会留下空位(不保持顺序)
+            self.list.db.transaction(|tx| {
+                let tuple_list = self.bind(tx);
+                let length = tuple_list.length()?;
+                if index >= length {
+                    return Err(DbError::OutOfBounds { index, length })
+                }
+                let last = length - 1;
+                if index != last {
+                    for (j, v) in tuple_list.access(last)?.into_iter().enumerate() {
+                        let i = tuple_list.get_list_index(index, j as u16);
+                        match v {
+                            Some(v) => tuple_list.list.overwrite(i, &v)?,
+                            None => tuple_list.list.remove_element(i)?,
+                        }
+                    }
+                }
+                tuple_list.list.truncate(last * self.len as usize)
+            })
+        }
+    }
+
+    pub struct TupleListIter {
+        // 元组列表迭代器,把实际列表中相邻的元素组合为元组
+        // front/back 是两端各自多读出来的一个元素,属于下一个元组
+        inner: list::ListIter,
+        len: usize,
+        front: Option<(usize, Vec<u8>)>,
+        back: Option<(usize, Vec<u8>)>,
+    }
+
+    type Tuple = (usize, Vec<Option<Vec<u8>>>); // (元组index, 元组)
+
+    impl TupleListIter {
+        fn pull_front(&mut self) -> Option<DbResult<(usize, Vec<u8>)>> {
+            // 从前面取一个元素,中间的元素取完后取另一端多读出来的元素
+            match self.front.take() {
+                Some(item) => Some(Ok(item)),
+                None => self.inner.next().or_else(|| self.back.take().map(Ok)),
+            }
+        }
+
+        fn pull_back(&mut self) -> Option<DbResult<(usize, Vec<u8>)>> {
+            match self.back.take() {
+                Some(item) => Some(Ok(item)),
+                None => self.inner.next_back().or_else(|| self.front.take().map(Ok)),
+            }
+        }
+
+        fn collect_tuple(&mut self, forward: bool) -> Option<DbResult<Tuple>> {
+            // 一直读到下一个元组的元素为止,这个元素留给下一次
+            let pull = |this: &mut Self| if forward { this.pull_front() } else { this.pull_back() };
+            let (i, v) = match pull(self)? {
+                Ok(item) => item,
+                Err(e) => return Some(Err(e)),
+            };
+            let index = i / self.len;
+            let mut tuple = vec![None; self.len];
+            tuple[i % self.len] = Some(v);
+            loop {
+                match pull(self) {
+                    None => break,
+                    Some(Err(e)) => return Some(Err(e)),
+                    Some(Ok((j, w))) if j / self.len == index => tuple[j % self.len] = Some(w),
+                    Some(Ok(item)) => {
+                        if forward { self.front = Some(item) } else { self.back = Some(item) }
+                        break
+                    }
+                }
+            }
+            Some(Ok((index, tuple)))
+        }
+    }
+
+    impl Iterator for TupleListIter {
+        type Item = DbResult<Tuple>; // 整个元组都被删除时跳过,部分删除的元素为 None
+        fn next(&mut self) -> Option<Self::Item> {
+            self.collect_tuple(true)
+        }
+    }
+
+    impl DoubleEndedIterator for TupleListIter {
+        fn next_back(&mut self) -> Option<Self::Item> {
+            self.collect_tuple(false)
+        }
+    }
+
+    impl TupleList {
+        pub fn iter(&self) -> DbResult<TupleListIter> {
+            Ok(TupleListIter { inner: self.list.iter()?, len: self.len as usize, front: None, back: None })
+        }
+
+        pub fn contains(&self, value: &[&[u8]]) -> DbResult<bool> {
+            for item in self.iter()? {
+                let (_, tuple) = item?;
+                if tuple.iter().map(|e| e.as_deref()).eq(value.iter().map(|v| Some(*v))) {
+                    return Ok(true)
+                }
+            }
+            Ok(false)
+        }
+
+        pub fn arity(&self) -> u16 {
+            self.len
+        }
+    }
+    #[test]
+    fn test(){
+        use crate::sdk::db::kv_operation::{temporary, KvDbOperaTrait};
+        let a = TupleList::new( // 如果这个元组列表不存在,需要先 new
+            KvDbOperaObject::new(temporary()), "156745qxxs23".to_string(), 2).unwrap();
+        dbg!(a.length().unwrap());
+        dbg!(&a.append(&["I love".as_bytes(), "XXXXXXXXXXXX".as_bytes()]));
+        dbg!(a.length().unwrap());
+        dbg!(&a.access(a.length().unwrap()-1));
+        dbg!(&a.delete(a.length().unwrap()-1));
+        dbg!(a.length().unwrap());
+        dbg!(&a.append(&["I love".as_bytes(), "She".as_bytes()]));
+        dbg!(&a.overwrite(0,&["I love".as_bytes(), "He".as_bytes()]));
+        dbg!(&a.access(a.length().unwrap()-1));
+        assert_eq!(a.append(&["I love".as_bytes()]), Err(DbError::TupleArity { expected: 2, found: 1 }));
+        assert_eq!(a.access_tuple_elements(0, 2), Err(DbError::OutOfBounds { index: 2, length: 2 }));
+        dbg!(&a.delete(a.length().unwrap()-1));
+        dbg!(a.length().unwrap());
+
+        // 两端同时迭代时,元组不会被拆开
+        let b = TupleList::new(a.list.db.clone(), "iter".to_string(), 3).unwrap();
+        for i in 0..4u8 {
+            b.append(&[&[i], &[i + 10], &[i + 20]]).unwrap();
+        }
+        b.delete(1).unwrap();
+        b.delete_tuple_elements(2, 1).unwrap();
+        let mut it = b.iter().unwrap();
+        assert_eq!(it.next(), Some(Ok((0, vec![Some(vec![0]), Some(vec![10]), Some(vec![20])]))));
+        assert_eq!(it.next_back(), Some(Ok((3, vec![Some(vec![3]), Some(vec![13]), Some(vec![23])]))));
+        assert_eq!(it.next_back(), Some(Ok((2, vec![Some(vec![2]), None, Some(vec![22])]))));
+        assert_eq!(it.next(), None);
+        assert_eq!(b.contains(&[&[3], &[13], &[23]]), Ok(true));
+        assert_eq!(b.contains(&[&[1], &[11], &[21]]), Ok(false));
+    }
+}
+
+pub mod hashtable_sled_db {
+    // 基于sled的有序哈希表
+    // 每个条目直接储存为一个 sled 键 [TAG_KV][名字][0x00 0x01][0x01][原始键],
+    // 所以条目在 sled 中按键的字节顺序排好序,迭代和范围查询都是 sled 的范围扫描
+    // 元数据(条目个数)储存在 [TAG_KV][名字][0x00 0x01][0x00]
+    // sorted_map_db::SortedMap 也使用这里的实现,只是类型标记不同
+    use std::ops::{Bound, RangeBounds};
+    use sled::IVec;
+    use crate::sdk::catalog;
+    use crate::sdk::db::key_codec;
+    use crate::sdk::db::kv_operation::{KvDbOperaObject, KvIter};
+    use crate::sdk::db::metadata::{CollectionKind, Metadata};
+    use crate::sdk::db::kv_operation::KvDbOperaTrait;
+    use crate::sdk::error::{DbError, DbResult};
+
+    #[derive(Debug)]
+    #[derive(Clone)]
+    struct OriginalHashtable { // 原始hash表,只有键值对
+        db : KvDbOperaObject,
+        name : String,
+        tag : u8, // 键的类型标记
+    }
+
+    #[derive(Debug)]
+    #[derive(Clone)]
+    pub struct Hashtable { // 带有元数据的有序哈希表
+        hashtable: OriginalHashtable,
+        kind: CollectionKind,
+    }
+
+    impl OriginalHashtable {
+        fn new(db:KvDbOperaObject , name : String, tag : u8) -> Self {
+            OriginalHashtable { db,name,tag }
+        }
+
+        fn insert(&self,key:&[u8],value:&[u8]) -> DbResult<()> {
+            self.db.insert(self.get_key(key),value)
+        }
+
+        fn get(&self,key:&[u8]) -> DbResult<Option<Vec<u8>>> {
+            Ok(self.db.get(self.get_key(key))?.map(|t| t.to_vec()))
+        }
+
+        fn delete(&self,key:&[u8]) -> DbResult<()> {
+            self.db.delete(self.get_key(key))
+        }
+
+        fn prefix(&self) -> Vec<u8> {
+            key_codec::prefix(self.tag, &self.name)
+        }
+
+        fn get_key(&self,key:&[u8]) -> Vec<u8> {
+            key_codec::entry_key(&self.prefix(), key)
+        }
+
+        fn meta_key(&self) -> Vec<u8> {
+            key_codec::meta_key(&self.prefix())
+        }
+
+        fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range:R) -> OrderedIter {
+            // 把键的范围转化为 sled 键的范围,没有下界/上界时为整个表
+            let elements = key_codec::elements_prefix(&self.prefix());
+            let start = match range.start_bound() {
+                Bound::Included(k) => Bound::Included(self.get_key(k.as_ref())),
+                Bound::Excluded(k) => Bound::Excluded(self.get_key(k.as_ref())),
+                Bound::Unbounded => Bound::Included(elements.clone()),
+            };
+            let end = match range.end_bound() {
+                Bound::Included(k) => Bound::Included(self.get_key(k.as_ref())),
+                Bound::Excluded(k) => Bound::Excluded(self.get_key(k.as_ref())),
+                Bound::Unbounded => Bound::Excluded(key_codec::prefix_end(&elements)),
+            };
+            OrderedIter { prefix_len: elements.len(), inner: self.db.range((start, end)) }
+        }
+    }
+
+    pub struct OrderedIter {
+        // 按键的顺序迭代 (键, 值)
+        prefix_len: usize,
+        inner: KvIter,
+    }
+
+    impl OrderedIter {
+        fn entry(&self, kv: DbResult<(IVec, IVec)>) -> DbResult<(Vec<u8>, Vec<u8>)> {
+            kv.map(|(key, value)| (key[self.prefix_len..].to_vec(), value.to_vec()))
+        }
+    }
+
+    impl Iterator for OrderedIter {
+        type Item = DbResult<(Vec<u8>, Vec<u8>)>;
+        fn next(&mut self) -> Option<Self::Item> {
+            let kv = self.inner.next()?;
+            Some(self.entry(kv))
+        }
+    }
+
+    impl DoubleEndedIterator for OrderedIter {
+        fn next_back(&mut self) -> Option<Self::Item> {
+            let kv = self.inner.next_back()?;
+            Some(self.entry(kv))
+        }
+    }
+
+    impl Hashtable {
+        pub fn new(db:KvDbOperaObject,name:String) -> DbResult<Self> {
+            // 打开一个哈希表,不存在时创建
+            Self::new_with_kind(db, name, key_codec::TAG_KV, CollectionKind::OrderedHashtable)
+        }
+
+        pub(crate) fn new_with_kind(db:KvDbOperaObject,name:String,tag:u8,kind:CollectionKind) -> DbResult<Self> {
+            let table = Hashtable { hashtable: OriginalHashtable::new(db, name, tag), kind };
+            table.hashtable.db.transaction(|tx| {
+                if tx.get(table.hashtable.meta_key())?.is_none() {
+                    tx.insert(table.hashtable.meta_key(), Metadata::new(kind, 0).encode())?;
+                    catalog::register(tx, kind, table.name())?;
+                }
+                Ok(())
+            })?;
+            Ok(table)
+        }
+
+        pub fn open(db:KvDbOperaObject,name:String) -> DbResult<Self> {
+            // 打开一个哈希表,不存在时返回 NotFound
+            Self::open_with_kind(db, name, key_codec::TAG_KV, CollectionKind::OrderedHashtable)
+        }
+
+        pub(crate) fn open_with_kind(db:KvDbOperaObject,name:String,tag:u8,kind:CollectionKind) -> DbResult<Self> {
+            let table = Hashtable { hashtable: OriginalHashtable::new(db, name, tag), kind };
+            table.metadata()?;
+            Ok(table)
+        }
+
+        pub fn bind(&self, db:&KvDbOperaObject) -> Self {
+            // 换一个数据库对象(比如事务)来操作这个哈希表
+            Hashtable { hashtable: OriginalHashtable { db: db.clone(), ..self.hashtable.clone() }, kind: self.kind }
+        }
+
+        pub fn name(&self) -> &str {
+            &self.hashtable.name
+        }
+
+        pub(crate) fn db(&self) -> KvDbOperaObject {
+            self.hashtable.db.clone()
+        }
+
+        fn metadata(&self) -> DbResult<Metadata> {
+            let v = self.hashtable.db.get(self.hashtable.meta_key())?
+                .ok_or_else(|| DbError::NotFound(format!("{:?} {}", self.kind, self.hashtable.name)))?;
+            let meta = Metadata::decode(&v)?.current()?;
+            if meta.kind != self.kind {
+                return Err(DbError::CorruptMetadata(format!("{} is a {:?}, not a {:?}", self.hashtable.name, meta.kind, self.kind)))
+            }
+            Ok(meta)
+        }
+
+        fn write_metadata(&self, meta:&Metadata) -> DbResult<()> {
+            self.hashtable.db.insert(self.hashtable.meta_key(), meta.encode())
+        }
+
+        pub fn insert(&self,key:&[u8],value:&[u8]) -> DbResult<()> {
+            self.hashtable.db.transaction(|tx| {
+                let table = self.bind(tx);
+                if table.hashtable.get(key)?.is_none() { // 新的键,条目个数加一
+                    let mut meta = table.metadata()?;
+                    meta.count += 1;
+                    table.write_metadata(&meta)?;
+                }
+                table.hashtable.insert(key, value)
+            })
+        }
+
+        pub fn get(&self,key:&[u8]) -> DbResult<Option<Vec<u8>>> {
+            self.hashtable.get(key)
+        }
+
+        pub fn delete(&self,key:&[u8]) -> DbResult<()> {
+            self.hashtable.db.transaction(|tx| {
+                let table = self.bind(tx);
+                if table.hashtable.get(key)?.is_none() {
+                    return Err(DbError::NotFound(format!("Key {}", String::from_utf8_lossy(key))))
+                }
+                let mut meta = table.metadata()?;
+                meta.count = meta.count.saturating_sub(1);
+                table.write_metadata(&meta)?;
+                table.hashtable.delete(key)
+            })
+        }
+
+        pub fn insert_many<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, items:&[(K, V)]) -> DbResult<()> {
+            // 批量插入,条目个数只写一次,所有条目在一个事务中写入(提交时是一个 sled::Batch)
+            self.hashtable.db.transaction(|tx| {
+                let table = self.bind(tx);
+                let mut meta = table.metadata()?;
+                for (key, value) in items {
+                    if table.hashtable.get(key.as_ref())?.is_none() {
+                        meta.count += 1;
+                    }
+                    table.hashtable.insert(key.as_ref(), value.as_ref())?;
+                }
+                table.write_metadata(&meta)
+            })
+        }
+
+        pub fn get_many<K: AsRef<[u8]>>(&self, keys:&[K]) -> DbResult<Vec<Option<Vec<u8>>>> {
+            // 批量读取,所有键在一个事务中读取,结果来自同一个版本
+            self.hashtable.db.transaction(|tx| {
+                let table = self.bind(tx);
+                keys.iter().map(|key| table.get(key.as_ref())).collect()
+            })
+        }
+
+        pub fn delete_many<K: AsRef<[u8]>>(&self, keys:&[K]) -> DbResult<usize> {
+            // 批量删除,跳过不存在的键,返回删除的条目个数
+            self.hashtable.db.transaction(|tx| {
+                let table = self.bind(tx);
+                let mut meta = table.metadata()?;
+                let mut deleted = 0;
+                for key in keys {
+                    if table.hashtable.get(key.as_ref())?.is_some() {
+                        table.hashtable.delete(key.as_ref())?;
+                        deleted += 1;
+                    }
+                }
+                meta.count = meta.count.saturating_sub(deleted as u64);
+                table.write_metadata(&meta)?;
+                Ok(deleted)
+            })
+        }
+
+        pub fn contains_key(&self,key:&[u8]) -> DbResult<bool> {
+            Ok(self.get(key)?.is_some())
+        }
+
+        pub fn len(&self) -> DbResult<usize> {
+            Ok(self.metadata()?.count as usize)
+        }
+
+        pub fn is_empty(&self) -> DbResult<bool> {
+            Ok(self.len()? == 0)
+        }
+
+        pub fn iter(&self) -> OrderedIter {
+            // 按键的字节顺序迭代,在事务中时能看到事务自己的写入
+            self.hashtable.range::<&[u8], _>(..)
+        }
+
+        pub fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range:R) -> OrderedIter {
+            // 迭代键在 range 中的条目
+            self.hashtable.range(range)
+        }
+    }
+
+    #[test]
+    fn test_original_hashtable () {
+        use crate::sdk::db::kv_operation::temporary;
+        let a = OriginalHashtable::new(KvDbOperaObject::new(temporary()),"823789792".to_string(),key_codec::TAG_KV);
+        dbg!(&a.insert(b"heheh", &[1, 1, 4, 5, 1, 4]));
+        dbg!(&a.get(b"heheh"));
+        dbg!(&a.delete(b"heheh"));
+        dbg!(&a.get(b"heheh"));
+    }
+
+    #[test]
+    fn test_ordered_hashtable () {
+        use crate::sdk::db::kv_operation::temporary;
+        let db = KvDbOperaObject::new(temporary());
+        assert!(matches!(Hashtable::open(db.clone(), "t".to_string()), Err(DbError::NotFound(_))));
+        let a = Hashtable::new(db.clone(), "t".to_string()).unwrap();
+        let b = Hashtable::new(db.clone(), "t\0".to_string()).unwrap(); // 名字互为前缀的表互不影响
+        b.insert(b"x", b"").unwrap();
+        for k in [&b"b"[..], b"a", b"c\0", b"c", b"a"] {
+            a.insert(k, k).unwrap();
+        }
+        assert_eq!((a.len(), b.len()), (Ok(4), Ok(1)));
+        fn keys(it: impl Iterator<Item = DbResult<(Vec<u8>, Vec<u8>)>>) -> Vec<Vec<u8>> {
+            it.map(|r| r.unwrap().0).collect()
+        }
+        assert_eq!(keys(a.iter()), vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"c\0".to_vec()]);
+        assert_eq!(keys(a.iter().rev()), vec![b"c\0".to_vec(), b"c".to_vec(), b"b".to_vec(), b"a".to_vec()]);
+        assert_eq!(keys(a.range(&b"b"[..]..&b"c\0"[..])), vec![b"b".to_vec(), b"c".to_vec()]);
+        assert_eq!(keys(a.range(&b"b"[..]..)), vec![b"b".to_vec(), b"c".to_vec(), b"c\0".to_vec()]);
+        a.delete(b"a").unwrap();
+        assert!(matches!(a.delete(b"a"), Err(DbError::NotFound(_))));
+        assert_eq!((a.len(), a.get(b"b"), a.contains_key(b"a")), (Ok(3), Ok(Some(b"b".to_vec())), Ok(false)));
+
+        a.insert_many(&[(&b"b"[..], &b"B"[..]), (b"d", b"D"), (b"d", b"d")]).unwrap();
+        assert_eq!(a.len(), Ok(4));
+        assert_eq!(a.get_many(&[b"b", b"d", b"z"]), Ok(vec![Some(b"B".to_vec()), Some(b"d".to_vec()), None]));
+        assert_eq!(a.delete_many(&[b"b", b"z", b"b"]), Ok(1));
+        assert_eq!((a.len(), a.iter().count()), (Ok(3), 3));
+
+        // 同时批量写入时,get_many 读到的值来自同一个版本
+        let keys = (0..10u8).map(|i| vec![b'k', i]).collect::<Vec<_>>();
+        let writer = a.clone();
+        let written = keys.clone();
+        let handle = std::thread::spawn(move || {
+            for round in 0..=255u8 {
+                writer.insert_many(&written.iter().map(|k| (k.clone(), vec![round])).collect::<Vec<_>>()).unwrap();
+            }
+        });
+        while !handle.is_finished() {
+            let values = a.get_many(&keys).unwrap();
+            assert!(values.iter().all(|v| *v == values[0]), "{values:?}");
+        }
+        handle.join().unwrap();
+    }
+
+}
+
+pub mod sorted_map_db {
+    // 有序映射,适合时间序列和排行榜
+    // 条目按键的字节顺序排列(python 的类型化键见 typed_key,数字和元组的编码保持顺序),
+    // 支持范围查询、前缀查询、first/last、floor/ceiling 和反向扫描(迭代器都可以 rev())
+    // 储存方式和 hashtable_sled_db 相同,类型标记为 CollectionKind::SortedMap
+    use std::ops::RangeBounds;
+    use crate::sdk::db::key_codec;
+    use crate::sdk::db::hashtable_sled_db::{Hashtable, OrderedIter};
+    use crate::sdk::db::kv_operation::KvDbOperaObject;
+    use crate::sdk::db::metadata::CollectionKind;
+    use crate::sdk::error::DbResult;
+
+    pub type Entry = (Vec<u8>, Vec<u8>); // (键, 值)
+
+    #[derive(Debug)]
+    #[derive(Clone)]
+    pub struct SortedMap {
+        map: Hashtable,
+    }
+
+    impl SortedMap {
+        pub fn new(db:KvDbOperaObject,name:String) -> DbResult<Self> {
+            // 打开一个有序映射,不存在时创建
+            let kind = CollectionKind::SortedMap;
+            Ok(SortedMap { map: Hashtable::new_with_kind(db, name, key_codec::kind_tag(kind), kind)? })
+        }
+
+        pub fn open(db:KvDbOperaObject,name:String) -> DbResult<Self> {
+            // 打开一个有序映射,不存在时返回 NotFound
+            let kind = CollectionKind::SortedMap;
+            Ok(SortedMap { map: Hashtable::open_with_kind(db, name, key_codec::kind_tag(kind), kind)? })
+        }
+
+        pub fn bind(&self, db:&KvDbOperaObject) -> Self {
+            SortedMap { map: self.map.bind(db) }
+        }
+
+        pub fn name(&self) -> &str {
+            self.map.name()
+        }
+
+        pub fn insert(&self,key:&[u8],value:&[u8]) -> DbResult<()> {
+            self.map.insert(key, value)
+        }
+
+        pub fn get(&self,key:&[u8]) -> DbResult<Option<Vec<u8>>> {
+            self.map.get(key)
+        }
+
+        pub fn delete(&self,key:&[u8]) -> DbResult<()> {
+            self.map.delete(key)
+        }
+
+        pub fn contains_key(&self,key:&[u8]) -> DbResult<bool> {
+            self.map.contains_key(key)
+        }
+
+        pub fn len(&self) -> DbResult<usize> {
+            self.map.len()
+        }
+
+        pub fn is_empty(&self) -> DbResult<bool> {
+            self.map.is_empty()
+        }
+
+        pub fn iter(&self) -> OrderedIter {
+            self.map.iter()
+        }
+
+        pub fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range:R) -> OrderedIter {
+            self.map.range(range)
+        }
+
+        pub fn prefix(&self, prefix:&[u8]) -> OrderedIter {
+            // 键以 prefix 开头的条目
+            let end = key_codec::prefix_end(prefix);
+            if end.is_empty() { // prefix 为空或者全是 0xFF,没有上界
+                return self.map.range(prefix..)
+            }
+            self.map.range(prefix..end.as_slice())
+        }
+
+        pub fn first(&self) -> DbResult<Option<Entry>> {
+            self.iter().next().transpose()
+        }
+
+        pub fn last(&self) -> DbResult<Option<Entry>> {
+            self.iter().next_back().transpose()
+        }
+
+        pub fn floor(&self, key:&[u8]) -> DbResult<Option<Entry>> {
+            // 键 <= key 的最大条目
+            self.range(..=key).next_back().transpose()
+        }
+
+        pub fn ceiling(&self, key:&[u8]) -> DbResult<Option<Entry>> {
+            // 键 >= key 的最小条目
+            self.range(key..).next().transpose()
+        }
+    }
+
+    #[test]
+    fn test_sorted_map() {
+        use crate::sdk::db::kv_operation::{temporary, KvDbOperaTrait};
+        use crate::sdk::db::typed_key::TypedKey;
+        let db = KvDbOperaObject::new(temporary());
+        let a = SortedMap::new(db.clone(), "ts".to_string()).unwrap();
+        assert_eq!((a.first(), a.floor(b"x")), (Ok(None), Ok(None)));
+        // 时间序列: 整数键的编码保持数字顺序
+        let ts = |t: i64| TypedKey::Int(t).encode();
+        for t in [30, -5, 10, 20, 0] {
+            a.insert(&ts(t), &t.to_be_bytes()).unwrap();
+        }
+        let keys = |it: OrderedIter| it.map(|r| TypedKey::decode(&r.unwrap().0).unwrap()).collect::<Vec<_>>();
+        assert_eq!(keys(a.range(ts(0)..ts(30))), [0, 10, 20].map(TypedKey::Int));
+        assert_eq!(a.first().map(|e| e.map(|e| e.0)), Ok(Some(ts(-5))));
+        assert_eq!(a.last().map(|e| e.map(|e| e.0)), Ok(Some(ts(30))));
+        assert_eq!(a.floor(&ts(15)).map(|e| e.map(|e| e.0)), Ok(Some(ts(10))));
+        assert_eq!(a.ceiling(&ts(15)).map(|e| e.map(|e| e.0)), Ok(Some(ts(20))));
+        assert_eq!(a.ceiling(&ts(31)), Ok(None));
+        assert_eq!(a.iter().rev().map(|r| r.unwrap().1).next(), Some(30i64.to_be_bytes().to_vec()));
+
+        let b = SortedMap::new(db.clone(), "words".to_string()).unwrap();
+        for k in ["app", "apple", "apply", "b", "ap"] {
+            b.insert(k.as_bytes(), b"").unwrap();
+        }
+        assert_eq!(b.prefix(b"app").map(|r| r.unwrap().0).collect::<Vec<_>>(), [&b"app"[..], b"apple", b"apply"]);
+        assert_eq!(b.prefix(b"").count(), 5);
+        assert_eq!(b.len(), Ok(5));
+        assert!(SortedMap::open(db.clone(), "nope".to_string()).is_err());
+        // 同名的有序哈希表和有序映射是不同的集合
+        assert!(crate::sdk::db::hashtable_sled_db::Hashtable::open(db, "words".to_string()).is_err());
+    }
+}
+
+pub mod set_db {
+    // 集合
+    // 成员储存方式和 sorted_map_db 相同(值为空),类型标记为 CollectionKind::Set,
+    // 所以成员按字节顺序排列,并集、交集、差集是两个有序序列的归并
+    use std::cmp::Ordering;
+    use std::collections::BTreeSet;
+    use crate::sdk::db::key_codec;
+    use crate::sdk::db::hashtable_sled_db::{Hashtable, OrderedIter};
+    use crate::sdk::db::kv_operation::KvDbOperaObject;
+    use crate::sdk::db::metadata::CollectionKind;
+    use crate::sdk::error::DbResult;
+
+    #[derive(Debug)]
+    #[derive(Clone)]
+    pub struct Set {
+        set: Hashtable,
+    }
+
+    pub struct SetIter(OrderedIter);
+
+    impl Iterator for SetIter {
+        type Item = DbResult<Vec<u8>>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            Some(self.0.next()?.map(|(k, _)| k))
+        }
+    }
+
+    impl DoubleEndedIterator for SetIter {
+        fn next_back(&mut self) -> Option<Self::Item> {
+            Some(self.0.next_back()?.map(|(k, _)| k))
+        }
+    }
+
+    impl Set {
+        pub fn new(db:KvDbOperaObject,name:String) -> DbResult<Self> {
+            // 打开一个集合,不存在时创建
+            let kind = CollectionKind::Set;
+            Ok(Set { set: Hashtable::new_with_kind(db, name, key_codec::kind_tag(kind), kind)? })
+        }
+
+        pub fn open(db:KvDbOperaObject,name:String) -> DbResult<Self> {
+            // 打开一个集合,不存在时返回 NotFound
+            let kind = CollectionKind::Set;
+            Ok(Set { set: Hashtable::open_with_kind(db, name, key_codec::kind_tag(kind), kind)? })
+        }
+
+        pub fn bind(&self, db:&KvDbOperaObject) -> Self {
+            Set { set: self.set.bind(db) }
+        }
+
+        pub fn name(&self) -> &str {
+            self.set.name()
+        }
+
+        fn db(&self) -> KvDbOperaObject {
+            self.set.db()
+        }
+
+        pub fn add(&self,member:&[u8]) -> DbResult<bool> {
+            // 返回是否是新加入的成员
+            self.db().transaction(|tx| {
+                let set = self.bind(tx);
+                if set.contains(member)? {
+                    return Ok(false)
+                }
+                set.set.insert(member, &[])?;
+                Ok(true)
+            })
+        }
+
+        pub fn remove(&self,member:&[u8]) -> DbResult<bool> {
+            // 返回成员是否存在,不存在时什么也不做
+            self.db().transaction(|tx| {
+                let set = self.bind(tx);
+                if !set.contains(member)? {
+                    return Ok(false)
+                }
+                set.set.delete(member)?;
+                Ok(true)
+            })
+        }
+
+        pub fn contains(&self,member:&[u8]) -> DbResult<bool> {
+            self.set.contains_key(member)
+        }
+
+        pub fn len(&self) -> DbResult<usize> {
+            self.set.len()
+        }
+
+        pub fn is_empty(&self) -> DbResult<bool> {
+            self.set.is_empty()
+        }
+
+        pub fn iter(&self) -> SetIter {
+            // 按字节顺序迭代成员,在事务中时能看到事务自己的写入
+            SetIter(self.set.iter())
+        }
+
+        pub fn pop_first(&self) -> DbResult<Option<Vec<u8>>> {
+            // 移除并返回最小的成员,集合为空时返回 None;读取和移除在同一个事务中,同时 pop 不会取到同一个成员
+            self.db().transaction(|tx| {
+                let set = self.bind(tx);
+                let Some(member) = set.iter().next().transpose()? else {
+                    return Ok(None)
+                };
+                set.set.delete(&member)?;
+                Ok(Some(member))
+            })
+        }
+
+        pub fn clear(&self) -> DbResult<()> {
+            self.db().transaction(|tx| {
+                let set = self.bind(tx);
+                for m in set.iter() {
+                    set.remove(&m?)?;
                 }
-            }
+                Ok(())
+            })
         }
 
-        pub(crate) fn access_tuple_elements(&self, index: usize,tuple_index:u16) -> Option<Vec<u8>> {
-            self.list.access(self.get_list_index(index,tuple_index))
+        pub fn update(&self,members:&BTreeSet<Vec<u8>>) -> DbResult<()> {
+            // 加入所有成员(|=),下面几个原地修改的集合运算都在一个事务中完成
+            self.db().transaction(|tx| {
+                let set = self.bind(tx);
+                for m in members {
+                    set.add(m)?;
+                }
+                Ok(())
+            })
         }
 
-        pub(crate) fn delete_tuple_elements(&self, index: usize,tuple_index:u16) -> Result<bool, String> {
-            // 列表index和列表index对应的元组index
-            // 删除列表中的元组中的某个元素的值
-            self.list.delete(self.get_list_index(index,tuple_index))
+        pub fn difference_update(&self,members:&BTreeSet<Vec<u8>>) -> DbResult<()> {
+            // 移除所有成员(-=)
+            self.db().transaction(|tx| {
+                let set = self.bind(tx);
+                for m in members {
+                    set.remove(m)?;
+                }
+                Ok(())
+            })
         }
 
-        fn get_list_index(&self, index: usize,tuple_index:u16) -> usize {
-            // 列表index和列表index对应的元组index
-            index*(self.len as usize)+(tuple_index as usize)
+        pub fn intersection_update(&self,members:&BTreeSet<Vec<u8>>) -> DbResult<()> {
+            // 只保留 members 中也有的成员(&=)
+            self.db().transaction(|tx| {
+                let set = self.bind(tx);
+                for m in set.iter() {
+                    let m = m?;
+                    if !members.contains(&m) {
+                        set.remove(&m)?;
+                    }
+                }
+                Ok(())
+            })
         }
 
-        pub(crate) fn delete(&self, index: usize){
-            // 删除列表中的某个元组
-            for j in (index*(self.len as usize))..(index*(self.len as usize))+(self.len as usize) { // 收拾残局(注意当前覆写的残局已被 self.List.overwrite 收拾干净了,所以不需要再收拾一遍了)
-                let _ = &self.list.delete(j);
-            }
+        pub fn symmetric_difference_update(&self,members:&BTreeSet<Vec<u8>>) -> DbResult<()> {
+            // 已有的成员移除,没有的加入(^=)
+            self.db().transaction(|tx| {
+                let set = self.bind(tx);
+                for m in members {
+                    if !set.remove(m)? {
+                        set.add(m)?;
+                    }
+                }
+                Ok(())
+            })
         }
 
-        fn get_key(&self, index: usize) -> Vec<String> {
-            // 返回在数据库中的索引
-            let list_hard_index = index*(self.len as usize);
-            let mut ret = vec![];
-            for i in 0..self.len{
-                ret.push(self.list.get_key(list_hard_index+(i as usize)));
-            }
-            ret
+        pub fn union(&self,other:&Set) -> DbResult<Vec<Vec<u8>>> {
+            merge(self.iter(), other.iter(), |_| true)
+        }
+
+        pub fn intersection(&self,other:&Set) -> DbResult<Vec<Vec<u8>>> {
+            merge(self.iter(), other.iter(), |o| o == Ordering::Equal)
+        }
+
+        pub fn difference(&self,other:&Set) -> DbResult<Vec<Vec<u8>>> {
+            // 在 self 中而不在 other 中的成员
+            merge(self.iter(), other.iter(), |o| o == Ordering::Less)
         }
+    }
 
-        pub(crate) fn length(&self) -> Option<usize> {
-            match self.list.length() {
-                Some(t) => {
-                    Some(t/(self.len as usize))},
-                None => None,
+    fn merge(mut a:SetIter, mut b:SetIter, keep:fn(Ordering) -> bool) -> DbResult<Vec<Vec<u8>>> {
+        // 归并两个有序的成员序列,keep 根据成员来自哪边决定是否保留:
+        // Less 只在 a 中, Greater 只在 b 中, Equal 两边都有
+        let (mut x, mut y) = (a.next().transpose()?, b.next().transpose()?);
+        let mut out = Vec::new();
+        loop {
+            let order = match (&x, &y) {
+                (None, None) => return Ok(out),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(x), Some(y)) => x.cmp(y),
+            };
+            let member = match order {
+                Ordering::Less => std::mem::replace(&mut x, a.next().transpose()?),
+                Ordering::Greater => std::mem::replace(&mut y, b.next().transpose()?),
+                Ordering::Equal => {
+                    y = b.next().transpose()?;
+                    std::mem::replace(&mut x, a.next().transpose()?)
+                },
+            };
+            if keep(order) {
+                out.extend(member);
             }
         }
     }
+
     #[test]
-    fn test(){
-        use crate::sdk::db::kv_operation::KvDbOperaTrait;
-        let a = TupleList::open( // 如果这个元组列表不存在,需要先 new
-            KvDbOperaObject::new(initialization("/tmp/welcome-to-sled".to_string())), "156745qxxs23".to_string(), 2).unwrap();
-        dbg!(a.length().unwrap());
-        dbg!(&a.append(&vec![&"I love".to_string().as_bytes().to_vec(), &"XXXXXXXXXXXX".to_string().as_bytes().to_vec()]));
-        dbg!(a.length().unwrap());
-        dbg!(&a.access(a.length().unwrap()-1));
-        dbg!(&a.delete(a.length().unwrap()-1));
-        dbg!(a.length().unwrap());
-        dbg!(&a.access(a.length().unwrap()));
-        dbg!(&a.append(&vec![&"I love".to_string().as_bytes().to_vec(), &"She".to_string().as_bytes().to_vec()]));
-        dbg!(&a.overwrite(0,&vec![&"I love".to_string().as_bytes().to_vec(),& "He".to_string().as_bytes().to_vec()]));
-        dbg!(&a.access(a.length().unwrap()-1));
-        dbg!(&a.delete(a.length().unwrap()-1));
-        dbg!(a.length().unwrap());
+    fn test_set() {
+        use crate::sdk::db::kv_operation::{temporary, KvDbOperaTrait};
+        let db = KvDbOperaObject::new(temporary());
+        let a = Set::new(db.clone(), "a".to_string()).unwrap();
+        let b = Set::new(db.clone(), "b".to_string()).unwrap();
+        for m in ["x", "y", "z"] {
+            assert_eq!(a.add(m.as_bytes()), Ok(true));
+        }
+        assert_eq!(a.add(b"x"), Ok(false));
+        for m in ["w", "y"] {
+            b.add(m.as_bytes()).unwrap();
+        }
+        assert_eq!((a.len(), a.contains(b"y"), a.contains(b"w")), (Ok(3), Ok(true), Ok(false)));
+        let strs = |v: DbResult<Vec<Vec<u8>>>| v.unwrap().into_iter().map(|m| String::from_utf8(m).unwrap()).collect::<Vec<_>>();
+        assert_eq!(strs(a.union(&b)), ["w", "x", "y", "z"]);
+        assert_eq!(strs(a.intersection(&b)), ["y"]);
+        assert_eq!(strs(a.difference(&b)), ["x", "z"]);
+        assert_eq!(strs(b.difference(&a)), ["w"]);
+        assert_eq!(strs(a.iter().rev().collect()), ["z", "y", "x"]);
+        assert_eq!((a.remove(b"x"), a.remove(b"x"), a.len()), (Ok(true), Ok(false), Ok(2)));
+        let members = |v: &[&str]| v.iter().map(|m| m.as_bytes().to_vec()).collect::<BTreeSet<_>>();
+        a.update(&members(&["v", "w"])).unwrap();
+        a.symmetric_difference_update(&members(&["v", "x"])).unwrap();
+        assert_eq!(strs(a.iter().collect()), ["w", "x", "y", "z"]);
+        a.intersection_update(&members(&["w", "x", "y", "q"])).unwrap();
+        a.difference_update(&members(&["w"])).unwrap();
+        assert_eq!((strs(a.iter().collect()), a.len()), (vec!["x".to_string(), "y".to_string()], Ok(2)));
+        a.clear().unwrap();
+        assert_eq!((a.len(), a.iter().count()), (Ok(0), 0));
+        assert_eq!(a.pop_first(), Ok(None));
+
+        // 几个线程同时 pop,每个成员只被取出一次
+        for i in 0..200u32 {
+            a.add(&i.to_be_bytes()).unwrap();
+        }
+        let handles = (0..4).map(|_| {
+            let a = a.clone();
+            std::thread::spawn(move || std::iter::from_fn(|| a.pop_first().unwrap()).collect::<Vec<_>>())
+        }).collect::<Vec<_>>();
+        let popped = handles.into_iter().flat_map(|h| h.join().unwrap()).collect::<Vec<_>>();
+        assert_eq!((popped.len(), popped.iter().collect::<BTreeSet<_>>().len(), a.len()), (200, 200, Ok(0)));
+        // 集合和同名的有序映射互不相干
+        assert!(crate::sdk::db::sorted_map_db::SortedMap::open(db, "a".to_string()).is_err());
     }
 }
 
-pub mod hashtable_sled_db {
-    // 储存键列表 + 基于sled的哈希表
-    use crate::sdk::db::kv_operation::{initialization, KvDbOperaObject};
-    use crate::sdk::db::list_db::ListDb;
-    use crate::sdk::db::kv_operation::KvDbOperaTrait;
+pub mod deque_db {
+    // 双端队列,两端的 push 和 pop 都是 O(1)
+    // 元素储存为 [类型标记][名字][0x00 0x01][0x01][大端 u64 位置],
+    // 元数据的 extra 中记录 head(第一个元素的位置) 和 tail(最后一个元素的下一个位置),
+    // 位置从 2^63 开始向两边增长,所以 sled 中的顺序就是队列的顺序
+    // pop_front_timeout 用 sled 的 watch_prefix 订阅元数据的修改,在队列为空时等待新的元素
+    use std::time::{Duration, Instant};
+    use sled::IVec;
+    use crate::sdk::catalog;
+    use crate::sdk::db::key_codec;
+    use crate::sdk::db::kv_operation::{KvDbOperaObject, KvDbOperaTrait, KvIter};
+    use crate::sdk::db::metadata::{CollectionKind, Metadata};
+    use crate::sdk::error::{DbError, DbResult};
 
-    struct OriginalHashtable { // 原始hash表,只有键值对
-        db : KvDbOperaObject,
-        name : String,
+    const ORIGIN: u64 = 1 << 63;
+
+    #[derive(Debug)]
+    #[derive(Clone)]
+    pub struct Deque {
+        db: KvDbOperaObject,
+        name: String,
     }
 
-    struct Hashtable { // 原始hash表,只有键值对
-        hashtable: OriginalHashtable,
-        list : ListDb,
+    struct Ends {
+        meta: Metadata,
+        head: u64,
+        tail: u64,
     }
 
-    impl OriginalHashtable {
-        fn new(db:KvDbOperaObject , name : String) -> Self {
-            OriginalHashtable { db,name }
+    pub struct DequeIter(KvIter); // 从队头到队尾迭代,可以 rev()
+
+    impl Iterator for DequeIter {
+        type Item = DbResult<Vec<u8>>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            Some(self.0.next()?.map(|(_, v)| v.to_vec()))
+        }
+    }
+
+    impl DoubleEndedIterator for DequeIter {
+        fn next_back(&mut self) -> Option<Self::Item> {
+            Some(self.0.next_back()?.map(|(_, v)| v.to_vec()))
         }
+    }
 
-        fn insert(&self,key:&String,value:Vec<u8>) -> Result<bool,String> {
-            self.db.insert(self.get_key(key),value)
+    impl Deque {
+        pub fn new(db:KvDbOperaObject,name:String) -> DbResult<Self> {
+            // 打开一个队列,不存在时创建
+            let deque = Deque { db, name };
+            deque.db.transaction(|tx| {
+                let d = deque.bind(tx);
+                if tx.get(d.meta_key())?.is_none() {
+                    let meta = Metadata::new(CollectionKind::Deque, 0);
+                    d.write_ends(Ends { meta, head: ORIGIN, tail: ORIGIN })?;
+                    catalog::register(tx, CollectionKind::Deque, &d.name)?;
+                }
+                Ok(())
+            })?;
+            Ok(deque)
         }
 
-        fn get(&self,key:&String) -> Result<Option<Vec<u8>>,String> {
-            match self.db.get(self.get_key(key)) {
-                Ok(t) => Ok(match t {
-                    Some(t1) => Some(t1.to_vec()),
-                    _ => None
-                }),
-                Err(e) => Err(e)
+        pub fn open(db:KvDbOperaObject,name:String) -> DbResult<Self> {
+            // 打开一个队列,不存在时返回 NotFound
+            let deque = Deque { db, name };
+            deque.ends()?;
+            Ok(deque)
+        }
+
+        pub fn bind(&self, db:&KvDbOperaObject) -> Self {
+            Deque { db: db.clone(), name: self.name.clone() }
+        }
+
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn prefix(&self) -> Vec<u8> {
+            key_codec::prefix(key_codec::kind_tag(CollectionKind::Deque), &self.name)
+        }
+
+        fn meta_key(&self) -> Vec<u8> {
+            key_codec::meta_key(&self.prefix())
+        }
+
+        fn get_key(&self, pos:u64) -> Vec<u8> {
+            key_codec::element_key(&self.prefix(), pos)
+        }
+
+        fn ends(&self) -> DbResult<Ends> {
+            let v = self.db.get(self.meta_key())?.ok_or_else(|| DbError::NotFound(format!("Deque {}", self.name)))?;
+            let meta = Metadata::decode(&v)?.current()?;
+            if meta.kind != CollectionKind::Deque || meta.extra.len() != 16 {
+                return Err(DbError::CorruptMetadata(format!("{} is not a deque", self.name)))
             }
+            let head = u64::from_be_bytes(meta.extra[..8].try_into().unwrap());
+            let tail = u64::from_be_bytes(meta.extra[8..].try_into().unwrap());
+            if head > tail {
+                return Err(DbError::CorruptMetadata(format!("deque {}: head {head} > tail {tail}", self.name)))
+            }
+            Ok(Ends { meta, head, tail })
         }
 
-        fn delete(&self,key:&String) {
-            let _ = self.db.delete(self.get_key(key));
+        fn write_ends(&self, mut ends:Ends) -> DbResult<()> {
+            ends.meta.count = ends.tail - ends.head;
+            ends.meta.length = ends.meta.count;
+            ends.meta.extra = [ends.head.to_be_bytes(), ends.tail.to_be_bytes()].concat();
+            self.db.insert(self.meta_key(), ends.meta.encode())
         }
 
-        fn get_key(&self,key:&String) -> String {
-            format!("key-value:{}:{}",self.name,&key)
+        pub fn len(&self) -> DbResult<usize> {
+            let ends = self.ends()?;
+            Ok((ends.tail - ends.head) as usize)
         }
 
-        fn upgrade_to_iterable(self,list:ListDb) -> Hashtable {
-            Hashtable { hashtable: self, list }
+        pub fn is_empty(&self) -> DbResult<bool> {
+            Ok(self.len()? == 0)
         }
-    }
 
+        pub fn push_back(&self, value:&[u8]) -> DbResult<()> {
+            self.db.transaction(|tx| {
+                let d = self.bind(tx);
+                let mut ends = d.ends()?;
+                tx.insert(d.get_key(ends.tail), value)?;
+                ends.tail += 1;
+                d.write_ends(ends)
+            })
+        }
 
+        pub fn push_front(&self, value:&[u8]) -> DbResult<()> {
+            self.db.transaction(|tx| {
+                let d = self.bind(tx);
+                let mut ends = d.ends()?;
+                ends.head -= 1;
+                tx.insert(d.get_key(ends.head), value)?;
+                d.write_ends(ends)
+            })
+        }
 
-    // impl Hashtable {
-    //     fn insert(&self,key:&String,value:Vec<u8>) -> Result<bool,String> {
-    //         self.hashtable.insert(key,value)
-    //     }
-    //
-    //     fn get(&self,key:&String) -> Result<Option<Vec<u8>>,String> {
-    //         match self.db.get(self.get_key(key)) {
-    //             Ok(t) => Ok(match t {
-    //                 Some(t1) => Some(t1.to_vec()),
-    //                 _ => None
-    //             }),
-    //             Err(e) => Err(e)
-    //         }
-    //     }
-    //
-    //     fn delete(&self,key:&String) {
-    //         if let _ = self.db.delete(self.get_key(key)) {}
-    //     }
-    //
-    //     fn get_key(&self,key:&String) -> String {
-    //         format!("key-value:{}:{}",self.name,&key)
-    //     }
-    // }
+        fn value_at(&self, pos:u64) -> DbResult<IVec> {
+            self.db.get(self.get_key(pos))?
+                .ok_or_else(|| DbError::CorruptMetadata(format!("deque {}: missing element {pos}", self.name)))
+        }
 
-    #[test]
-    fn test_original_hashtable () {
-        let a = OriginalHashtable::new(KvDbOperaObject::new(initialization("/tmp/welcome-to-sled".to_string())),"823789792".to_string());
-        dbg!(&a.insert(&"heheh".to_string(), vec![1, 1, 4, 5, 1, 4]));
-        dbg!(&a.get(&"heheh".to_string()));
-        dbg!(&a.delete(&"heheh".to_string()));
-        dbg!(&a.get(&"heheh".to_string()));
+        pub fn pop_front(&self) -> DbResult<Option<Vec<u8>>> {
+            // 队列为空时返回 None
+            self.db.transaction(|tx| {
+                let d = self.bind(tx);
+                let mut ends = d.ends()?;
+                if ends.head == ends.tail {
+                    return Ok(None)
+                }
+                let value = d.value_at(ends.head)?;
+                tx.delete(d.get_key(ends.head))?;
+                ends.head += 1;
+                d.write_ends(ends)?;
+                Ok(Some(value.to_vec()))
+            })
+        }
+
+        pub fn pop_back(&self) -> DbResult<Option<Vec<u8>>> {
+            self.db.transaction(|tx| {
+                let d = self.bind(tx);
+                let mut ends = d.ends()?;
+                if ends.head == ends.tail {
+                    return Ok(None)
+                }
+                ends.tail -= 1;
+                let value = d.value_at(ends.tail)?;
+                tx.delete(d.get_key(ends.tail))?;
+                d.write_ends(ends)?;
+                Ok(Some(value.to_vec()))
+            })
+        }
+
+        pub fn peek_front(&self) -> DbResult<Option<Vec<u8>>> {
+            // 队头和队头的元素在同一个事务中读取,不会被同时进行的 pop 打断
+            self.db.transaction(|tx| {
+                let d = self.bind(tx);
+                let ends = d.ends()?;
+                if ends.head == ends.tail {
+                    return Ok(None)
+                }
+                Ok(Some(d.value_at(ends.head)?.to_vec()))
+            })
+        }
+
+        pub fn peek_back(&self) -> DbResult<Option<Vec<u8>>> {
+            self.db.transaction(|tx| {
+                let d = self.bind(tx);
+                let ends = d.ends()?;
+                if ends.head == ends.tail {
+                    return Ok(None)
+                }
+                Ok(Some(d.value_at(ends.tail - 1)?.to_vec()))
+            })
+        }
+
+        pub fn pop_front_timeout(&self, timeout:Option<Duration>) -> DbResult<Option<Vec<u8>>> {
+            // 从队头取出一个元素,队列为空时等待其他人 push,直到超时(None 为一直等待)
+            // 在事务中看不到其他人新提交的元素,所以不会等待
+            if self.db.in_transaction() {
+                return self.pop_front()
+            }
+            let deadline = timeout.map(|t| Instant::now() + t);
+            loop {
+                // 先订阅再检查,检查之后的 push 一定会被订阅收到
+                let mut subscriber = self.db.watch_prefix(self.meta_key())?;
+                if let Some(value) = self.pop_front()? {
+                    return Ok(Some(value))
+                }
+                match deadline {
+                    None => { subscriber.next(); },
+                    Some(deadline) => {
+                        let now = Instant::now();
+                        if now >= deadline || subscriber.next_timeout(deadline - now).is_err() {
+                            return self.pop_front() // 超时前的最后一次检查
+                        }
+                    }
+                }
+            }
+        }
+
+        pub fn iter(&self) -> DbResult<DequeIter> {
+            // 从队头到队尾迭代,在事务中时能看到事务自己的写入
+            let ends = self.ends()?;
+            Ok(DequeIter(self.db.range(self.get_key(ends.head)..self.get_key(ends.tail))))
+        }
     }
 
+    #[test]
+    fn test_deque() {
+        use crate::sdk::db::kv_operation::temporary;
+        let db = KvDbOperaObject::new(temporary());
+        let d = Deque::new(db.clone(), "q".to_string()).unwrap();
+        assert_eq!((d.pop_front(), d.pop_back(), d.peek_front()), (Ok(None), Ok(None), Ok(None)));
+        d.push_back(b"b").unwrap();
+        d.push_back(b"c").unwrap();
+        d.push_front(b"a").unwrap();
+        let all = |d: &Deque| d.iter().unwrap().collect::<DbResult<Vec<_>>>().unwrap();
+        assert_eq!(all(&d), [b"a", b"b", b"c"]);
+        assert_eq!((d.len(), d.peek_front(), d.peek_back()), (Ok(3), Ok(Some(b"a".to_vec())), Ok(Some(b"c".to_vec()))));
+        assert_eq!(d.pop_back(), Ok(Some(b"c".to_vec())));
+        assert_eq!(d.pop_front(), Ok(Some(b"a".to_vec())));
+        assert_eq!(Deque::open(db.clone(), "q".to_string()).unwrap().len(), Ok(1));
+        assert!(Deque::open(db.clone(), "nope".to_string()).is_err());
+        assert_eq!(d.pop_front_timeout(Some(Duration::from_millis(10))), Ok(Some(b"b".to_vec())));
+        assert_eq!(d.pop_front_timeout(Some(Duration::from_millis(10))), Ok(None));
+
+        // 另一个线程稍后 push,阻塞的 pop 被唤醒
+        let producer = d.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            producer.push_back(b"job").unwrap();
+        });
+        assert_eq!(d.pop_front_timeout(None), Ok(Some(b"job".to_vec())));
+        handle.join().unwrap();
+        assert_eq!(d.is_empty(), Ok(true));
+
+        // peek 和同时进行的 pop 不会读到被取走的元素
+        let worker = d.clone();
+        let handle = std::thread::spawn(move || {
+            for _ in 0..3000 {
+                worker.push_back(b"x").unwrap();
+                worker.pop_front().unwrap();
+            }
+        });
+        while !handle.is_finished() {
+            assert!(d.peek_front().is_ok() && d.peek_back().is_ok());
+        }
+        handle.join().unwrap();
+    }
 }
 
 pub mod hashtable_zipper_db {
     // 拉链法哈希表
+    // 使用线性哈希(linear hashing)扩容: 桶的个数为 initial * 2^level + split,
+    // 条目个数超过 负载因子 * 桶的个数 时分裂 split 指向的桶,每次插入最多分裂一个桶,插入/查询/删除都是摊还 O(1)
     use crate::sdk::db::list_db::ListDb;
-    use crate::sdk::db::kv_operation::{initialization, KvDbOperaObject};
-    use std::hash::{Hash, Hasher};
+    use crate::sdk::db::metadata::{CollectionKind, Metadata};
+    use crate::sdk::db::kv_operation::{KvDbOperaObject, KvDbOperaTrait};
+    use std::hash::Hasher;
     use std::collections::hash_map::DefaultHasher;
+    use siphasher::sip::SipHasher13;
     use crate::sdk::db::tuple_list_db::TupleList;
+    use crate::sdk::error::{DbError, DbResult};
+
+    const DEFAULT_INITIAL_BUCKETS: u64 = 16;
+    const DEFAULT_LOAD_FACTOR: u32 = 200; // 百分比,平均每个桶 2 个条目
+    const LEGACY_BUCKETS: u64 = 10000; // 旧版本的哈希表固定为 10000 个桶,相当于没有分裂过的线性哈希
+    const BUCKET_MARKER: &[u8] = &[0xFF]; // hashlist 中以桶号命名的拉链,旧版本储存的是第一个键(合法的 UTF-8,不会是 0xFF)
+    const PARAMS_LEN: usize = 33;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum HashAlgorithm {
+        StdDefault = 0, // 旧版本使用的 DefaultHasher,标准库不保证它在不同的 Rust 版本中一致,只用于读取旧的哈希表
+        SipHash13 = 1, // SipHash-1-3,密钥为 (0, 0),输入为键的字节,结果为 u64
+    }
+
+    impl HashAlgorithm {
+        pub fn hash(self, key:&[u8]) -> u64 {
+            match self {
+                HashAlgorithm::StdDefault => {
+                    // 和旧版本对 &str 的哈希一致: 字节之后再写入一个 0xFF
+                    let mut hasher = DefaultHasher::new();
+                    hasher.write(key);
+                    hasher.write_u8(0xFF);
+                    hasher.finish()
+                }
+                HashAlgorithm::SipHash13 => {
+                    let mut hasher = SipHasher13::new_with_keys(0, 0);
+                    hasher.write(key);
+                    hasher.finish()
+                }
+            }
+        }
+
+        fn from_u8(v: u8) -> Option<Self> {
+            match v {
+                0 => Some(HashAlgorithm::StdDefault),
+                1 => Some(HashAlgorithm::SipHash13),
+                _ => None,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TableParams {
+        // 储存在哈希表元数据的 extra 中
+        // 二进制格式(大端): [条目个数 u64][初始桶个数 u64][level u32][split u64][负载因子 u32][哈希函数 u8]
+        pub count: u64, // 条目个数
+        pub initial: u64, // 初始桶的个数
+        pub level: u32, // 桶的个数翻倍的次数
+        pub split: u64, // 下一个要分裂的桶
+        pub load_factor: u32, // 负载因子(百分比)
+        pub hasher: HashAlgorithm, // 决定键放在哪个桶的哈希函数
+    }
+
+    impl TableParams {
+        fn new() -> Self {
+            TableParams { count: 0, initial: DEFAULT_INITIAL_BUCKETS, level: 0, split: 0, load_factor: DEFAULT_LOAD_FACTOR, hasher: HashAlgorithm::SipHash13 }
+        }
+
+        fn legacy(count: u64) -> Self {
+            TableParams { count, initial: LEGACY_BUCKETS, hasher: HashAlgorithm::StdDefault, ..Self::new() }
+        }
+
+        fn encode(&self) -> Vec<u8> {
+            let mut out = Vec::with_capacity(PARAMS_LEN);
+            out.extend_from_slice(&self.count.to_be_bytes());
+            out.extend_from_slice(&self.initial.to_be_bytes());
+            out.extend_from_slice(&self.level.to_be_bytes());
+            out.extend_from_slice(&self.split.to_be_bytes());
+            out.extend_from_slice(&self.load_factor.to_be_bytes());
+            out.push(self.hasher as u8);
+            out
+        }
+
+        fn decode(extra: &[u8]) -> DbResult<Option<Self>> {
+            // 旧版本的哈希表没有参数(extra 为空),返回 None
+            match extra.len() {
+                0 => return Ok(None),
+                PARAMS_LEN => {},
+                n => return Err(DbError::CorruptMetadata(format!("hashtable params of {n} bytes"))),
+            }
+            let hasher = HashAlgorithm::from_u8(extra[PARAMS_LEN - 1])
+                .ok_or_else(|| DbError::CorruptMetadata(format!("unknown hash algorithm {}", extra[PARAMS_LEN - 1])))?;
+            let u64_at = |i: usize| u64::from_be_bytes(extra[i..i + 8].try_into().unwrap_or_default());
+            let u32_at = |i: usize| u32::from_be_bytes(extra[i..i + 4].try_into().unwrap_or_default());
+            Ok(Some(TableParams { count: u64_at(0), initial: u64_at(8), level: u32_at(16), split: u64_at(20), load_factor: u32_at(28), hasher }))
+        }
+
+        pub fn buckets(&self) -> u64 {
+            (self.initial << self.level) + self.split
+        }
+
+        fn bucket(&self, hash: u64) -> u64 {
+            // split 之前的桶已经分裂,使用下一轮的桶个数
+            let n = self.initial << self.level;
+            match hash % n {
+                b if b < self.split => hash % (n * 2),
+                b => b,
+            }
+        }
+
+        fn bucket_of(&self, key: &[u8]) -> u64 {
+            self.bucket(self.hasher.hash(key))
+        }
+
+        fn overloaded(&self) -> bool {
+            self.count * 100 > self.buckets() * self.load_factor as u64
+        }
+    }
 
     #[derive(Debug)]
     #[derive(Clone)]
@@ -547,139 +3126,496 @@ pub mod hashtable_zipper_db {
         hashlist : ListDb,
     }
 
+    pub type HashtableIter = Box<dyn DoubleEndedIterator<Item = DbResult<(Vec<u8>, Vec<u8>)>> + Send>; // 条目迭代器
+
     impl Hashtable {
-        pub fn new(db:KvDbOperaObject,name:String) -> Self {
-            Hashtable { db: db.clone() , hashlist : ListDb::new(db.clone(),format!("HashtableHashlist:{name}")).unwrap() } // 创建list对象
+        pub fn new(db:KvDbOperaObject,name:String) -> DbResult<Self> {
+            let table = Hashtable { db: db.clone() , hashlist : ListDb::new_with_kind(db,name,CollectionKind::Hashtable,0)? }; // 创建list对象
+            table.db.transaction(|tx| {
+                // 新建的哈希表写入线性哈希的参数,没有参数但已经有桶的是旧版本的哈希表
+                let hashlist = table.hashlist.bind(tx);
+                let meta = hashlist.metadata()?;
+                if meta.extra.is_empty() && meta.length == 0 {
+                    table.bind(tx).write_params(meta, &TableParams::new())?;
+                }
+                Ok(())
+            })?;
+            Ok(table)
         }
 
-        pub fn open(db:KvDbOperaObject,name:String) -> Self {
-            Hashtable { db: db.clone() , hashlist : ListDb::open(db.clone(),format!("HashtableHashlist:{name}")).unwrap() } // 创建list对象
+        pub fn open(db:KvDbOperaObject,name:String) -> DbResult<Self> {
+            Ok(Hashtable { db: db.clone() , hashlist : ListDb::open_with_kind(db,name,CollectionKind::Hashtable)? }) // 创建list对象
         }
 
-        pub fn insert(&self,key:&String,value:&Vec<u8>) -> Result<bool,String> {
-            let hash_value = self.get_hash(key);
-            return match self.hashlist.access(hash_value) {
-                Some(lzip_name) => { // 此情况为hash碰撞的情况
-                    let lzip = self.new_lzip(lzip_name);
-                    // 此元组列表为 [(key,value)]
-                    for i in 0..lzip.length().unwrap() {
-                        if &String::from_utf8(lzip.access(i).unwrap()[0].clone().unwrap()).unwrap() == key {
-                            return lzip.overwrite(i, &vec![&key.as_bytes().to_vec(), value])
-                        }
-                    } // 判断有没有已经存在的键,如果存在,就直接改
-                    lzip.append(&vec![&key.as_bytes().to_vec(), value]) // 如果不存在,直接追加
-                }
-                None => { // 没有碰撞
-                    if (hash_value + 1) > self.hashlist.length().unwrap() { // 当前键大于列表长度
-                        self.hashlist.change_length(hash_value + 1).unwrap(); // 更改列表长度,以用于下面的覆写
+        pub fn bind(&self, db:&KvDbOperaObject) -> Self {
+            // 换一个数据库对象(比如事务)来操作这个哈希表
+            Hashtable { db: db.clone(), hashlist: self.hashlist.bind(db) }
+        }
+
+        fn layout(&self) -> DbResult<TableParams> {
+            // 只用于定位桶,旧版本的哈希表不统计条目个数
+            let meta = self.hashlist.metadata()?;
+            Ok(TableParams::decode(&meta.extra)?.unwrap_or_else(|| TableParams::legacy(0)))
+        }
+
+        pub fn params(&self) -> DbResult<TableParams> {
+            // 旧版本的哈希表没有参数,第一次读取时统计条目个数并写入
+            let meta = self.hashlist.metadata()?;
+            if let Some(params) = TableParams::decode(&meta.extra)? {
+                return Ok(params)
+            }
+            let params = TableParams::legacy(self.iter()?.count() as u64);
+            self.write_params(meta, &params)?;
+            Ok(params)
+        }
+
+        fn write_params(&self, mut meta: Metadata, params: &TableParams) -> DbResult<()> {
+            meta.extra = params.encode();
+            self.hashlist.write_metadata(&meta)
+        }
+
+        fn save_params(&self, params: &TableParams) -> DbResult<()> {
+            self.write_params(self.hashlist.metadata()?, params)
+        }
+
+        pub fn set_load_factor(&self, percent:u32) -> DbResult<()> {
+            // 负载因子为平均每个桶的条目个数的百分比,调小会让之后的插入更快地分裂桶
+            self.db.transaction(|tx| {
+                let table = self.bind(tx);
+                let mut params = table.params()?;
+                params.load_factor = percent.max(1);
+                table.save_params(&params)
+            })
+        }
+
+        fn find_lzip(&self,bucket:u64) -> DbResult<Option<TupleList>> {
+            // 找到桶对应的拉链,不存在则返回 None
+            let index = bucket as usize;
+            if index >= self.hashlist.length()? {
+                return Ok(None)
+            }
+            match self.hashlist.access(index)? {
+                Some(lzip_name) => Ok(Some(self.lzip(index, lzip_name)?)),
+                None => Ok(None),
+            }
+        }
+
+        fn lzip(&self,bucket:usize,lzip_name:Vec<u8>) -> DbResult<TupleList> {
+            if lzip_name == BUCKET_MARKER {
+                return TupleList::new_with_kind(self.db.clone(), format!("{}:{}",self.hashlist.name,bucket), 2, CollectionKind::HashtableBucket)
+            }
+            self.new_lzip(lzip_name) // 旧版本以第一个键命名的拉链
+        }
+
+        fn open_lzip(&self,bucket:usize,lzip_name:Vec<u8>) -> DbResult<TupleList> {
+            // 和 lzip 一样,但是拉链不存在时返回 NotFound 而不是创建,只读的查找使用
+            if lzip_name == BUCKET_MARKER {
+                return TupleList::open_with_kind(self.db.clone(), format!("{}:{}",self.hashlist.name,bucket), 2, CollectionKind::HashtableBucket)
+            }
+            let name = String::from_utf8(lzip_name).map_err(|e| DbError::CorruptMetadata(format!("Hashtable chain name: {e}")))?;
+            TupleList::open_with_kind(self.db.clone(), format!("{}:{}",self.hashlist.name,name), 2, CollectionKind::HashtableChain)
+        }
+
+        pub(crate) fn chains(&self) -> DbResult<Vec<(CollectionKind,String)>> {
+            // 所有拉链的种类和名字(和 lzip 中的命名方式一致),目录删除或改名哈希表时用到
+            let mut ret = vec![];
+            for bucket in 0..self.hashlist.length()? {
+                match self.hashlist.access(bucket)? {
+                    Some(name) if name == BUCKET_MARKER => ret.push((CollectionKind::HashtableBucket, format!("{}:{}",self.hashlist.name,bucket))),
+                    Some(name) => {
+                        let name = String::from_utf8(name).map_err(|e| DbError::CorruptMetadata(format!("Hashtable chain name: {e}")))?;
+                        ret.push((CollectionKind::HashtableChain, format!("{}:{}",self.hashlist.name,name)))
                     }
-                    self.hashlist.overwrite(hash_value, &key.as_bytes().to_vec()).unwrap(); // 覆写为lzip的名字
+                    None => {}
+                }
+            }
+            Ok(ret)
+        }
+
+        fn new_bucket(&self,bucket:u64) -> DbResult<TupleList> {
+            // 创建以桶号命名的拉链,当前桶大于列表长度时会扩充列表
+            self.hashlist.safety_overwrite(bucket as usize, BUCKET_MARKER)?;
+            self.lzip(bucket as usize, BUCKET_MARKER.to_vec())
+        }
+
+        fn release_bucket(&self,bucket:u64,lzip:&TupleList) -> DbResult<()> {
+            // 回收拉链,桶变为空
+            lzip.destroy()?;
+            self.hashlist.remove_element(bucket as usize)
+        }
 
-                    let lzip = self.new_lzip(key.as_bytes().to_vec());
-                    lzip.append(&vec![&key.as_bytes().to_vec(), value]) // 追加以完成写入
+        fn find_in_lzip(lzip:&TupleList,key:&[u8]) -> DbResult<Option<(usize,Vec<u8>)>> {
+            // 在拉链中查找键,返回其在拉链中的index和值
+            // 此元组列表为 [(key,value)]
+            for i in 0..lzip.length()? {
+                let entry = lzip.access(i)?;
+                if entry[0].as_deref() == Some(key) {
+                    return Ok(Some((i, entry[1].clone().unwrap_or_default())))
                 }
-            };
+            }
+            Ok(None)
         }
-        pub fn get(&self,key:&String) -> Option<Vec<u8>> {
-            let hash_value = self.get_hash(key);
-            return match self.hashlist.access(hash_value) {
-                Some(lzip_name) => {
-                    let lzip = self.new_lzip(lzip_name);
-                    // 此元组列表为 [(key,value)]
-                    for i in 0..lzip.length().unwrap() { // 处理可能的哈希碰撞
-                        if &String::from_utf8(lzip.access(i).unwrap()[0].clone().unwrap()).unwrap() == key {
-                            return Some(lzip.access(i).unwrap()[1].clone().unwrap().clone()) // 提取出value
-                        }
-                    } // 判断有没有已经存在的键
-                    None
-                },
-                None =>  None ,
+
+        fn lzip_entries(lzip:&TupleList) -> DbResult<Vec<(Vec<u8>,Vec<u8>)>> {
+            // 逐个读取拉链中的条目(能看到事务中尚未提交的写入),跳过已被删除的条目
+            let mut ret = vec![];
+            for i in 0..lzip.length()? {
+                if let [Some(key), Some(value)] = &lzip.access(i)?[..] {
+                    ret.push((key.clone(), value.clone()));
+                }
             }
+            Ok(ret)
+        }
 
+        fn split(&self, params:&mut TableParams) -> DbResult<()> {
+            // 分裂 split 指向的桶,其中的条目重新分配到原来的桶和 split + initial * 2^level 这个新桶
+            let bucket = params.split;
+            let old = self.find_lzip(bucket)?;
+            params.split += 1;
+            if params.split == params.initial << params.level { // 这一轮所有的桶都已分裂
+                params.level += 1;
+                params.split = 0;
+            }
+            let Some(old) = old else { return Ok(()) };
+            let entries = Self::lzip_entries(&old)?;
+            self.release_bucket(bucket, &old)?; // 旧版本的拉链也在这里换成以桶号命名的拉链
+            for (key, value) in entries {
+                let target = params.bucket_of(&key);
+                let lzip = match self.find_lzip(target)? {
+                    Some(lzip) => lzip,
+                    None => self.new_bucket(target)?,
+                };
+                lzip.append(&[&key, &value])?;
+            }
+            Ok(())
         }
 
-        pub fn delete(&self,key:&String) -> Result<bool,String> {
-            let hash_value = self.get_hash(key);
-            return match self.hashlist.access(hash_value) {
-                Some(lzip_name) => {
-                    let lzip = self.new_lzip(lzip_name);
-                    // 此元组列表为 [(key,value)]
-                    for i in 0..lzip.length().unwrap() { // 处理可能的哈希碰撞
-                        if &String::from_utf8(lzip.access(i).unwrap()[0].clone().unwrap()).unwrap() == key {
-                            lzip.delete(i);
-                            return Ok(true)
-                        }
-                    } // 判断有没有已经存在的键
-                    Err("Key does not exist".to_string())
-                },
-                None =>  Err("Key does not exist".to_string()) ,
+        pub fn insert(&self,key:&[u8],value:&[u8]) -> DbResult<()> {
+            self.db.transaction(|tx| {
+                let table = self.bind(tx);
+                let mut params = table.params()?;
+                let bucket = params.bucket_of(key);
+                let lzip = match table.find_lzip(bucket)? {
+                    Some(lzip) => lzip, // 此情况为hash碰撞的情况
+                    None => table.new_bucket(bucket)?, // 没有碰撞
+                };
+                if let Some((i, _)) = Self::find_in_lzip(&lzip, key)? { // 判断有没有已经存在的键,如果存在,就直接改
+                    return lzip.overwrite(i, &[key, value])
+                }
+                lzip.append(&[key, value])?; // 如果不存在,直接追加
+                params.count += 1;
+                if params.overloaded() {
+                    table.split(&mut params)?;
+                }
+                table.save_params(&params)
+            })
+        }
+        pub fn get(&self,key:&[u8]) -> DbResult<Option<Vec<u8>>> {
+            // 不开事务: 插入、删除和分裂都会改写哈希表的元数据(条目个数和桶的布局),
+            // 查找前后元数据没有变化,说明桶的位置和拉链的内容来自同一个版本;有变化时重试
+            // 已经在事务中时直接查找
+            if self.db.in_transaction() {
+                return self.lookup(key)
+            }
+            let meta_key = self.hashlist.meta_key();
+            loop {
+                let before = self.db.get(&meta_key)?;
+                let ret = self.lookup(key);
+                if self.db.get(&meta_key)? == before {
+                    return ret
+                }
             }
         }
-        pub fn to_tuple_list(&self, number_of_entries:Option<usize>) -> Vec<(String, Vec<u8>)> {
-            // 时间复杂度极高,慎用!这是把哈希表所有内容提取出来的方法之一
-            // 算法过程 : 迭代散列的所有元素,也就是迭代 self.hashlist,将其中的元组列表的数据挨个提取出来
-            // number_of_entries 为条目数量
-            let mut ret = vec![];
-            match number_of_entries {
-                Some(t) => {
-                    let mut number_of_entries_i = 0usize;
-                    for i in 0..self.hashlist.length().unwrap() {
-                        match self.hashlist.access(i) {
-                            Some(lzip_name) => {
-                                let lzip = self.new_lzip(lzip_name);
-                                for i in 0..lzip.length().unwrap(){
-                                    let value = lzip.access(i).unwrap();
-                                    ret.push((String::from_utf8(value[0].clone().unwrap().clone()).unwrap(),value[1].clone().unwrap().clone()));
-                                    if number_of_entries_i >= t {
-                                        return ret
-                                    } else {
-                                        number_of_entries_i += 1;
-                                    }
-                                }
-                            },
-                            _ => {},
+
+        fn lookup(&self,key:&[u8]) -> DbResult<Option<Vec<u8>>> {
+            // 只读的查找,不会创建拉链
+            let bucket = self.layout()?.bucket_of(key) as usize;
+            if bucket >= self.hashlist.length()? {
+                return Ok(None)
+            }
+            match self.hashlist.access(bucket)? {
+                Some(lzip_name) => Ok(Self::find_in_lzip(&self.open_lzip(bucket, lzip_name)?, key)?.map(|(_, value)| value)), // 处理可能的哈希碰撞,提取出value
+                None => Ok(None),
+            }
+        }
+
+        pub fn delete(&self,key:&[u8]) -> DbResult<()> {
+            self.db.transaction(|tx| {
+                let table = self.bind(tx);
+                let mut params = table.params()?;
+                let bucket = params.bucket_of(key);
+                if let Some(lzip) = table.find_lzip(bucket)? {
+                    if let Some((i, _)) = Self::find_in_lzip(&lzip, key)? { // 处理可能的哈希碰撞
+                        params.count = params.count.saturating_sub(1);
+                        table.save_params(&params)?;
+                        lzip.swap_remove(i)?; // 用最后一个条目填补,拉链中不留空位
+                        if lzip.length()? == 0 { // 回收空的拉链
+                            table.release_bucket(bucket, &lzip)?;
                         }
+                        return Ok(())
                     }
                 }
-                None => {
-                    for i in 0..self.hashlist.length().unwrap() {
-                        match self.hashlist.access(i) {
-                            Some(lzip_name) => {
-                                let lzip = self.new_lzip(lzip_name);
-                                for i in 0..lzip.length().unwrap(){
-                                    let value = lzip.access(i).unwrap();
-                                    ret.push((String::from_utf8(value[0].clone().unwrap().clone()).unwrap(),value[1].clone().unwrap().clone()));
-                                }
-                            },
-                            _ => {},
-                        }
+                Err(DbError::NotFound(format!("Key {}", String::from_utf8_lossy(key))))
+            })
+        }
+
+        pub fn insert_many<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, items:&[(K, V)]) -> DbResult<()> {
+            // 批量插入,所有条目在一个事务中写入(提交时是一个 sled::Batch)
+            self.db.transaction(|tx| {
+                let table = self.bind(tx);
+                for (key, value) in items {
+                    table.insert(key.as_ref(), value.as_ref())?;
+                }
+                Ok(())
+            })
+        }
+
+        pub fn get_many<K: AsRef<[u8]>>(&self, keys:&[K]) -> DbResult<Vec<Option<Vec<u8>>>> {
+            // 批量读取,所有键在一个事务中读取,结果来自同一个版本
+            self.db.transaction(|tx| {
+                let table = self.bind(tx);
+                keys.iter().map(|key| table.lookup(key.as_ref())).collect()
+            })
+        }
+
+        pub fn delete_many<K: AsRef<[u8]>>(&self, keys:&[K]) -> DbResult<usize> {
+            // 批量删除,跳过不存在的键,返回删除的条目个数
+            self.db.transaction(|tx| {
+                let table = self.bind(tx);
+                let mut deleted = 0;
+                for key in keys {
+                    match table.delete(key.as_ref()) {
+                        Ok(()) => deleted += 1,
+                        Err(DbError::NotFound(_)) => {}, // 没有写入任何东西,可以继续
+                        Err(e) => return Err(e),
                     }
-                    return ret
                 }
+                Ok(deleted)
+            })
+        }
+        pub fn to_tuple_list(&self, number_of_entries:Option<usize>) -> DbResult<Vec<(Vec<u8>, Vec<u8>)>> {
+            // 把哈希表所有内容提取出来,number_of_entries 为条目数量
+            self.iter()?.take(number_of_entries.unwrap_or(usize::MAX)).collect()
+        }
+
+        pub fn iter(&self) -> DbResult<HashtableIter> {
+            // 按桶的顺序迭代所有条目: 先扫描 self.hashlist,再扫描其中的每一个拉链
+            let table = self.clone();
+            let iter = self.hashlist.iter()?.flat_map(move |lzip_name| -> HashtableIter {
+                match lzip_name.and_then(|(bucket, name)| table.lzip(bucket, name)).and_then(|lzip| lzip.iter()) {
+                    Ok(lzip) => Box::new(lzip.filter_map(Self::entry)),
+                    Err(e) => Box::new(std::iter::once(Err(e))),
+                }
+            });
+            Ok(Box::new(iter))
+        }
+
+        fn entry(item: DbResult<(usize, Vec<Option<Vec<u8>>>)>) -> Option<DbResult<(Vec<u8>, Vec<u8>)>> {
+            // 拉链中的一个元组转化为条目,跳过已被删除的条目
+            match item {
+                Ok((_, tuple)) => match <[Option<Vec<u8>>; 2]>::try_from(tuple) {
+                    Ok([Some(key), Some(value)]) => Some(Ok((key, value))),
+                    _ => None,
+                },
+                Err(e) => Some(Err(e)),
             }
-            ret
         }
 
-        fn get_hash(&self,key:&String) -> usize {
-            let mut hasher = DefaultHasher::new();key.hash(& mut hasher );
-            (hasher.finish() % 10000) as usize
+        pub fn keys(&self) -> DbResult<impl DoubleEndedIterator<Item = DbResult<Vec<u8>>>> {
+            Ok(self.iter()?.map(|r| r.map(|(key, _)| key)))
+        }
+
+        pub fn contains_key(&self,key:&[u8]) -> DbResult<bool> {
+            Ok(self.get(key)?.is_some())
+        }
+
+        pub fn len(&self) -> DbResult<usize> {
+            Ok(self.params()?.count as usize)
         }
 
-        fn new_lzip(&self,name:Vec<u8>) -> TupleList {
-            TupleList::new(self.db.clone(), format!("lzip:{}:{}",self.hashlist.name,String::from_utf8(name).unwrap()), 2).unwrap()
+        pub fn is_empty(&self) -> DbResult<bool> {
+            Ok(self.len()? == 0)
+        }
+
+        pub fn buckets(&self) -> DbResult<usize> {
+            Ok(self.layout()?.buckets() as usize)
+        }
+
+        pub fn rehash(&self) -> DbResult<bool> {
+            // 用固定的哈希函数(SipHash13)重建旧哈希函数建立的哈希表,已经是固定哈希函数时什么都不做
+            // 返回是否重建;整个重建在一个事务中完成,所有条目会暂存在内存中
+            self.db.transaction(|tx| {
+                let table = self.bind(tx);
+                let params = table.params()?;
+                if params.hasher == HashAlgorithm::SipHash13 {
+                    return Ok(false)
+                }
+                let mut entries = vec![];
+                for bucket in 0..table.hashlist.length()? {
+                    if let Some(lzip) = table.find_lzip(bucket as u64)? {
+                        entries.extend(Self::lzip_entries(&lzip)?);
+                        lzip.destroy()?;
+                    }
+                }
+                let old = table.hashlist.metadata()?;
+                table.hashlist.destroy()?;
+                let meta = Metadata { created_at: old.created_at, ..Metadata::new(CollectionKind::Hashtable, 0) }; // 保留创建时间
+                table.write_params(meta, &TableParams { load_factor: params.load_factor, ..TableParams::new() })?;
+                for (key, value) in entries {
+                    table.insert(&key, &value)?;
+                }
+                Ok(true)
+            })
+        }
+
+        fn new_lzip(&self,name:Vec<u8>) -> DbResult<TupleList> {
+            let name = String::from_utf8(name).map_err(|e| DbError::CorruptMetadata(format!("Hashtable chain name: {e}")))?;
+            TupleList::new_with_kind(self.db.clone(), format!("{}:{}",self.hashlist.name,name), 2, CollectionKind::HashtableChain)
         }
     }
 
     #[test]
     fn test_hashtable(){
-        use crate::sdk::db::kv_operation::KvDbOperaTrait;
-        let a = Hashtable::open(KvDbOperaObject::new(initialization("/tmp/welcome-to-sled".to_string())),"1]&_+3)_~*-1)4".to_string());
-        dbg!(&a.insert(&"lst".to_string(), &vec![1, 5, 2]));
-        dbg!(&a.get(&"lst".to_string()));
-        dbg!(&a.insert(&"I li".to_string(), &vec![1, 1, 4, 5, 1, 4]));
+        use crate::sdk::db::kv_operation::{temporary, KvDbOperaTrait};
+        let a = Hashtable::new(KvDbOperaObject::new(temporary()),"1]&_+3)_~*-1)4".to_string()).unwrap();
+        dbg!(&a.insert(b"lst", &[1, 5, 2]));
+        assert_eq!(a.get(b"lst"), Ok(Some(vec![1, 5, 2])));
+        dbg!(&a.insert(b"I li", &[1, 1, 4, 5, 1, 4]));
 
         dbg!(&a.to_tuple_list(Some(10usize)));
-        dbg!(&a.delete(&"lst".to_string()));
-        dbg!(&a.get(&"lst".to_string()));
+        dbg!(&a.delete(b"lst"));
+        assert_eq!(a.get(b"lst"), Ok(None));
+        assert!(matches!(a.delete(b"lst"), Err(DbError::NotFound(_))));
+
+        a.insert(b"I li", &[0]).unwrap(); // 覆写已有的键不改变条目个数
+        assert_eq!(a.len(), Ok(1));
+        for i in 0..20 {
+            a.insert(i.to_string().as_bytes(), &[i]).unwrap();
+        }
+        assert_eq!(a.len(), Ok(21));
+        let forward = a.keys().unwrap().collect::<DbResult<Vec<_>>>().unwrap();
+        let mut backward = a.keys().unwrap().rev().collect::<DbResult<Vec<_>>>().unwrap();
+        backward.reverse();
+        assert_eq!((forward.len(), &forward), (21, &backward));
+        assert_eq!((a.contains_key(b"19"), a.contains_key(b"20")), (Ok(true), Ok(false)));
+
+        let items = (20..60u8).map(|i| (i.to_string(), vec![i])).collect::<Vec<_>>();
+        a.insert_many(&items).unwrap();
+        assert_eq!(a.len(), Ok(61));
+        assert_eq!(a.get_many(&["25", "60"]), Ok(vec![Some(vec![25]), None]));
+        let keys = (0..70u8).map(|i| i.to_string()).collect::<Vec<_>>();
+        assert_eq!(a.delete_many(&keys), Ok(60));
+        assert_eq!(a.len(), Ok(1));
+    }
+
+    #[test]
+    fn test_linear_hashing(){
+        use crate::sdk::db::kv_operation::{temporary, KvDbOperaTrait};
+        let db = KvDbOperaObject::new(temporary());
+        let a = Hashtable::new(db.clone(), "grow".to_string()).unwrap();
+        a.set_load_factor(100).unwrap();
+        for i in 0..1000u32 {
+            a.insert(i.to_string().as_bytes(), &i.to_be_bytes()).unwrap();
+        }
+        let params = a.params().unwrap();
+        assert_eq!((params.count, params.buckets()), (1000, 1000)); // 每次插入最多分裂一个桶
+        assert!(params.level >= 5);
+        for i in (0..1000u32).step_by(7) {
+            assert_eq!(a.get(i.to_string().as_bytes()), Ok(Some(i.to_be_bytes().to_vec())));
+        }
+        assert_eq!(a.iter().unwrap().count(), 1000);
+        for i in 0..1000u32 {
+            a.delete(i.to_string().as_bytes()).unwrap();
+        }
+        assert_eq!(a.len(), Ok(0));
+
+        // 旧版本的哈希表: 10000 个桶,拉链以第一个键命名
+        let legacy = ListDb::new_with_kind(db.clone(), "legacy".to_string(), CollectionKind::Hashtable, 0).unwrap();
+        legacy.safety_overwrite((HashAlgorithm::StdDefault.hash(b"old") % LEGACY_BUCKETS) as usize, b"old").unwrap();
+        TupleList::new_with_kind(db.clone(), "legacy:old".to_string(), 2, CollectionKind::HashtableChain).unwrap()
+            .append(&[b"old", b"value"]).unwrap();
+        let b = Hashtable::new(db.clone(), "legacy".to_string()).unwrap();
+        assert_eq!(b.get(b"old"), Ok(Some(b"value".to_vec())));
+        assert_eq!((b.len(), b.buckets()), (Ok(1), Ok(10000)));
+        b.insert(b"new", b"1").unwrap();
+        assert_eq!((b.get(b"new"), b.len()), (Ok(Some(b"1".to_vec())), Ok(2)));
+
+        // 重建后使用固定的哈希函数,旧的拉链被删除
+        assert_eq!(b.params().map(|p| p.hasher), Ok(HashAlgorithm::StdDefault));
+        assert_eq!((b.rehash(), b.rehash()), (Ok(true), Ok(false)));
+        assert_eq!(b.params().map(|p| (p.hasher, p.count, p.initial)), Ok((HashAlgorithm::SipHash13, 2, DEFAULT_INITIAL_BUCKETS)));
+        assert_eq!((b.get(b"old"), b.get(b"new")), (Ok(Some(b"value".to_vec())), Ok(Some(b"1".to_vec()))));
+        assert!(ListDb::open_with_kind(db.clone(), "legacy:old".to_string(), CollectionKind::HashtableChain).is_err());
+        // SipHash-1-3 的结果是固定的,不随编译器版本改变
+        assert_eq!(HashAlgorithm::SipHash13.hash(b""), 0xd1fba762150c532c);
+    }
+
+    #[test]
+    fn test_bucket_compaction(){
+        use crate::sdk::db::kv_operation::{temporary, KvDbOperaTrait};
+        let db = KvDbOperaObject::new(temporary());
+        let a = Hashtable::new(db.clone(), "compact".to_string()).unwrap();
+        a.set_load_factor(100000).unwrap(); // 不分裂,让每个桶里有很多条目
+        for i in 0..200u32 {
+            a.insert(i.to_string().as_bytes(), &i.to_be_bytes()).unwrap();
+        }
+        for i in (0..200u32).filter(|i| i % 3 != 0) {
+            a.delete(i.to_string().as_bytes()).unwrap();
+        }
+        for bucket in 0..a.buckets().unwrap() as u64 {
+            if let Some(lzip) = a.find_lzip(bucket).unwrap() {
+                // 删除后拉链中没有空位
+                assert_eq!(lzip.length().unwrap(), Hashtable::lzip_entries(&lzip).unwrap().len());
+            }
+        }
+        assert_eq!(a.to_tuple_list(None).map(|v| v.len()), Ok(67));
+        for i in (0..200u32).step_by(3) {
+            assert_eq!(a.get(i.to_string().as_bytes()), Ok(Some(i.to_be_bytes().to_vec())));
+            a.delete(i.to_string().as_bytes()).unwrap();
+        }
+        // 空的拉链全部被回收
+        assert_eq!(a.hashlist.count(), Ok(0));
+        assert_eq!(db.scan_prefix([CollectionKind::HashtableBucket as u8]).count(), 0);
+        a.insert(b"again", b"1").unwrap();
+        assert_eq!(a.get(b"again"), Ok(Some(b"1".to_vec())));
+    }
+
+    #[test]
+    fn test_concurrent_insert(){
+        // 多个线程同时插入并分裂桶,写入不能丢失,读取不能看到分裂到一半的状态
+        use crate::sdk::db::kv_operation::{temporary, KvDbOperaTrait};
+        let db = KvDbOperaObject::new(temporary());
+        let a = Hashtable::new(db, "concurrent".to_string()).unwrap();
+        let fixed = (0..50u32).map(|i| format!("fixed-{i}").into_bytes()).collect::<Vec<_>>();
+        for key in &fixed {
+            a.insert(key, b"v").unwrap();
+        }
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let reader = { // 不开事务的读取在分裂期间也能找到已有的键
+            let (a, fixed, done) = (a.clone(), fixed.clone(), done.clone());
+            std::thread::spawn(move || while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                for key in &fixed {
+                    assert_eq!(a.get(key), Ok(Some(b"v".to_vec())));
+                }
+                assert!(a.get_many(&fixed).unwrap().iter().all(|v| v.as_deref() == Some(&b"v"[..])));
+            })
+        };
+        let workers = (0..4u32).map(|n| {
+            let a = a.clone();
+            std::thread::spawn(move || {
+                for i in 0..150u32 {
+                    let key = format!("{n}-{i}");
+                    a.insert(key.as_bytes(), &i.to_be_bytes()).unwrap();
+                    assert_eq!(a.get(key.as_bytes()), Ok(Some(i.to_be_bytes().to_vec())));
+                }
+            })
+        }).collect::<Vec<_>>();
+        for w in workers {
+            w.join().unwrap();
+        }
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        reader.join().unwrap();
+        assert_eq!(a.len(), Ok(650));
+        assert_eq!(a.iter().unwrap().count(), 650);
     }
 }
@@ -0,0 +1,59 @@
+// 数据库错误类型
+// sdk 中所有公开的操作都返回 DbResult , 以便调用者区分不同的失败原因
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbError {
+    Storage(String), // sled 储存引擎错误(I/O 等)
+    NotFound(String), // 键或集合不存在
+    OutOfBounds { index: usize, length: usize }, // 索引越界
+    CorruptMetadata(String), // 元数据(列表长度等)损坏,无法解析
+    TupleArity { expected: usize, found: usize }, // 元组长度与元组列表不匹配
+    InvalidIndexPath(String), // IndexVec 路径格式错误
+    ArchiveNotFound(String), // 档案不存在
+    TransactionConflict, // 事务读取的数据在提交前被其他人修改
+    TransactionClosed, // 事务已经提交或回滚
+    Closed, // 数据库已经关闭
+    NameConflict(String), // 名字已被使用,或者同名的集合有多个而没有指定种类
+}
+
+pub type DbResult<T> = Result<T, DbError>;
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Storage(e) => write!(f, "storage error: {e}"),
+            DbError::NotFound(what) => write!(f, "not found: {what}"),
+            DbError::OutOfBounds { index, length } => {
+                write!(f, "index {index} out of range for length {length}")
+            }
+            DbError::CorruptMetadata(e) => write!(f, "corrupt metadata: {e}"),
+            DbError::TupleArity { expected, found } => {
+                write!(f, "tuple arity mismatch: expected {expected}, found {found}")
+            }
+            DbError::InvalidIndexPath(e) => write!(f, "invalid index path: {e}"),
+            DbError::ArchiveNotFound(name) => write!(f, "archive not found: {name}"),
+            DbError::TransactionConflict => write!(f, "transaction conflict"),
+            DbError::TransactionClosed => write!(f, "transaction already committed or rolled back"),
+            DbError::Closed => write!(f, "database is closed"),
+            DbError::NameConflict(e) => write!(f, "name conflict: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<sled::transaction::TransactionError<DbError>> for DbError {
+    fn from(e: sled::transaction::TransactionError<DbError>) -> Self {
+        match e {
+            sled::transaction::TransactionError::Abort(e) => e,
+            sled::transaction::TransactionError::Storage(e) => e.into(),
+        }
+    }
+}
+
+impl From<sled::Error> for DbError {
+    fn from(e: sled::Error) -> Self {
+        DbError::Storage(format!("{e:?}"))
+    }
+}
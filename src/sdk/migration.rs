@@ -0,0 +1,237 @@
+// 旧版本数据库的一次性迁移工具
+// 旧版本的键是 "List:{name}" / "List:{name}:{index}" / "archive:{name}" 这样的字符串,
+// 这里把它们改写为 key_codec 的二进制键,旧格式的元数据改写为新格式,然后登记到目录中
+use std::collections::{BTreeMap, BTreeSet};
+use crate::sdk::archive;
+use crate::sdk::catalog;
+use crate::sdk::db::key_codec;
+use crate::sdk::db::hashtable_zipper_db::Hashtable;
+use crate::sdk::db::kv_operation::{KvDbOperaObject, KvDbOperaTrait};
+use crate::sdk::db::list_db::ListDb;
+use crate::sdk::db::metadata::{CollectionKind, Decoded, Metadata};
+use crate::sdk::error::DbResult;
+
+const LIST_PREFIX: &[u8] = b"List:";
+const ARCHIVE_PREFIX: &[u8] = b"archive:";
+
+type LegacyElements = Vec<(Vec<u8>, usize)>; // (旧键, index)
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    pub collections: usize, // 迁移的列表类集合个数
+    pub archives: usize, // 迁移的档案个数
+    pub keys: usize, // 迁移的键总数
+    pub metadata: usize, // 改写为新格式的元数据个数
+    pub catalogued: usize, // 新登记到目录中的集合个数
+    pub tagged: usize, // 中间索引改写为带标记格式的档案个数
+    pub skipped: Vec<Vec<u8>>, // 无法识别而没有迁移的旧键
+}
+
+fn legacy_collection(name: &str) -> (CollectionKind, String) {
+    // 旧版本用名字前缀区分集合种类
+    if let Some(rest) = name.strip_prefix("Tuple:lzip:HashtableHashlist:") {
+        (CollectionKind::HashtableChain, rest.to_string())
+    } else if let Some(rest) = name.strip_prefix("Tuple:") {
+        (CollectionKind::TupleList, rest.to_string())
+    } else if let Some(rest) = name.strip_prefix("HashtableHashlist:") {
+        (CollectionKind::Hashtable, rest.to_string())
+    } else {
+        (CollectionKind::List, name.to_string())
+    }
+}
+
+fn split_element_key<'a>(rest: &'a [u8], keys: &BTreeSet<Vec<u8>>) -> Option<(&'a [u8], usize)> {
+    // "List:{name}:{index}" 中的 name 可能也含有 ':',只有 "List:{name}" 存在时才认为这是一个元素
+    let pos = rest.iter().rposition(|b| *b == b':')?;
+    let index = std::str::from_utf8(&rest[pos + 1..]).ok()?.parse::<usize>().ok()?;
+    let name = &rest[..pos];
+    keys.contains(&[LIST_PREFIX, name].concat()).then_some((name, index))
+}
+
+pub fn migrate_string_keys(db: &KvDbOperaObject) -> DbResult<MigrationReport> {
+    let mut report = MigrationReport::default();
+
+    let mut keys = BTreeSet::new();
+    for kv in db.scan_prefix(LIST_PREFIX) {
+        keys.insert(kv?.0.to_vec());
+    }
+    // 先找出所有列表,再把元素归到各自的列表下
+    let mut lists: BTreeMap<Vec<u8>, LegacyElements> = BTreeMap::new();
+    let mut elements: Vec<(Vec<u8>, Vec<u8>, usize)> = vec![];
+    for key in &keys {
+        let rest = &key[LIST_PREFIX.len()..];
+        match split_element_key(rest, &keys) {
+            Some((name, index)) => elements.push((name.to_vec(), key.clone(), index)),
+            None => {
+                let value = db.get(key)?.unwrap_or_default();
+                match (std::str::from_utf8(rest), Metadata::decode(&value)) {
+                    (Ok(_), Ok(_)) => { lists.insert(rest.to_vec(), vec![]); },
+                    _ => report.skipped.push(key.clone()),
+                }
+            }
+        }
+    }
+    for (name, key, index) in elements {
+        match lists.get_mut(&name) {
+            Some(items) => items.push((key, index)),
+            None => report.skipped.push(key),
+        }
+    }
+
+    for (name, items) in lists {
+        let name = String::from_utf8(name).unwrap_or_default(); // 上面已经检查过是 UTF-8
+        let (kind, new_name) = legacy_collection(&name);
+        let list = ListDb { db: db.clone(), name: new_name, kind };
+        let old_meta = [LIST_PREFIX, name.as_bytes()].concat();
+        db.transaction(|tx| {
+            // 每个集合在一个事务中迁移
+            if let Some(v) = tx.get(&old_meta)? {
+                tx.insert(list.meta_key(), v)?;
+                tx.delete(&old_meta)?;
+            }
+            for (key, index) in &items {
+                if let Some(v) = tx.get(key)? {
+                    tx.insert(list.get_key(*index), v)?;
+                    tx.delete(key)?;
+                }
+            }
+            Ok(())
+        })?;
+        report.collections += 1;
+        report.keys += items.len() + 1;
+    }
+
+    for kv in db.scan_prefix(ARCHIVE_PREFIX) {
+        let (key, value) = kv?;
+        match std::str::from_utf8(&key[ARCHIVE_PREFIX.len()..]) {
+            Ok(name) => {
+                let new_key = key_codec::archive_key(name);
+                db.transaction(|tx| {
+                    tx.insert(&new_key, value.clone())?;
+                    tx.delete(&key)
+                })?;
+                report.archives += 1;
+                report.keys += 1;
+            }
+            Err(_) => report.skipped.push(key.to_vec()),
+        }
+    }
+    report.metadata = upgrade_metadata(db)?;
+    report.tagged = archive::upgrade_legacy(db)?;
+    report.catalogued = catalog::register_existing(db)?;
+    Ok(report)
+}
+
+fn upgrade_metadata(db: &KvDbOperaObject) -> DbResult<usize> {
+    // 旧格式的元数据(十进制字符串长度)改写为新格式,返回改写的个数
+    // 旧格式没有记录创建时间和元组长度,元组长度在第一次打开元组列表时补上
+    let mut lists = vec![];
+    for kind in [CollectionKind::List, CollectionKind::TupleList, CollectionKind::Hashtable, CollectionKind::HashtableChain] {
+        for kv in db.scan_prefix([key_codec::kind_tag(kind)]) {
+            let (key, value) = kv?;
+            if let (Some((name, [0x00])), Ok(Decoded::Legacy(_))) = (key_codec::unescape(&key[1..]), Metadata::decode(&value)) {
+                if let Ok(name) = String::from_utf8(name) {
+                    lists.push(ListDb { db: db.clone(), name, kind });
+                }
+            }
+        }
+    }
+    for list in &lists {
+        db.transaction(|tx| {
+            let list = list.bind(tx);
+            let Some(Ok(Decoded::Legacy(length))) = tx.get(list.meta_key())?.map(|v| Metadata::decode(&v)) else { return Ok(()) };
+            let mut meta = Metadata::new(list.kind, 0);
+            meta.created_at = 0;
+            meta.length = length as u64;
+            for kv in tx.scan_prefix(key_codec::elements_prefix(&list.prefix())) { // 统计实际储存的元素个数
+                if key_codec::element_index(&list.prefix(), &kv?.0).is_some_and(|i| i < meta.length) {
+                    meta.count += 1;
+                }
+            }
+            list.write_metadata(&meta)
+        })?;
+    }
+    Ok(lists.len())
+}
+
+pub fn rehash_hashtables(db: &KvDbOperaObject) -> DbResult<usize> {
+    // 用固定的哈希函数重建所有旧哈希函数建立的哈希表,返回重建的个数
+    let mut names = vec![];
+    for kv in db.scan_prefix([key_codec::kind_tag(CollectionKind::Hashtable)]) {
+        let (key, _) = kv?;
+        if let Some((name, [0x00])) = key_codec::unescape(&key[1..]) { // 只看元数据键
+            if let Ok(name) = String::from_utf8(name) {
+                names.push(name);
+            }
+        }
+    }
+    let mut rehashed = 0;
+    for name in names {
+        if Hashtable::open(db.clone(), name)?.rehash()? {
+            rehashed += 1;
+        }
+    }
+    Ok(rehashed)
+}
+
+#[test]
+fn migrate_test() {
+    use crate::sdk::archive::{Archive, IVE};
+    use crate::sdk::catalog::Catalog;
+    use crate::sdk::db::kv_operation::temporary;
+    let db = KvDbOperaObject::new(temporary());
+    // 旧版本写入的数据: 列表 "a" 中有 0 和 10 两个元素,列表 "b:x" 的名字中含有 ':'
+    for (k, v) in [
+        ("List:a", "11"), ("List:a:0", "x"), ("List:a:10", "y"), ("List:b:x", "0"),
+        ("List:HashtableHashlist:t", "1"), ("List:HashtableHashlist:t:0", "k"),
+        ("List:Tuple:lzip:HashtableHashlist:t:k", "2"), ("List:Tuple:lzip:HashtableHashlist:t:k:0", "k"), ("List:Tuple:lzip:HashtableHashlist:t:k:1", "v"),
+        ("archive:ar", "head"), ("List:Tuple:head", "2"), ("List:Tuple:head:1", "data"),
+    ] {
+        db.insert(k, v).unwrap();
+    }
+    let report = migrate_string_keys(&db).unwrap();
+    assert_eq!((report.collections, report.archives, report.keys), (5, 1, 12));
+    assert!(report.skipped.is_empty());
+    assert_eq!(db.scan_prefix("List:").count(), 0);
+    // 元数据已经改写为新格式,不是 uuid 命名的集合和档案都登记到了目录中
+    assert_eq!((report.metadata, report.catalogued), (5, 5));
+    let meta_key = |kind, name| key_codec::meta_key(&key_codec::prefix(key_codec::kind_tag(kind), name));
+    assert_eq!(db.get(meta_key(CollectionKind::List, "a")).unwrap().map(|v| v[0]), Some(0xB7));
+    assert_eq!(db.collections().unwrap().len(), 5);
+
+    let a = ListDb::open(db.clone(), "a".to_string()).unwrap();
+    assert_eq!((a.length(), a.access(10)), (Ok(11), Ok(Some(b"y".to_vec()))));
+    assert_eq!(a.count(), Ok(2));
+    assert_eq!(ListDb::open(db.clone(), "b:x".to_string()).unwrap().length(), Ok(0));
+    let t = Hashtable::open(db.clone(), "t".to_string()).unwrap();
+    assert_eq!(t.len(), Ok(1));
+    let chain = ListDb::open_with_kind(db.clone(), "t:k".to_string(), CollectionKind::HashtableChain).unwrap();
+    assert_eq!(chain.access(1), Ok(Some(b"v".to_vec())));
+    let archive = Archive::open_object(db.clone(), "ar".to_string()).unwrap();
+    let ear = archive.open(vec![IVE::HeadMarking, IVE::TupleList(0, 1, 2)]).unwrap();
+    assert_eq!(ear.access(), Ok(Some(b"data".to_vec())));
+    assert_eq!(report.tagged, 1);
+    assert_eq!(migrate_string_keys(&db).map(|r| (r.keys, r.tagged)), Ok((0, 0))); // 只需要迁移一次
+
+    // 中间层储存没有标记的 uuid 的档案: uuid 改为带标记的索引,以 0xFF 开头的数据加上转义,看起来像 uuid 的数据保持不变
+    let (head, level) = ("9e1b5c4e-0a3f-4a57-9a4c-2f0d3c1e8b71", "5b0f3c2a-7d6e-4f1a-8c9b-1a2b3c4d5e6f");
+    let user_id = "0d9c8b7a-6f5e-4d3c-2b1a-0f9e8d7c6b5a";
+    db.insert(key_codec::archive_key("old"), head).unwrap();
+    let top = Hashtable::new(db.clone(), head.to_string()).unwrap();
+    top.insert(b"users", level.as_bytes()).unwrap();
+    top.insert(b"id", user_id.as_bytes()).unwrap();
+    ListDb::new(db.clone(), level.to_string()).unwrap().extend(&[&[0xFF, 1][..], b"x"]).unwrap();
+    assert!(Archive::open_object(db.clone(), "old".to_string()).is_err()); // 迁移之前不能打开
+    assert_eq!(migrate_string_keys(&db).map(|r| r.tagged), Ok(1));
+    let archive = Archive::open_object(db.clone(), "old".to_string()).unwrap();
+    assert_eq!(archive.open("/h:users/l:0").unwrap().access(), Ok(Some(vec![0xFF, 1])));
+    assert_eq!(archive.open("/h:users/l:1").unwrap().access(), Ok(Some(b"x".to_vec())));
+    assert_eq!(archive.open("/h:id").unwrap().access(), Ok(Some(user_id.as_bytes().to_vec())));
+    assert!(matches!(archive.open_or_new("/h:id/h:x"), Err(crate::sdk::error::DbError::InvalidIndexPath(_))));
+    assert_eq!(crate::sdk::archive::gc(&db, false).map(|r| r.removed.len()), Ok(0));
+
+    Hashtable::new(db.clone(), "fresh".to_string()).unwrap();
+    assert_eq!(rehash_hashtables(&db), Ok(1)); // 只有旧的 "t" 需要重建
+    assert_eq!(t.get(b"k"), Ok(Some(b"v".to_vec())));
+    assert_eq!(rehash_hashtables(&db), Ok(0));
+}
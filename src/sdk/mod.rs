@@ -1,8 +1,12 @@
 pub mod db;
 pub mod archive;
+pub mod error;
+pub mod migration;
+pub mod catalog;
 
-fn string_to_usize(number_str:String) -> usize{
+use error::{DbError, DbResult};
+
+fn string_to_usize(number_str:&str) -> DbResult<usize>{
     // dbg!(&number_str);
-    number_str.parse::<usize>().expect("String parsing to usize failed")
+    number_str.parse::<usize>().map_err(|e| DbError::CorruptMetadata(format!("String parsing to usize failed: {e}")))
 }
-
@@ -1,4 +1,3 @@
-use sled::IVec;
 
 #[test]
 fn sled_test(){
@@ -6,7 +5,7 @@ fn sled_test(){
     let tree = &sled::open("/tmp/welcome-to-sled").expect("open");
 
 // 插入KV，读取Key对应的值
-    tree.insert([1,3,5,7,9], &*"Hah").expect("TODO: panic message");
+    tree.insert([1,3,5,7,9], "Hah").expect("TODO: panic message");
     // assert_eq!(tree.get(&"KEY1"), Ok(Some(sled::IVec::from("VAL1"))));
     let a = tree.get([1,3,5,7,9]).unwrap().unwrap();
     dbg!(&String::from_utf8(a.to_vec()));